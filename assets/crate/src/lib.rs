@@ -1,28 +1,467 @@
 use once_cell::sync::Lazy;
 
+#[cfg(feature = "bind")]
+use serde::Serialize;
 #[cfg(feature = "bind")]
 use serde_wasm_bindgen;
 #[cfg(feature = "bind")]
 use wasm_bindgen::prelude::*;
 
-use tinysearch::{search as base_search, Filters, PostId, Storage};
+#[cfg(all(feature = "bind", feature = "result-fields"))]
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use tinysearch::{
+    search_indexes, search_shards, search_shards_with_details, LabeledResult, Page, PostId,
+    SearchOptions, SearchResult, SearchResults, Storage,
+};
 
 #[cfg(feature = "bind")]
 #[global_allocator]
 static ALLOC: wee_alloc::WeeAlloc = wee_alloc::WeeAlloc::INIT;
 
-static FILTERS: Lazy<Filters> = Lazy::new(|| {
+/// The filters of the posts are queried in place from the embedded bytes, so only the
+/// posts are decoded on the first search
+#[cfg(not(feature = "shards"))]
+static STORAGE: Lazy<Storage> = Lazy::new(|| {
     let bytes = include_bytes!("storage");
-    Storage::from_bytes(bytes).unwrap().filters
+    Storage::from_static_bytes(bytes).unwrap()
 });
 
+/// Shards of the index loaded so far, in order
+#[cfg(feature = "shards")]
+static SHARDS: Lazy<Mutex<Vec<&'static Storage>>> = Lazy::new(Default::default);
+
+/// Other indexes loaded by name, see `load_index_local`
+static INDEXES: Lazy<Mutex<Vec<(&'static str, &'static Storage)>>> = Lazy::new(Default::default);
+
+/// Name of the index built into the module among the results of `search_all_local`
+pub const DEFAULT_INDEX: &str = "default";
+
+/// Content hash of the index, as written to `storage.hash`. It isn't embedded when the
+/// index is loaded at runtime, so that the module doesn't change along with the index.
+#[cfg(not(feature = "shards"))]
+pub const STORAGE_HASH: &str = include_str!("storage.hash");
+
+/// Version of the functions the module exports to JavaScript. It's increased when an
+/// export changes or goes away, but not when one is added. Modules built before it
+/// existed don't export `abi_version` at all.
+pub const ABI_VERSION: u32 = 1;
+
+/// Results include the meta field of posts
+pub const CAPABILITY_META: u32 = 1 << 0;
+/// The index is fetched at runtime and added with `load_shard`
+pub const CAPABILITY_SHARDS: u32 = 1 << 1;
+/// The index supports fuzzy search, see `--fuzzy`
+pub const CAPABILITY_FUZZY: u32 = 1 << 2;
+/// The index has facets to filter by, see `--facet-field`
+pub const CAPABILITY_FACETS: u32 = 1 << 3;
+/// The index has dates to sort by, see `--date-field`
+pub const CAPABILITY_DATES: u32 = 1 << 4;
+/// The index has related terms to suggest queries with, see `--related-terms`
+pub const CAPABILITY_RELATED: u32 = 1 << 5;
+
+/// What the module and the index loaded so far support, as `CAPABILITY_*` bits
+pub fn capabilities_local() -> u32 {
+    let mut capabilities = 0;
+    if cfg!(feature = "meta") {
+        capabilities |= CAPABILITY_META;
+    }
+    if cfg!(feature = "shards") {
+        capabilities |= CAPABILITY_SHARDS;
+    }
+    for storage in shards() {
+        if storage.typos.is_some() {
+            capabilities |= CAPABILITY_FUZZY;
+        }
+        if !storage.facets.is_empty() {
+            capabilities |= CAPABILITY_FACETS;
+        }
+        if !storage.dates.is_empty() {
+            capabilities |= CAPABILITY_DATES;
+        }
+        if !storage.related.is_empty() {
+            capabilities |= CAPABILITY_RELATED;
+        }
+    }
+    capabilities
+}
+
+#[cfg(not(feature = "shards"))]
+fn shards() -> Vec<&'static Storage> {
+    vec![&*STORAGE]
+}
+
+#[cfg(feature = "shards")]
+fn shards() -> Vec<&'static Storage> {
+    SHARDS.lock().unwrap().clone()
+}
+
+/// Add the next shard of the index. Shards must be loaded in order, and are kept
+/// for the lifetime of the module along with their bytes, which their filters are
+/// queried from.
+#[cfg(feature = "shards")]
+pub fn load_shard_local(bytes: &[u8]) -> Result<(), tinysearch::StorageError> {
+    let bytes = Box::leak(bytes.to_vec().into_boxed_slice());
+    let storage = Box::leak(Box::new(Storage::from_static_bytes(bytes)?));
+    SHARDS.lock().unwrap().push(storage);
+    Ok(())
+}
+
+/// Add a storage built separately, e.g. with `tinysearch -m storage`, under the given
+/// name. It is only searched by `search_all_local`, and kept for the lifetime of the module.
+pub fn load_index_local(name: &str, bytes: &[u8]) -> Result<(), tinysearch::StorageError> {
+    let bytes = Box::leak(bytes.to_vec().into_boxed_slice());
+    let storage = Box::leak(Box::new(Storage::from_static_bytes(bytes)?));
+    let name = Box::leak(name.to_string().into_boxed_str());
+    INDEXES.lock().unwrap().push((name, storage));
+    Ok(())
+}
+
+/// Search the index of the module (as `DEFAULT_INDEX`) and all indexes loaded with
+/// `load_index_local`, merging their results
+pub fn search_all_local(
+    query: String,
+    num_results: usize,
+    options: &SearchOptions,
+) -> Vec<LabeledResult<'static>> {
+    let shards = shards();
+    let loaded = INDEXES.lock().unwrap().clone();
+    let loaded_shards: Vec<[&'static Storage; 1]> =
+        loaded.iter().map(|(_, storage)| [*storage]).collect();
+    let mut indexes: Vec<(&'static str, &[&'static Storage])> = vec![(DEFAULT_INDEX, &shards)];
+    for ((name, _), shards) in loaded.iter().zip(&loaded_shards) {
+        indexes.push((name, shards));
+    }
+    search_indexes(&indexes, query, num_results, options)
+}
+
+/// Search the index. A `num_results` of 0 returns all matches.
 pub fn search_local(query: String, num_results: usize) -> Vec<&'static PostId> {
-    base_search(&FILTERS, query, num_results)
+    search_with_scores_local(query, num_results)
+        .into_iter()
+        .map(|(post_id, _)| post_id)
+        .collect()
+}
+
+/// Like `search_local`, but returns the relevance score of each post as well
+pub fn search_with_scores_local(query: String, num_results: usize) -> Vec<(&'static PostId, f32)> {
+    search_with_options_local(query, num_results, &SearchOptions::default())
+}
+
+/// Search with [`SearchOptions`], returning posts along with their scores
+pub fn search_with_options_local(
+    query: String,
+    num_results: usize,
+    options: &SearchOptions,
+) -> Vec<(&'static PostId, f32)> {
+    search_with_deadline_local(query, num_results, options, || false).results
+}
+
+/// Like `search_with_options_local`, but stops scoring posts once `expired` returns true
+pub fn search_with_deadline_local(
+    query: String,
+    num_results: usize,
+    options: &SearchOptions,
+    expired: impl Fn() -> bool,
+) -> SearchResults<'static> {
+    search_shards(&shards(), query, num_results, options, expired)
+}
+
+/// Like `search_with_options_local`, but returns the `page`-th page of `per_page`
+/// results, counting from 1, along with the number of all matches
+pub fn search_paginated_local(
+    query: String,
+    page: usize,
+    per_page: usize,
+    options: &SearchOptions,
+) -> Page<'static> {
+    Page::of(search_with_options_local(query, 0, options), page, per_page)
+}
+
+/// Like `search_with_options_local`, but also returns the query terms each post matched
+pub fn search_with_details_local(
+    query: String,
+    num_results: usize,
+    options: &SearchOptions,
+) -> Vec<SearchResult<'static>> {
+    search_shards_with_details(&shards(), query, num_results, options, || false)
+}
+
+/// Up to `num_results` posts of the index in the order they were indexed, without a
+/// query. A `num_results` of 0 returns all posts.
+pub fn documents_local(num_results: usize) -> Vec<&'static PostId> {
+    let documents = shards().into_iter().flat_map(|storage| storage.documents());
+    match num_results {
+        0 => documents.collect(),
+        n => documents.take(n).collect(),
+    }
+}
+
+/// Queries related to `query`, see `Storage::related_queries`. Each shard has related
+/// terms of its own posts, so the suggestions of all of them are merged in order.
+pub fn related_queries_local(query: &str) -> Vec<String> {
+    let mut queries: Vec<String> = Vec::new();
+    for related in shards()
+        .iter()
+        .flat_map(|storage| storage.related_queries(query))
+    {
+        if !queries.contains(&related) {
+            queries.push(related);
+        }
+    }
+    queries.truncate(tinysearch::MAX_RELATED_QUERIES);
+    queries
+}
+
+/// Convert search results for JavaScript, with the meta of posts as plain objects
+#[cfg(feature = "bind")]
+fn to_js<T: Serialize>(results: &T) -> Result<JsValue, serde_wasm_bindgen::Error> {
+    results.serialize(&serde_wasm_bindgen::Serializer::new().serialize_maps_as_objects(true))
+}
+
+/// Meta fields included in search results, one per line, see `--result-field`
+#[cfg(all(feature = "bind", feature = "result-fields"))]
+static RESULT_FIELDS: Lazy<Vec<&'static str>> =
+    Lazy::new(|| include_str!("result_fields").lines().collect());
+
+/// Search results passed to JavaScript are `[title, url, meta]` arrays
+#[cfg(all(feature = "bind", feature = "meta", not(feature = "result-fields")))]
+fn to_result(post_id: &'static PostId) -> &'static PostId {
+    post_id
+}
+
+/// Search results passed to JavaScript are `[title, url, meta]` arrays, with only the
+/// meta fields in `RESULT_FIELDS`
+#[cfg(all(feature = "bind", feature = "result-fields"))]
+fn to_result(
+    post_id: &'static PostId,
+) -> (
+    &'static str,
+    &'static str,
+    BTreeMap<&'static str, &'static str>,
+) {
+    let meta = post_id
+        .2
+        .iter()
+        .filter(|(field, _)| RESULT_FIELDS.contains(&field.as_str()))
+        .map(|(field, value)| (field.as_str(), value.as_str()))
+        .collect();
+    (&post_id.0, &post_id.1, meta)
+}
+
+/// Search results passed to JavaScript are `[title, url]` arrays
+#[cfg(all(feature = "bind", not(feature = "meta")))]
+fn to_result(post_id: &'static PostId) -> (&'static str, &'static str) {
+    (&post_id.0, &post_id.1)
 }
 
 #[cfg(feature = "bind")]
 #[wasm_bindgen]
 pub fn search(query: String, num_results: usize) -> JsValue {
-    serde_wasm_bindgen::to_value(&search_local(query, num_results))
-        .expect("failed to serialize search result")
+    let results: Vec<_> = search_local(query, num_results)
+        .into_iter()
+        .map(to_result)
+        .collect();
+    to_js(&results).expect("failed to serialize search result")
+}
+
+#[cfg(feature = "bind")]
+#[wasm_bindgen]
+pub fn search_with_scores(query: String, num_results: usize) -> JsValue {
+    let results: Vec<_> = search_with_scores_local(query, num_results)
+        .into_iter()
+        .map(|(post_id, score)| (to_result(post_id), score))
+        .collect();
+    to_js(&results).expect("failed to serialize search result")
+}
+
+/// Posts without a query, as `[title, url, meta]` like the results of `search`, e.g.
+/// to show posts while the search box is empty
+#[cfg(feature = "bind")]
+#[wasm_bindgen]
+pub fn documents(num_results: usize) -> JsValue {
+    let results: Vec<_> = documents_local(num_results)
+        .into_iter()
+        .map(to_result)
+        .collect();
+    to_js(&results).expect("failed to serialize documents")
+}
+
+/// Queries related to `query`, e.g. for "people also searched for" links below the
+/// results. Empty unless the index was built with `--related-terms`.
+#[cfg(feature = "bind")]
+#[wasm_bindgen]
+pub fn related_queries(query: String) -> JsValue {
+    to_js(&related_queries_local(&query)).expect("failed to serialize related queries")
+}
+
+/// See `ABI_VERSION`
+#[cfg(feature = "bind")]
+#[wasm_bindgen]
+pub fn abi_version() -> u32 {
+    ABI_VERSION
+}
+
+/// `CAPABILITY_*` bits of the module and the index loaded so far, for feature
+/// detection by the loader and other integrations
+#[cfg(feature = "bind")]
+#[wasm_bindgen]
+pub fn capabilities() -> u32 {
+    capabilities_local()
+}
+
+#[cfg(all(feature = "bind", not(feature = "shards")))]
+#[wasm_bindgen]
+pub fn storage_hash() -> String {
+    STORAGE_HASH.to_string()
+}
+
+/// Add the next shard of the index, as fetched from `storage-<n>`. With
+/// `--external-storage` the whole index is a single shard.
+#[cfg(all(feature = "bind", feature = "shards"))]
+#[wasm_bindgen]
+pub fn load_shard(bytes: &[u8]) -> Result<(), JsValue> {
+    load_shard_local(bytes).map_err(|e| JsValue::from_str(&e.to_string()))
+}
+
+/// Add an index fetched separately under the given name, see `search_all`
+#[cfg(feature = "bind")]
+#[wasm_bindgen]
+pub fn load_index(name: &str, bytes: &[u8]) -> Result<(), JsValue> {
+    load_index_local(name, bytes).map_err(|e| JsValue::from_str(&e.to_string()))
+}
+
+#[cfg(feature = "bind")]
+fn parse_options(options: JsValue) -> Result<SearchOptions, JsValue> {
+    if options.is_undefined() || options.is_null() {
+        Ok(SearchOptions::default())
+    } else {
+        Ok(serde_wasm_bindgen::from_value(options)?)
+    }
+}
+
+/// Search with an options object, e.g. `{ query_mode: "advanced", fuzzy: 1, type_limits: { blog: 2 } }`.
+/// Fuzzy search needs a storage built with `--fuzzy`.
+#[cfg(feature = "bind")]
+#[wasm_bindgen]
+pub fn search_with_options(
+    query: String,
+    num_results: usize,
+    options: JsValue,
+) -> Result<JsValue, JsValue> {
+    let options = parse_options(options)?;
+    let results: Vec<_> = search_with_options_local(query, num_results, &options)
+        .into_iter()
+        .map(|(post_id, score)| (to_result(post_id), score))
+        .collect();
+    Ok(to_js(&results)?)
+}
+
+/// Like `search_with_options`, but returns `{ total, results }` with the `page`-th page
+/// of `per_page` results, counting from 1, and the number of all matches
+#[cfg(feature = "bind")]
+#[wasm_bindgen]
+pub fn search_paginated(
+    query: String,
+    page: usize,
+    per_page: usize,
+    options: JsValue,
+) -> Result<JsValue, JsValue> {
+    let options = parse_options(options)?;
+    let Page { total, results } = search_paginated_local(query, page, per_page, &options);
+    let results: Vec<_> = results
+        .into_iter()
+        .map(|(post_id, score)| (to_result(post_id), score))
+        .collect();
+    let object = js_sys::Object::new();
+    js_sys::Reflect::set(&object, &"total".into(), &(total as f64).into())?;
+    js_sys::Reflect::set(&object, &"results".into(), &to_js(&results)?)?;
+    Ok(object.into())
+}
+
+/// Like `search_with_options`, but returns `[result, score, matched_terms, highlights]`,
+/// where `matched_terms` are the (lowercased) query terms found in the post and
+/// `highlights` where they are in its title and excerpt, e.g. for highlighting
+#[cfg(feature = "bind")]
+#[wasm_bindgen]
+pub fn search_with_details(
+    query: String,
+    num_results: usize,
+    options: JsValue,
+) -> Result<JsValue, JsValue> {
+    let options = parse_options(options)?;
+    let results: Vec<_> = search_with_details_local(query, num_results, &options)
+        .into_iter()
+        .map(|result| {
+            (
+                to_result(result.post_id),
+                result.score,
+                result.matched_terms,
+                to_utf16(result.post_id, result.highlights),
+            )
+        })
+        .collect();
+    Ok(to_js(&results)?)
+}
+
+/// Highlights with offsets in UTF-16 code units instead of bytes, which is how
+/// JavaScript indexes strings
+#[cfg(feature = "bind")]
+fn to_utf16(post_id: &PostId, highlights: tinysearch::Highlights) -> tinysearch::Highlights {
+    highlights
+        .into_iter()
+        .map(|(field, ranges)| {
+            let text = match field.as_str() {
+                "title" => &post_id.0,
+                _ => &post_id.2[&field],
+            };
+            let offset = |byte: usize| text[..byte].encode_utf16().count();
+            let ranges = ranges
+                .into_iter()
+                .map(|(start, end)| (offset(start), offset(end)))
+                .collect();
+            (field, ranges)
+        })
+        .collect()
+}
+
+/// Search the index of the module and all indexes added with `load_index`, returning
+/// `[index, result, score]`, where `index` is the name of the index (`"default"` for
+/// the one of the module)
+#[cfg(feature = "bind")]
+#[wasm_bindgen]
+pub fn search_all(query: String, num_results: usize, options: JsValue) -> Result<JsValue, JsValue> {
+    let options = parse_options(options)?;
+    let results: Vec<_> = search_all_local(query, num_results, &options)
+        .into_iter()
+        .map(|result| (result.index, to_result(result.post_id), result.score))
+        .collect();
+    Ok(to_js(&results)?)
+}
+
+/// Like `search_with_options`, but returns after roughly `budget_ms` milliseconds even if
+/// not all posts were scored yet. Returns `{ results, truncated }`, where `truncated`
+/// tells whether the results only cover part of the index.
+#[cfg(feature = "bind")]
+#[wasm_bindgen]
+pub fn search_with_budget(
+    query: String,
+    num_results: usize,
+    budget_ms: f64,
+    options: JsValue,
+) -> Result<JsValue, JsValue> {
+    let options = parse_options(options)?;
+    let deadline = js_sys::Date::now() + budget_ms;
+    let SearchResults { results, truncated } =
+        search_with_deadline_local(query, num_results, &options, || {
+            js_sys::Date::now() > deadline
+        });
+    let results: Vec<_> = results
+        .into_iter()
+        .map(|(post_id, score)| (to_result(post_id), score))
+        .collect();
+    let object = js_sys::Object::new();
+    js_sys::Reflect::set(&object, &"results".into(), &to_js(&results)?)?;
+    js_sys::Reflect::set(&object, &"truncated".into(), &truncated.into())?;
+    Ok(object.into())
 }
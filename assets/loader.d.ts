@@ -0,0 +1,166 @@
+// Type definitions for tinysearch.js
+
+/** A matching post, as `[title, url, meta]`. Lists like tags in `meta` are JSON. */
+export type SearchResult = {RESULT};
+
+/** A matching post along with its relevance score */
+export type ScoredResult = [result: SearchResult, score: number];
+
+/** A matching post with its score and the (lowercased) query terms found in it */
+/**
+ * Start and end offsets of the words matching the query in the `title` and the
+ * `excerpt` of a post, for use with `String.prototype.slice`
+ */
+export type Highlights = Partial<Record<'title' | 'excerpt', [start: number, end: number][]>>;
+
+export type DetailedResult = [
+  result: SearchResult,
+  score: number,
+  matchedTerms: string[],
+  highlights: Highlights,
+];
+
+/** A matching post of `searchAll`, along with the name of its index */
+export type LabeledResult = [index: string, result: SearchResult, score: number];
+
+export interface SearchOptions {
+  /** `"advanced"` enables "exact phrases", AND, OR, and -excluded words */
+  query_mode?: "simple" | "advanced";
+  /** Maximum edit distance of typos, needs an index built with `--fuzzy` */
+  fuzzy?: number;
+  /** Maximum number of results per post type, e.g. `{ blog: 2 }` */
+  type_limits?: Record<string, number>;
+  /** Only posts with these facet values, e.g. `{ category: "rust" }`, needs `--facet-field` */
+  filter?: Record<string, string>;
+  /** `"date"` returns the newest posts first, needs an index built with `--date-field` */
+  sort?: "relevance" | "date";
+  /** Order of results with the same score: `"index"` keeps the order of the index (default) */
+  tie_break?: "index" | "title" | "url" | "date";
+  /** Boost of newer posts: the newest post's score is multiplied by `1 + recency` */
+  recency?: number;
+  /** Only match query words in these fields, e.g. `["title", "tags"]`; all by default */
+  fields?: string[];
+  /** Never return posts with these URLs, as they were indexed, e.g. the current page */
+  exclude?: string[];
+  /** Only posts of these sections: their `section` meta field, or else the first segment of their URL path */
+  sections?: string[];
+  /** Secret key the index was built with, see `--hash-key` */
+  hash_key?: string;
+  /** Weight of query words found in the title instead of the one of the index, see `--title-boost` */
+  title_boost?: number;
+  /** Weight of query words found in the body instead of the one of the index, see `--body-boost` */
+  body_boost?: number;
+  /** Weight of query words found only in facet fields instead of the one of the index, see `--facet-boost` */
+  facet_boost?: number;
+  /** Leave out query words with fewer characters instead of the minimum of the index, see `--min-word-length` */
+  min_word_len?: number;
+}
+
+/** Fetch and instantiate the module. Only the first call does any work. */
+export function load(): Promise<void>;
+
+/**
+ * Rewrite the URL of every result: `{url}` in a string template is replaced by the
+ * URL of the post, a function gets the URL and the result. `null` turns it off.
+ */
+export function setUrlTemplate(
+  template: string | ((url: string, result: SearchResult) => string) | null,
+): void;
+
+/**
+ * Return the title, URL, and meta fields of results with `&<>"'` escaped, for
+ * templates that build HTML from strings. The offsets of highlights still refer to
+ * the raw strings.
+ */
+export function setEscapeHtml(enabled: boolean): void;
+
+/**
+ * The URL if it's relative or uses http(s), otherwise `'#'`. The URLs of results
+ * are already checked; use it for URLs built from results in other ways.
+ */
+export function safeUrl(url: string): string;
+
+/** Load the module and run a throwaway query once the browser is idle */
+export function warmup(): Promise<void>;
+
+/** Up to `numResults` matching posts, best matches first. 0 returns all matches. */
+export function search(query: string, numResults: number): Promise<SearchResult[]>;
+
+/**
+ * Up to `numResults` posts in the order of the index (by URL if built with
+ * `--deterministic`), without a query, e.g. to show posts while the search box is
+ * empty. 0 returns all posts.
+ */
+export function documents(numResults: number): Promise<SearchResult[]>;
+
+export function searchWithOptions(
+  query: string,
+  numResults: number,
+  options?: SearchOptions,
+): Promise<ScoredResult[]>;
+
+/**
+ * The `page`-th page of `perPage` results, counting from 1, along with the number of
+ * all matching posts, e.g. for "42 results, page 2 of 5". A `perPage` of 0 returns
+ * all matches.
+ */
+export function searchPaginated(
+  query: string,
+  page: number,
+  perPage: number,
+  options?: SearchOptions,
+): Promise<{ total: number; results: ScoredResult[] }>;
+
+/** Like `searchWithOptions`, along with the query terms each post matched, e.g. for highlighting */
+export function searchWithDetails(
+  query: string,
+  numResults: number,
+  options?: SearchOptions,
+): Promise<DetailedResult[]>;
+
+/**
+ * Up to 5 queries related to `query`, each the query along with a word that often
+ * occurs in the same posts as its words, e.g. for "people also searched for" links.
+ * Empty unless the index was built with `--related-terms`.
+ */
+export function relatedQueries(query: string): Promise<string[]>;
+
+/**
+ * Fetch a storage built separately, e.g. with `tinysearch -m storage`, and search it
+ * with `searchAll` under the given name. Not supported by the JavaScript fallback.
+ */
+export function loadIndex(name: string, url: string): Promise<void>;
+
+/** Search the index of the module (named `"default"`) and all indexes added with `loadIndex` */
+export function searchAll(
+  query: string,
+  numResults: number,
+  options?: SearchOptions,
+): Promise<LabeledResult[]>;
+
+/** What the module and its index support, see `capabilities` */
+export interface Capabilities {
+  /** Version of the module exports, 0 for modules built before it was added */
+  abiVersion: number;
+  /** Results include the `meta` field */
+  meta: boolean;
+  /** The index is fetched separately, see `--shards` and `--external-storage` */
+  shards: boolean;
+  /** The index supports the `fuzzy` option, see `--fuzzy` */
+  fuzzy: boolean;
+  /** The index supports the `filter` option, see `--facet-field` */
+  facets: boolean;
+  /** The index supports sorting by date and `recency`, see `--date-field` */
+  dates: boolean;
+  /** `relatedQueries` has suggestions, see `--related-terms` */
+  related: boolean;
+  /** `searchWithDetails` is available, e.g. for highlighting */
+  details: boolean;
+  /** `documents` is available */
+  documents: boolean;
+  /** `loadIndex` and `searchAll` are available */
+  indexes: boolean;
+}
+
+/** Feature-detect what the deployed module supports instead of relying on its version */
+export function capabilities(): Promise<Capabilities>;
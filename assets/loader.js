@@ -0,0 +1,152 @@
+// Loader for the tinysearch WASM module.
+//
+// The functions exported here can be called right away; they wait for the
+// module to be loaded first. Once `load()` resolved, the functions of the
+// generated bindings in './{WASM_NAME}.js' can be called synchronously too.
+import init, * as engine from './{WASM_NAME}.js';
+
+// The query string contains a hash of the search index (see `storage.hash`),
+// so browsers fetch the module again whenever the index changes, even if it
+// is cached forever. Without the index, it's a hash of the module itself.
+const WASM_URL = './{WASM_NAME}_bg.wasm?v={MODULE_HASH}';
+
+// Hashes of the index shards when built with `--shards` or `--external-storage`,
+// which are fetched from './storage-<n>' instead of being embedded in the module.
+const SHARDS = {SHARDS};
+
+// Version of the module exports this loader was written for, see `abi_version`
+// in the engine. Modules built before it was added count as version 0.
+const ABI_VERSION = 1;
+
+// Names of the bits of the engine's `capabilities()`, in order
+const CAPABILITY_BITS = ['meta', 'shards', 'fuzzy', 'facets', 'dates', 'related'];
+
+let loading = null;
+
+{RESULTS_JS}
+
+// Fetch and instantiate the module and fetch the shards of the index, if any.
+// Only the first call does any work.
+export function load() {
+  if (loading === null) {
+    loading = Promise.all([init(WASM_URL), ...SHARDS.map(fetchShard)]).then(
+      ([, ...shards]) => {
+        if (abiVersion() > ABI_VERSION) {
+          console.warn('tinysearch: the module is newer than tinysearch.js, regenerate both together');
+        }
+        shards.forEach((bytes) => engine.load_shard(bytes));
+      },
+    );
+  }
+  return loading;
+}
+
+function abiVersion() {
+  return engine.abi_version ? engine.abi_version() : 0;
+}
+
+// What the module and its index support, e.g. `{ facets: true, fuzzy: false, ... }`,
+// so that pages can feature-detect instead of breaking when an older module is
+// deployed. Modules without `capabilities` only report what their exports tell.
+export async function capabilities() {
+  await load();
+  const bits = engine.capabilities ? engine.capabilities() : 0;
+  const supported = Object.fromEntries(
+    CAPABILITY_BITS.map((name, bit) => [name, (bits & (1 << bit)) !== 0]),
+  );
+  return {
+    ...supported,
+    abiVersion: abiVersion(),
+    shards: supported.shards || typeof engine.load_shard === 'function',
+    details: typeof engine.search_with_details === 'function',
+    documents: typeof engine.documents === 'function',
+    indexes: typeof engine.load_index === 'function',
+  };
+}
+
+// The shards are fetched in parallel but loaded in order, which keeps the
+// ranking the same as with a single index.
+async function fetchShard(hash, shard) {
+  const response = await fetch(`./storage-${shard}?v=${hash}`);
+  if (!response.ok) {
+    throw new Error(`Failed to fetch shard ${shard}: ${response.status}`);
+  }
+  return new Uint8Array(await response.arrayBuffer());
+}
+
+// Load the module and run a throwaway query once the browser is idle, so that
+// neither compiling the module nor decoding the index delays the first real
+// keystroke. Call it on page load or when the search box gets focus.
+export function warmup() {
+  const idle = globalThis.requestIdleCallback || ((callback) => setTimeout(callback, 1));
+  return new Promise((resolve, reject) => {
+    idle(() => load().then(() => {
+      engine.search('', 1);
+      resolve();
+    }, reject));
+  });
+}
+
+export async function search(query, numResults) {
+  await load();
+  return engine.search(query, numResults).map(withUrl);
+}
+
+// Up to `numResults` posts in the order of the index (by URL if built with
+// `--deterministic`), without a query, e.g. to show posts while the search box is
+// empty. 0 returns all posts.
+export async function documents(numResults) {
+  await load();
+  return engine.documents(numResults).map(withUrl);
+}
+
+export async function searchWithOptions(query, numResults, options) {
+  await load();
+  return withUrls(engine.search_with_options(query, numResults, options));
+}
+
+// The `page`-th page of `perPage` results, counting from 1, as `{ total, results }`
+// with the number of all matches, e.g. for "42 results, page 2 of 5"
+export async function searchPaginated(query, page, perPage, options) {
+  await load();
+  if (engine.search_paginated) {
+    const { total, results } = engine.search_paginated(query, page, perPage, options);
+    return { total, results: withUrls(results) };
+  }
+  // Modules built before it was added return all results to page through
+  const results = withUrls(engine.search_with_options(query, 0, options));
+  const start = Math.max(page - 1, 0) * perPage;
+  return { total: results.length, results: perPage > 0 ? results.slice(start, start + perPage) : results };
+}
+
+export async function searchWithDetails(query, numResults, options) {
+  await load();
+  return withUrls(engine.search_with_details(query, numResults, options));
+}
+
+// Up to 5 queries related to `query`, e.g. for "people also searched for" links
+// below the results. Empty unless the index was built with `--related-terms`, or
+// if the module is older than that.
+export async function relatedQueries(query) {
+  await load();
+  return engine.related_queries ? engine.related_queries(query) : [];
+}
+
+// Fetch a storage built separately, e.g. with `tinysearch -m storage`, and add it
+// under `name`, so that `searchAll` searches it along with the index of the module.
+export async function loadIndex(name, url) {
+  const [, response] = await Promise.all([load(), fetch(url)]);
+  if (!response.ok) {
+    throw new Error(`Failed to fetch index ${name}: ${response.status}`);
+  }
+  engine.load_index(name, new Uint8Array(await response.arrayBuffer()));
+}
+
+// Search all indexes, returning `[index, result, score]` with the name of the
+// index of each result ('default' for the one of the module)
+export async function searchAll(query, numResults, options) {
+  await load();
+  return engine
+    .search_all(query, numResults, options)
+    .map(([index, result, score]) => [index, withUrl(result), score]);
+}
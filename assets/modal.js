@@ -0,0 +1,219 @@
+// <tinysearch-modal> search overlay, opened with `/` or Ctrl+K (Cmd+K on macOS)
+// like the search of most documentation sites. Add it anywhere on the page:
+//
+//   <script type="module" src="./tinysearch-modal.js"></script>
+//   <tinysearch-modal placeholder="Search the docs" results="10"></tinysearch-modal>
+//
+// Call `open()` on the element to open it from a button as well. The
+// `placeholder` attribute overrides the placeholder of the `--strings` file.
+//
+// Result links point to the indexed URLs. Set the `url-template` attribute, e.g.
+// to `{url}?ref=search`, or the `resultUrl` property to a function
+// `(url, result) => url` to rewrite them. Choosing a result fires a cancelable
+// `tinysearch-select` event with `{ url, result }` as its detail; cancel it to
+// navigate with a client-side router instead of following the link.
+import { safeUrl, search, warmup } from './tinysearch.js';
+
+// Texts of the `--strings` file passed to tinysearch
+const STRINGS = {STRINGS};
+
+function resultCount(count) {
+  if (count === 0) {
+    return STRINGS.no_results;
+  }
+  return count === 1 ? STRINGS.one_result : STRINGS.results.replace('{n}', count);
+}
+
+const TEMPLATE = `
+  <style>
+    dialog {
+      width: min(40rem, 90vw);
+      margin-top: 10vh;
+      padding: 0;
+      border: none;
+      border-radius: 8px;
+      box-shadow: 0 8px 24px rgba(0, 0, 0, 0.3);
+      font-family: system-ui, sans-serif;
+      color: #1f2328;
+    }
+
+    dialog::backdrop {
+      background: rgba(0, 0, 0, 0.4);
+    }
+
+    input {
+      box-sizing: border-box;
+      width: 100%;
+      padding: 1rem;
+      border: none;
+      border-bottom: 1px solid #d0d7de;
+      font-size: 1.1rem;
+      outline: none;
+    }
+
+    ul {
+      max-height: 60vh;
+      margin: 0;
+      padding: 0.5rem;
+      overflow-y: auto;
+      list-style: none;
+    }
+
+    ul:empty {
+      display: none;
+    }
+
+    p {
+      margin: 0;
+      padding: 0.5rem 1rem;
+      font-size: 0.9rem;
+      color: #656d76;
+    }
+
+    p:empty {
+      display: none;
+    }
+
+    a {
+      display: block;
+      padding: 0.6rem 0.8rem;
+      border-radius: 6px;
+      color: inherit;
+      text-decoration: none;
+    }
+
+    small {
+      display: block;
+      color: #656d76;
+    }
+
+    a[aria-selected="true"] {
+      background: #0969da;
+      color: white;
+    }
+
+    a[aria-selected="true"] small {
+      color: #ddf4ff;
+    }
+  </style>
+  <dialog part="dialog">
+    <input type="search" part="input" autocomplete="off" />
+    <p part="status" aria-live="polite"></p>
+    <ul part="results"></ul>
+  </dialog>
+`;
+
+class TinysearchModal extends HTMLElement {
+  constructor() {
+    super();
+    this.attachShadow({ mode: 'open' }).innerHTML = TEMPLATE;
+    this.dialog = this.shadowRoot.querySelector('dialog');
+    this.input = this.shadowRoot.querySelector('input');
+    this.list = this.shadowRoot.querySelector('ul');
+    this.status = this.shadowRoot.querySelector('p');
+    this.selected = 0;
+    this.resultUrl = null;
+    this.onShortcut = this.onShortcut.bind(this);
+
+    this.input.addEventListener('input', () => this.update());
+    this.dialog.addEventListener('keydown', (event) => this.onKey(event));
+    // Close when clicking the backdrop
+    this.dialog.addEventListener('click', (event) => {
+      if (event.target === this.dialog) {
+        this.close();
+      }
+    });
+  }
+
+  connectedCallback() {
+    this.input.placeholder = this.getAttribute('placeholder') ?? STRINGS.placeholder;
+    document.addEventListener('keydown', this.onShortcut);
+  }
+
+  disconnectedCallback() {
+    document.removeEventListener('keydown', this.onShortcut);
+  }
+
+  open() {
+    // Start loading the module while the user types
+    warmup();
+    this.dialog.showModal();
+    this.input.select();
+  }
+
+  close() {
+    this.dialog.close();
+  }
+
+  onShortcut(event) {
+    const typing = event.target.closest?.('input, textarea, [contenteditable]');
+    const shortcut = (event.key === '/' && !typing) ||
+      (event.key === 'k' && (event.ctrlKey || event.metaKey));
+    if (shortcut && !this.dialog.open) {
+      event.preventDefault();
+      this.open();
+    }
+  }
+
+  onKey(event) {
+    if (event.key === 'ArrowDown' || event.key === 'ArrowUp') {
+      event.preventDefault();
+      this.select(this.selected + (event.key === 'ArrowDown' ? 1 : -1));
+    } else if (event.key === 'Enter') {
+      this.list.querySelectorAll('a')[this.selected]?.click();
+    }
+  }
+
+  href(result) {
+    const [, url] = result;
+    if (this.resultUrl) {
+      return safeUrl(this.resultUrl(url, result));
+    }
+    const template = this.getAttribute('url-template');
+    return template ? safeUrl(template.replaceAll('{url}', url)) : url;
+  }
+
+  select(index) {
+    const links = this.list.querySelectorAll('a');
+    if (links.length === 0) {
+      return;
+    }
+    this.selected = (index + links.length) % links.length;
+    links.forEach((link, i) => link.setAttribute('aria-selected', i === this.selected));
+    links[this.selected].scrollIntoView({ block: 'nearest' });
+  }
+
+  async update() {
+    const numResults = Number(this.getAttribute('results') ?? 10);
+    const query = this.input.value;
+    const results = await search(query, numResults);
+    this.status.textContent = query.trim() ? resultCount(results.length) : '';
+    this.list.replaceChildren(...results.map((result) => {
+      const [title, url] = result;
+      const link = document.createElement('a');
+      link.href = this.href(result);
+      link.textContent = title;
+      const path = document.createElement('small');
+      path.textContent = url;
+      link.appendChild(path);
+      link.addEventListener('click', (event) => {
+        const selected = new CustomEvent('tinysearch-select', {
+          detail: { url: link.getAttribute('href'), result },
+          bubbles: true,
+          composed: true,
+          cancelable: true,
+        });
+        if (!this.dispatchEvent(selected)) {
+          event.preventDefault();
+        }
+        this.close();
+      });
+      const item = document.createElement('li');
+      item.appendChild(link);
+      return item;
+    }));
+    this.select(0);
+  }
+}
+
+customElements.define('tinysearch-modal', TinysearchModal);
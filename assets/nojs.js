@@ -0,0 +1,337 @@
+// Pure JavaScript fallback of tinysearch for browsers that can't run WebAssembly,
+// e.g. because of a Content Security Policy without 'wasm-unsafe-eval'.
+//
+// It has the same functions as tinysearch.js and ranks posts the same way,
+// but stores the words of each post instead of xor filters, so it's larger.
+// Fuzzy search and the advanced query mode aren't supported.
+const INDEX = {INDEX};
+const STOPWORDS = new Set(INDEX.stopwords);
+
+let posts = null;
+// Oldest and newest date of the posts, for the recency boost
+let dateRange = null;
+
+{RESULTS_JS}
+
+function isCjk(c) {
+  const code = c.codePointAt(0);
+  return (
+    (code >= 0x3040 && code <= 0x30ff) ||
+    (code >= 0x3400 && code <= 0x4dbf) ||
+    (code >= 0x4e00 && code <= 0x9fff) ||
+    (code >= 0xac00 && code <= 0xd7af) ||
+    (code >= 0xf900 && code <= 0xfaff) ||
+    (code >= 0x20000 && code <= 0x2fa1f)
+  );
+}
+
+// Split runs of CJK characters into overlapping bigrams, like `cjk::segment`
+function segment(token) {
+  const words = [];
+  let run = [];
+  let other = '';
+  const flush = () => {
+    if (run.length === 1) {
+      words.push(run[0]);
+    }
+    for (let i = 0; i + 1 < run.length; i++) {
+      words.push(run[i] + run[i + 1]);
+    }
+    run = [];
+  };
+  for (const c of token) {
+    if (isCjk(c)) {
+      if (other) {
+        words.push(other);
+        other = '';
+      }
+      run.push(c);
+    } else {
+      flush();
+      other += c;
+    }
+  }
+  flush();
+  if (other) {
+    words.push(other);
+  }
+  return words;
+}
+
+// Same as `Joiners::contains`
+function isJoiner(c) {
+  const { joiners } = INDEX.analyzer;
+  return (c === '-' && joiners.hyphen) || (c === '_' && joiners.underscore) || (c === '.' && joiners.dot);
+}
+
+// Same as `Analyzer::split`: runs of letters and apostrophes, and of digits and
+// joiners if enabled. Joined words come after their parts.
+function split(s) {
+  const inWord = (c) => /[\p{Alphabetic}']/u.test(c) || (INDEX.analyzer.digits && /\p{N}/u.test(c));
+  const words = [];
+  let run = '';
+  for (const c of s.toLowerCase() + ' ') {
+    if (inWord(c) || isJoiner(c)) {
+      run += c;
+      continue;
+    }
+    const word = run.replace(/^[-_.]+|[-_.]+$/g, '');
+    const parts = word.split(/[-_.]/).filter((part) => part);
+    if (parts.length > 1) {
+      words.push(...parts);
+    }
+    if (word) {
+      words.push(word);
+    }
+    run = '';
+  }
+  return words;
+}
+
+// Same as `Analyzer::tokenize`
+function tokenize(s) {
+  return split(s)
+    .flatMap((token) => {
+      if (INDEX.analyzer.normalize_unicode) {
+        token = token.normalize('NFKD').replace(/\p{M}/gu, '');
+      }
+      return INDEX.analyzer.cjk ? segment(token) : [token];
+    })
+    .filter((word) => word);
+}
+
+export function load() {
+  if (posts === null) {
+    posts = INDEX.posts.map(([title, url, meta, words], i) => ({
+      result: [title, url, meta],
+      facets: INDEX.facets?.[i] ?? {},
+      date: INDEX.dates?.[i] ?? null,
+      titleWords: tokenize(title),
+      words: new Set(words.map((word) => INDEX.words[word])),
+    }));
+    const dates = posts.map((post) => post.date).filter((date) => date !== null);
+    if (dates.length > 0) {
+      dateRange = [Math.min(...dates), Math.max(...dates)];
+    }
+  }
+  return Promise.resolve();
+}
+
+export function warmup() {
+  return load();
+}
+
+// The first segment of the URL path, like `post_type`
+function postType(url) {
+  let path = url;
+  const scheme = url.indexOf('://');
+  if (scheme >= 0) {
+    const host = url.indexOf('/', scheme + 3);
+    path = host < 0 ? '' : url.slice(host);
+  }
+  return path.replace(/^\/+/, '').split('/')[0];
+}
+
+// Same as `post_section`: the `section` meta field, or else the post type
+function postSection([, url, meta]) {
+  return meta?.section ?? postType(url);
+}
+
+// Same as `freshness`: the position of a date between the oldest (0) and the newest (1) post
+function freshness(date) {
+  if (date === null || dateRange === null) {
+    return 0;
+  }
+  const [oldest, newest] = dateRange;
+  return newest > oldest ? (date - oldest) / (newest - oldest) : 1;
+}
+
+// Same as `matches_filter`: every field of the filter has to be among the facets of the post
+function matchesFilter(post, filter) {
+  return Object.entries(filter).every(([field, value]) => post.facets[field]?.includes(value));
+}
+
+// Same as `query_words`: stopwords and short words are left out like in the index
+function queryWords(query, options) {
+  const minWordLen = options.min_word_len ?? INDEX.min_word_len;
+  return tokenize(query).filter((word) => [...word].length >= minWordLen && !STOPWORDS.has(word));
+}
+
+function ranked(query, numResults, options = {}) {
+  load();
+  const terms = queryWords(query, options);
+  if (terms.length === 0) {
+    return [];
+  }
+  // Same as `bigram`: adjacent query words, which are indexed with `--bigram-boost`
+  const bigrams = INDEX.bigram_boost > 0 ? terms.slice(1).map((term, i) => `${terms[i]} ${term}`) : [];
+  const filter = options.filter ?? {};
+  // Same as `Fields`: all fields by default, facet fields only when asked for
+  const fields = options.fields ?? [];
+  const title = fields.length === 0 || fields.includes('title');
+  const body = fields.length === 0 || fields.includes('body');
+  const facetFields = fields.filter((field) => field !== 'title' && field !== 'body');
+  const exclude = new Set(options.exclude ?? []);
+  const sections = new Set(options.sections ?? []);
+  // Same as `SearchOptions::boosts`: the weights of the index unless overridden
+  const titleBoost = options.title_boost ?? INDEX.boosts.title;
+  const bodyBoost = options.body_boost ?? INDEX.boosts.body;
+  const facetBoost = options.facet_boost ?? INDEX.boosts.facets;
+  const matches = [];
+  for (const post of posts) {
+    if (!matchesFilter(post, filter) || exclude.has(post.result[1])) {
+      continue;
+    }
+    if (sections.size > 0 && !sections.has(postSection(post.result))) {
+      continue;
+    }
+    const facetWords = new Set(facetFields.flatMap((field) => (post.facets[field] ?? []).flatMap(tokenize)));
+    const inTitleWords = (term) => title && post.titleWords.includes(term);
+    const inBodyWords = (term) => body && post.words.has(term);
+    const inTitle = terms.filter(inTitleWords).length;
+    const inBody = terms.filter(inBodyWords).length;
+    const inOrder = body ? bigrams.filter((pair) => post.words.has(pair)).length : 0;
+    // Words of the body count only once, even if they are a tag as well
+    const inFacets = terms.filter((term) => !inBodyWords(term) && facetWords.has(term)).length;
+    const points = titleBoost * inTitle + bodyBoost * inBody + facetBoost * inFacets + INDEX.bigram_boost * inOrder;
+    if (points > 0) {
+      const matched = terms.filter((term) => inTitleWords(term) || inBodyWords(term) || facetWords.has(term));
+      // Same as `calibrated`: matching more of the query outweighs the field boosts
+      const coverage = (matched.length / terms.length) ** 2;
+      const score = points * coverage * (1 + (options.recency ?? 0) * freshness(post.date));
+      matches.push([post.result, score, [...new Set(matched)], post.date]);
+    }
+  }
+  // The sort is stable, so earlier posts win the ties left by `tie_break` like in
+  // the WASM module. Sorted by date, posts without one come last.
+  const byDate = (a, b) => (b[3] ?? -Infinity) - (a[3] ?? -Infinity);
+  const alphabetically = (field) => (a, b) => (a[0][field] < b[0][field] ? -1 : a[0][field] > b[0][field] ? 1 : 0);
+  const tieBreak = {
+    title: alphabetically(0),
+    url: alphabetically(1),
+    date: byDate,
+  }[options.tie_break] ?? (() => 0);
+  if (options.sort === 'date') {
+    matches.sort((a, b) => byDate(a, b) || b[1] - a[1] || tieBreak(a, b));
+  } else {
+    matches.sort((a, b) => b[1] - a[1] || tieBreak(a, b));
+  }
+  // Same as `dedup_canonical`: only the best match of posts with several URLs
+  const canonical = new Set();
+  const unique = matches.filter(([[, , meta]]) => {
+    if (meta?.canonical === undefined) {
+      return true;
+    }
+    const seen = canonical.has(meta.canonical);
+    canonical.add(meta.canonical);
+    return !seen;
+  });
+  const limits = { ...INDEX.type_limits, ...(options.type_limits ?? {}) };
+  const counts = {};
+  const limited = unique.filter(([[, url]]) => {
+    const type = postType(url);
+    counts[type] = (counts[type] ?? 0) + 1;
+    return !(type in limits) || counts[type] <= limits[type];
+  });
+  const results = limited.map(([result, score, matched]) => [result, score, matched]);
+  return numResults > 0 ? results.slice(0, numResults) : results;
+}
+
+export async function search(query, numResults) {
+  return ranked(query, numResults).map(([result]) => withUrl(result));
+}
+
+export async function documents(numResults) {
+  load();
+  const results = posts.map((post) => withUrl(post.result));
+  return numResults > 0 ? results.slice(0, numResults) : results;
+}
+
+export async function searchWithOptions(query, numResults, options) {
+  const results = ranked(query, numResults, options ?? {});
+  return withUrls(results.map(([result, score]) => [result, score]));
+}
+
+export async function searchPaginated(query, page, perPage, options) {
+  const results = await searchWithOptions(query, 0, options);
+  const start = Math.max(page - 1, 0) * perPage;
+  return { total: results.length, results: perPage > 0 ? results.slice(start, start + perPage) : results };
+}
+
+// Same as `highlight`: the words of `text` that are one of `terms`, as offsets into
+// the string like the module returns them
+function highlight(text, terms) {
+  const ranges = [];
+  // A hyphen at the end of the character class stands for itself
+  const joiners = ['_', '.', '-'].filter(isJoiner).join('');
+  for (const match of text.matchAll(new RegExp(`[\\p{Alphabetic}\\p{N}'${joiners}]+`, 'gu'))) {
+    if (tokenize(match[0]).some((word) => terms.includes(word))) {
+      ranges.push([match.index, match.index + match[0].length]);
+    }
+  }
+  return ranges;
+}
+
+// Same as `SearchResult::highlights`: matches in the title and the excerpt
+function highlights([title, , meta], terms) {
+  const fields = [['title', title], ['excerpt', meta?.excerpt]];
+  return Object.fromEntries(
+    fields
+      .filter(([, text]) => typeof text === 'string')
+      .map(([field, text]) => [field, highlight(text, terms)])
+      .filter(([, ranges]) => ranges.length > 0),
+  );
+}
+
+export async function searchWithDetails(query, numResults, options) {
+  const results = ranked(query, numResults, options ?? {});
+  return withUrls(
+    results.map(([result, score, matched]) => [result, score, matched, highlights(result, matched)]),
+  );
+}
+
+// Same as `Storage::related_queries`
+export async function relatedQueries(query) {
+  const words = queryWords(query, {});
+  // Terms get more points the higher they rank for more of the query words
+  const scores = new Map();
+  for (const related of words.map((word) => INDEX.related?.[word] ?? [])) {
+    related.forEach((term, rank) => {
+      if (!words.includes(term)) {
+        scores.set(term, (scores.get(term) ?? 0) + related.length - rank);
+      }
+    });
+  }
+  const normalized = query.split(/\s+/).filter((word) => word).join(' ');
+  return [...scores]
+    .sort((a, b) => b[1] - a[1])
+    .slice(0, 5)
+    .map(([term]) => `${normalized} ${term}`);
+}
+
+// Loading the storage of other indexes needs the WebAssembly module
+export async function loadIndex(name) {
+  throw new Error(`Can't load index ${name}: the JavaScript fallback only searches its own index`);
+}
+
+export async function searchAll(query, numResults, options) {
+  const results = ranked(query, numResults, options ?? {});
+  return results.map(([result, score]) => ['default', withUrl(result), score]);
+}
+
+// Same as `capabilities` of tinysearch.js. The fallback has no module, so its ABI
+// version is 0.
+export async function capabilities() {
+  return {
+    meta: true,
+    shards: false,
+    fuzzy: false,
+    facets: (INDEX.facets?.length ?? 0) > 0,
+    dates: (INDEX.dates?.length ?? 0) > 0,
+    related: Object.keys(INDEX.related ?? {}).length > 0,
+    abiVersion: 0,
+    details: true,
+    documents: true,
+    indexes: false,
+  };
+}
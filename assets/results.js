@@ -0,0 +1,58 @@
+let urlTemplate = null;
+let escapeResults = false;
+
+// Rewrite the URL of every result, e.g. to add `?ref=search` or to route it
+// through a client-side router. `template` is either a string in which `{url}`
+// is replaced by the URL of the post, or a function `(url, result) => url`.
+// Pass `null` to return the URLs as they were indexed.
+export function setUrlTemplate(template) {
+  urlTemplate = template;
+}
+
+// Return the title, URL, and meta fields of results HTML-escaped, e.g. for
+// templates that build markup from strings. Off by default, as rendering with
+// `textContent` is safer. The offsets of highlights refer to the raw strings.
+export function setEscapeHtml(enabled) {
+  escapeResults = enabled;
+}
+
+function escapeHtml(value) {
+  return String(value).replace(/[&<>"']/g, (c) => `&#${c.charCodeAt(0)};`);
+}
+
+// The URL if it's relative or uses http(s), otherwise '#', so that URLs from the
+// index or the URL template like `javascript:alert(1)` can't run scripts when
+// they are used as the href of a link
+export function safeUrl(url) {
+  try {
+    const { protocol } = new URL(String(url), 'https://relative.invalid/');
+    return protocol === 'http:' || protocol === 'https:' ? String(url) : '#';
+  } catch {
+    return '#';
+  }
+}
+
+function withUrl(result) {
+  const [title, url, ...rest] = result;
+  let rewritten = url;
+  if (urlTemplate !== null) {
+    rewritten = typeof urlTemplate === 'function'
+      ? urlTemplate(url, result)
+      : urlTemplate.replaceAll('{url}', url);
+  }
+  rewritten = safeUrl(rewritten);
+  if (!escapeResults) {
+    return [title, rewritten, ...rest];
+  }
+  const [meta] = rest;
+  const escaped = [escapeHtml(title), escapeHtml(rewritten)];
+  if (meta) {
+    escaped.push(Object.fromEntries(Object.entries(meta).map(([field, value]) => [field, escapeHtml(value)])));
+  }
+  return escaped;
+}
+
+// Same for results that come with a score and more, like `[result, score]`
+function withUrls(results) {
+  return results.map(([result, ...rest]) => [withUrl(result), ...rest]);
+}
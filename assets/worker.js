@@ -0,0 +1,25 @@
+
+// Answer the calls of the main-thread wrapper in tinysearch.js, so that the
+// module is compiled and searched off the UI thread.
+const methods = {
+  load,
+  warmup,
+  search,
+  documents,
+  searchWithOptions,
+  searchPaginated,
+  searchWithDetails,
+  relatedQueries,
+  loadIndex,
+  searchAll,
+  capabilities,
+};
+
+self.onmessage = async ({ data: { id, method, args } }) => {
+  try {
+    const result = await methods[method](...args);
+    self.postMessage({ id, result });
+  } catch (error) {
+    self.postMessage({ id, error: String(error) });
+  }
+};
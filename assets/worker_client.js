@@ -0,0 +1,79 @@
+// Runs tinysearch in a Web Worker, so that searching large indexes doesn't
+// block the page. It has the same functions as the regular loader; each one
+// returns a Promise that resolves once the worker answered.
+const worker = new Worker(new URL('./tinysearch.worker.js', import.meta.url), {
+  type: 'module',
+});
+
+let nextId = 0;
+const pending = new Map();
+
+worker.onmessage = ({ data: { id, result, error } }) => {
+  const { resolve, reject } = pending.get(id);
+  pending.delete(id);
+  if (error === undefined) {
+    resolve(result);
+  } else {
+    reject(new Error(error));
+  }
+};
+
+{RESULTS_JS}
+
+function call(method, ...args) {
+  return new Promise((resolve, reject) => {
+    const id = nextId++;
+    pending.set(id, { resolve, reject });
+    worker.postMessage({ id, method, args });
+  });
+}
+
+export function load() {
+  return call('load');
+}
+
+export function warmup() {
+  return call('warmup');
+}
+
+export function search(query, numResults) {
+  return call('search', query, numResults).then((results) => results.map(withUrl));
+}
+
+export function documents(numResults) {
+  return call('documents', numResults).then((results) => results.map(withUrl));
+}
+
+export function searchWithOptions(query, numResults, options) {
+  return call('searchWithOptions', query, numResults, options).then(withUrls);
+}
+
+export function searchPaginated(query, page, perPage, options) {
+  return call('searchPaginated', query, page, perPage, options).then(({ total, results }) => ({
+    total,
+    results: withUrls(results),
+  }));
+}
+
+export function searchWithDetails(query, numResults, options) {
+  return call('searchWithDetails', query, numResults, options).then(withUrls);
+}
+
+export function relatedQueries(query) {
+  return call('relatedQueries', query);
+}
+
+export function loadIndex(name, url) {
+  // The worker would resolve relative URLs against its own script
+  return call('loadIndex', name, new URL(url, document.baseURI).href);
+}
+
+export function searchAll(query, numResults, options) {
+  return call('searchAll', query, numResults, options).then((results) =>
+    results.map(([index, result, score]) => [index, withUrl(result), score]),
+  );
+}
+
+export function capabilities() {
+  return call('capabilities');
+}
@@ -0,0 +1,61 @@
+//! Node.js bindings for building and searching tinysearch indexes in-process, e.g.
+//! from the build scripts of Eleventy or Next.js sites, instead of running the CLI.
+//!
+//! Build them with `npm run build`, which writes `index.js`, `index.d.ts`, and the
+//! native module for the current platform.
+
+use std::collections::HashMap;
+
+use napi::bindgen_prelude::Buffer;
+use napi::{Error, Result};
+use napi_derive::napi;
+use tinysearch::prelude::{Meta, Storage};
+
+/// A post to index, like the entries of the JSON file the CLI reads
+#[napi(object)]
+pub struct Post {
+    pub title: String,
+    pub url: String,
+    /// Plain text of the post
+    pub body: Option<String>,
+    pub meta: Option<HashMap<String, String>>,
+}
+
+#[napi(object)]
+pub struct SearchResult {
+    pub title: String,
+    pub url: String,
+    pub meta: HashMap<String, String>,
+    pub score: f64,
+}
+
+/// Build an index from the posts, returning the storage file, which the WASM
+/// engine or `search` can load
+#[napi]
+pub fn build_index(posts: Vec<Post>) -> Result<Buffer> {
+    let storage = Storage::from_texts(posts.into_iter().map(|post| {
+        let meta: Meta = post.meta.unwrap_or_default().into_iter().collect();
+        ((post.title, post.url, meta), post.body.unwrap_or_default())
+    }));
+    let bytes = storage
+        .to_bytes()
+        .map_err(|e| Error::from_reason(e.to_string()))?;
+    Ok(bytes.into())
+}
+
+/// Search a storage file, returning at most `numResults` results (all of them for 0)
+#[napi]
+pub fn search(index: Buffer, query: String, num_results: Option<u32>) -> Result<Vec<SearchResult>> {
+    let storage = Storage::from_bytes(&index).map_err(|e| Error::from_reason(e.to_string()))?;
+    let num_results = num_results.unwrap_or(10) as usize;
+    Ok(storage
+        .search_with_scores(query, num_results)
+        .into_iter()
+        .map(|((title, url, meta), score)| SearchResult {
+            title: title.clone(),
+            url: url.clone(),
+            meta: meta.clone().into_iter().collect(),
+            score: score.into(),
+        })
+        .collect())
+}
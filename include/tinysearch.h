@@ -0,0 +1,48 @@
+/*
+ * C API of tinysearch, see src/capi.rs.
+ *
+ * Build the shared library with
+ *   cargo rustc --release --lib --features capi --crate-type cdylib
+ *
+ * Strings are NUL-terminated UTF-8. Strings returned by the library must be
+ * released with tinysearch_free. On errors, functions return -1 or NULL, and
+ * tinysearch_last_error tells why.
+ */
+
+#ifndef TINYSEARCH_H
+#define TINYSEARCH_H
+
+#include <stddef.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/*
+ * Build an index from a JSON array of posts like
+ * [{"title": "...", "url": "...", "body": "...", "meta": {"date": "..."}}]
+ * and write it to storage_path. Returns 0 on success and -1 on errors.
+ */
+int tinysearch_build_index(const char *posts_json, const char *storage_path);
+
+/*
+ * Search the index at storage_path, returning at most num_results results (all
+ * of them for 0) as a JSON array of [[title, url, meta], score]. Returns NULL on
+ * errors. The result must be released with tinysearch_free.
+ */
+char *tinysearch_search(const char *storage_path, const char *query, size_t num_results);
+
+/* Release a string returned by the library. NULL is ignored. */
+void tinysearch_free(char *s);
+
+/*
+ * The message of the last error on this thread, or NULL. The string belongs to
+ * the library and is valid until the next call that fails.
+ */
+const char *tinysearch_last_error(void);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* TINYSEARCH_H */
@@ -0,0 +1,213 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// How the words of posts and queries are normalized.
+///
+/// The settings are stored in the index, so that queries are analyzed the same way
+/// as the posts were. Each setting only takes effect if the matching cargo feature is
+/// enabled, which keeps the WASM module small for sites that don't need it.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Analyzer {
+    /// Split Chinese, Japanese, and Korean text into character bigrams (`cjk` feature)
+    pub cjk: bool,
+    /// Apply NFKD normalization and strip diacritics, so that "café" matches "cafe"
+    /// (`normalize` feature)
+    pub normalize_unicode: bool,
+    /// Keep digits in words, so that "wasm32" isn't indexed as "wasm"
+    pub digits: bool,
+    /// Punctuation that joins words instead of separating them
+    pub joiners: Joiners,
+}
+
+/// Punctuation kept within words, e.g. the hyphen of "actix-web". Joined words are
+/// indexed along with their parts, so that "actix" finds them as well. Joiners at the
+/// start or end of a word, like the dot at the end of a sentence, still separate it.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Joiners {
+    pub hyphen: bool,
+    pub underscore: bool,
+    pub dot: bool,
+}
+
+impl Joiners {
+    pub fn contains(&self, c: char) -> bool {
+        match c {
+            '-' => self.hyphen,
+            '_' => self.underscore,
+            '.' => self.dot,
+            _ => false,
+        }
+    }
+}
+
+/// The joiners in a string like `-_.`
+impl FromStr for Joiners {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut joiners = Joiners::default();
+        for c in s.chars() {
+            match c {
+                '-' => joiners.hyphen = true,
+                '_' => joiners.underscore = true,
+                '.' => joiners.dot = true,
+                _ => return Err(format!("'{}' can't join words, only '-', '_', and '.'", c)),
+            }
+        }
+        Ok(joiners)
+    }
+}
+
+impl fmt::Display for Joiners {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for c in ['-', '_', '.'] {
+            if self.contains(c) {
+                write!(f, "{}", c)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Analyzer {
+    /// The cargo feature a setting of the analyzer needs, if it isn't enabled. Without
+    /// it queries would be analyzed differently from the posts and silently miss them.
+    pub fn missing_feature(&self) -> Option<&'static str> {
+        if self.cjk && !cfg!(feature = "cjk") {
+            Some("cjk")
+        } else if self.normalize_unicode && !cfg!(feature = "normalize") {
+            Some("normalize")
+        } else {
+            None
+        }
+    }
+
+    /// Turn a lowercase, whitespace-free token into the words that get indexed or searched
+    pub fn words(&self, token: &str) -> Vec<String> {
+        #[cfg(feature = "normalize")]
+        let token = match self.normalize_unicode {
+            true => fold(token),
+            false => token.to_string(),
+        };
+        #[cfg(not(feature = "normalize"))]
+        let token = token.to_string();
+
+        #[cfg(feature = "cjk")]
+        if self.cjk {
+            return crate::cjk::segment(&token);
+        }
+        vec![token]
+    }
+
+    /// Lowercase `s` and split it into words: runs of letters and apostrophes (e.g. of
+    /// "don't"), and of digits and [`Joiners`] if enabled. Everything else separates
+    /// words. Joined words come after their parts.
+    pub fn split(&self, s: &str) -> Vec<String> {
+        let joiner = |c: char| self.joiners.contains(c);
+        let mut words = Vec::new();
+        for run in s
+            .to_lowercase()
+            .split(|c: char| !(self.in_word(c) || joiner(c)))
+        {
+            let word = run.trim_matches(joiner);
+            if word.is_empty() {
+                continue;
+            }
+            let parts: Vec<&str> = word.split(joiner).filter(|part| !part.is_empty()).collect();
+            if parts.len() > 1 {
+                words.extend(parts.into_iter().map(str::to_string));
+            }
+            words.push(word.to_string());
+        }
+        words
+    }
+
+    /// Split `s` into words (see [`Analyzer::split`]) and analyze each of them
+    pub fn tokenize(&self, s: &str) -> Vec<String> {
+        self.split(s)
+            .iter()
+            .flat_map(|token| self.words(token))
+            .filter(|word| !word.is_empty())
+            .collect()
+    }
+
+    /// Whether `c` is part of a word other than a joiner
+    pub(crate) fn in_word(&self, c: char) -> bool {
+        c.is_alphabetic() || c == '\'' || (self.digits && c.is_numeric())
+    }
+}
+
+/// Decompose a word and drop the combining marks, e.g. the accent of "é"
+#[cfg(feature = "normalize")]
+fn fold(word: &str) -> String {
+    use unicode_normalization::char::is_combining_mark;
+    use unicode_normalization::UnicodeNormalization;
+    word.nfkd().filter(|c| !is_combining_mark(*c)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tokenize() {
+        let analyzer = Analyzer::default();
+        assert_eq!(analyzer.tokenize(" Café  au lait "), ["café", "au", "lait"]);
+        assert_eq!(
+            analyzer.tokenize("Don't use wasm32, C-3PO!"),
+            ["don't", "use", "wasm", "c", "po"]
+        );
+    }
+
+    #[test]
+    fn test_digits_and_joiners() {
+        let analyzer = Analyzer {
+            digits: true,
+            joiners: "-_".parse().unwrap(),
+            ..Analyzer::default()
+        };
+        assert_eq!(
+            analyzer.tokenize("wasm32 and actix-web, -flag my_var."),
+            [
+                "wasm32",
+                "and",
+                "actix",
+                "web",
+                "actix-web",
+                "flag",
+                "my",
+                "var",
+                "my_var"
+            ]
+        );
+        assert_eq!(analyzer.joiners.to_string(), "-_");
+        assert!("+".parse::<Joiners>().is_err());
+    }
+
+    #[test]
+    fn test_missing_feature() {
+        assert_eq!(Analyzer::default().missing_feature(), None);
+        let cjk = Analyzer {
+            cjk: true,
+            ..Analyzer::default()
+        };
+        let expected = (!cfg!(feature = "cjk")).then_some("cjk");
+        assert_eq!(cjk.missing_feature(), expected);
+    }
+
+    #[cfg(feature = "normalize")]
+    #[test]
+    fn test_normalize_unicode() {
+        let analyzer = Analyzer {
+            normalize_unicode: true,
+            ..Analyzer::default()
+        };
+        assert_eq!(
+            analyzer.tokenize("Café Crème Ångström ﬁle"),
+            ["cafe", "creme", "angstrom", "file"]
+        );
+        // Decomposed input folds the same way
+        assert_eq!(analyzer.words("cafe\u{301}"), ["cafe"]);
+    }
+}
@@ -3,31 +3,64 @@
 extern crate log;
 
 mod utils;
+use utils::analyze;
 use utils::assets;
+use utils::bench::{self, BenchOptions};
+use utils::build_manifest::{self, BuildManifest, Schema};
+use utils::crawl;
+use utils::csv;
+use utils::deploy::{DeployManifest, MANIFEST_FILE};
+use utils::doctor;
+use utils::engine;
+use utils::http::{self, STDIO};
 use utils::index;
+use utils::inspect;
+use utils::logger::{self, LogFormat};
+use utils::manifest::{Manifest, Site};
+use utils::nojs;
+use utils::progress::{self, ProgressMode};
+use utils::source;
 use utils::storage;
+use utils::strings::Strings;
+use utils::validate;
+#[cfg(feature = "verify")]
+use utils::verify;
 
 use anyhow::{bail, Context};
 pub use anyhow::{Error, Result};
 use argh::FromArgs;
-use std::path::PathBuf;
-use std::process::{Command, Stdio};
+use regex::Regex;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::process::Command;
 use std::str::FromStr;
+use std::time::{Instant, SystemTime};
 use std::{env, fs};
 use tempfile::TempDir;
 use toml_edit::{value, Document};
 
 use index::Posts;
+use source::{ReadOptions, Source};
+use storage::{ContentFormat, Duplicates, FitBy, IndexOptions, StorageFormat};
 use strum::{EnumString, IntoStaticStr};
+use tinysearch::{
+    Analyzer, Boosts, Compression, Joiners, Language, QueryMode, SearchOptions, Sort, TieBreak,
+    CANONICAL_KEY, SECTION_KEY,
+};
 
 fn ensure_exists(path: PathBuf) -> Result<PathBuf, Error> {
+    if path == Path::new(STDIO) {
+        bail!("Only storage mode can write to standard output, use a directory with -p/--path");
+    }
     if !path.exists() {
         fs::create_dir_all(&path)?;
     }
     let path = path.canonicalize()?;
     if !path.exists() {
         for path in fs::read_dir(&path)? {
-            println!("Name: {}", path.unwrap().path().display())
+            debug!("Name: {}", path.unwrap().path().display())
         }
         bail!("Directory could not be created at {}", &path.display());
     }
@@ -68,8 +101,155 @@ impl FromStr for DirOrTemp {
 enum OutputMode {
     Search,
     Storage,
+    Crawl,
     Crate,
     Wasm,
+    #[strum(serialize = "storage-only")]
+    StorageOnly,
+    Engine,
+    Batch,
+    Nojs,
+    Doctor,
+    #[strum(serialize = "verify-deploy")]
+    VerifyDeploy,
+    Bench,
+    Inspect,
+    Explain,
+    Analyze,
+    Validate,
+}
+
+/// Kind of JavaScript module wasm-pack generates
+#[derive(IntoStaticStr, EnumString, Clone, Copy, Default, PartialEq, Eq)]
+#[strum(serialize_all = "snake_case")]
+enum WasmTarget {
+    /// ES module loaded directly by browsers, used by tinysearch.js and the demo
+    #[default]
+    Web,
+    /// ES module for bundlers like webpack or Vite, which load the WASM file themselves
+    Bundler,
+    /// CommonJS module for Node.js
+    Nodejs,
+}
+
+/// What wasm mode writes when the module can't be built
+#[derive(IntoStaticStr, EnumString, Clone, Copy, PartialEq, Eq)]
+#[strum(serialize_all = "snake_case")]
+enum Fallback {
+    /// The index as storage.json and the pure JavaScript search of nojs mode
+    Json,
+}
+
+/// Style of the generated demo.html
+#[derive(IntoStaticStr, EnumString, Clone, Copy, Default, PartialEq, Eq)]
+#[strum(serialize_all = "snake_case")]
+enum DemoTheme {
+    /// A bare-bones page showing how to use the loader
+    #[default]
+    Minimal,
+    /// A page with the <tinysearch-modal> search overlay, opened with `/` or Ctrl+K
+    Docs,
+    /// A search page with the date and tags of posts
+    Blog,
+    /// Don't write a demo
+    None,
+}
+
+/// How validate mode reports the issues it found
+#[derive(IntoStaticStr, EnumString, Clone, Copy, Default, PartialEq, Eq)]
+#[strum(serialize_all = "snake_case")]
+enum ReportFormat {
+    /// A line per issue and a summary
+    #[default]
+    Text,
+    /// A single JSON object, e.g. for CI pipelines
+    Json,
+}
+
+impl DemoTheme {
+    fn html(self) -> Option<&'static str> {
+        match self {
+            DemoTheme::Minimal => Some(assets::DEMO_HTML),
+            DemoTheme::Docs => Some(assets::DEMO_DOCS_HTML),
+            DemoTheme::Blog => Some(assets::DEMO_BLOG_HTML),
+            DemoTheme::None => None,
+        }
+    }
+}
+
+fn parse_source(str: &str) -> Result<&'static dyn Source, String> {
+    source::find(str).ok_or_else(|| {
+        let names: Vec<_> = source::SOURCES.iter().map(|s| s.name()).collect();
+        format!(
+            "Unknown source {}, expected one of {}",
+            str,
+            names.join(", ")
+        )
+    })
+}
+
+fn parse_type_limit(str: &str) -> Result<(String, usize), String> {
+    let (post_type, limit) = str
+        .split_once('=')
+        .ok_or_else(|| format!("Expected <type>=<limit>, got {}", str))?;
+    let limit = limit
+        .parse()
+        .map_err(|e| format!("Invalid limit {}: {}", limit, e))?;
+    Ok((post_type.to_string(), limit))
+}
+
+fn parse_filter(str: &str) -> Result<(String, String), String> {
+    let (field, value) = str
+        .split_once('=')
+        .ok_or_else(|| format!("Expected <field>=<value>, got {}", str))?;
+    Ok((field.to_string(), value.to_string()))
+}
+
+fn parse_size(str: &str) -> Result<usize, String> {
+    let digits = str.find(|c: char| !c.is_ascii_digit()).unwrap_or(str.len());
+    let (number, unit) = str.split_at(digits);
+    let number: usize = number
+        .parse()
+        .map_err(|_| format!("Expected a size like 100KB, got {}", str))?;
+    let unit = match unit.trim().to_ascii_uppercase().as_str() {
+        "" | "B" => 1,
+        "KB" => 1000,
+        "KIB" => 1024,
+        "MB" => 1000 * 1000,
+        "MIB" => 1024 * 1024,
+        _ => {
+            return Err(format!(
+                "Unknown unit {}, expected B, KB, KiB, MB or MiB",
+                unit
+            ))
+        }
+    };
+    Ok(number * unit)
+}
+
+fn parse_column(str: &str) -> Result<(String, String), String> {
+    let (field, column) = str
+        .split_once('=')
+        .ok_or_else(|| format!("Expected <field>=<column>, got {}", str))?;
+    if !csv::FIELDS.contains(&field) {
+        return Err(format!(
+            "Unknown field {}, expected one of {}",
+            field,
+            csv::FIELDS.join(", ")
+        ));
+    }
+    Ok((field.to_string(), column.to_string()))
+}
+
+fn parse_regex(str: &str) -> Result<Regex, String> {
+    Regex::new(str).map_err(|e| e.to_string())
+}
+
+/// A regular expression matching whole strings like the glob `str`, where `*` stands
+/// for any characters and `?` for a single one
+fn parse_glob(str: &str) -> Result<Regex, String> {
+    let pattern = regex::escape(str).replace(r"\*", ".*").replace(r"\?", ".");
+    parse_regex(&format!("^{}$", pattern))
 }
 
 fn parse_engine_version(str: &str) -> Result<toml_edit::Table, String> {
@@ -85,14 +265,44 @@ fn parse_engine_version(str: &str) -> Result<toml_edit::Table, String> {
 /// Valid modes are:
 /// **search** - runs search engine on generated storage data,
 /// **storage** - generates storage data for posts,
+/// **crawl** - crawls the pages of a sitemap and prints them as a JSON index,
 /// **crate** - creates a Rust crate with storage data,
-/// **wasm** - creates a crate and generates a loadable js/wasm script.
+/// **wasm** - creates a crate and generates a loadable js/wasm script,
+/// **storage-only** - pairs the storage with the prebuilt engine (or --engine-dir), without any Rust or WASM tools,
+/// **engine** - builds the index-agnostic module storage-only mode uses,
+/// **batch** - builds the sites listed in a JSON manifest, which share a single module built in -p/--path,
+/// **nojs** - generates tinysearch.nojs.js, a pure JavaScript fallback for browsers without WebAssembly,
+/// **doctor** - checks that all tools needed for wasm mode are installed,
+/// **verify-deploy** - compares the search-manifest.json in -p/--path with the deployed one at --url,
+/// **bench** - builds the storage in memory and measures its size and the latency of queries,
+/// **inspect** - describes a storage file, and which posts contain the words of -S/--search-term,
+/// **explain** - shows how the post at --url scores for -S/--search-term and where it ranks,
+/// **analyze** - prints the terms -S/--search-term is indexed as, with the options of a storage file or the command line,
+/// **validate** - checks the posts of a JSON file without building anything.
 ///
 struct Opt {
     /// show version and exit
     #[argh(switch)]
     version: bool,
 
+    /// show more of what tinysearch does, -vv for everything including dependencies
+    #[argh(switch, short = 'v')]
+    verbose: u8,
+
+    /// only show warnings and errors, not the progress
+    #[argh(switch, short = 'q')]
+    quiet: bool,
+
+    /// how messages are written to standard error: **text** (default) or **json**,
+    /// one object per line, e.g. for CI
+    #[argh(option, long = "log-format", default = "LogFormat::Text")]
+    log_format: LogFormat,
+
+    /// when to show progress bars for indexing and compiling: **auto** (default) when
+    /// standard error is a terminal and without -q, **always**, or **never**
+    #[argh(option, long = "progress", default = "ProgressMode::Auto")]
+    progress: ProgressMode,
+
     /// output mode
     #[argh(option, short = 'm', long = "mode", default = "OutputMode::Wasm")]
     output_mode: OutputMode,
@@ -106,15 +316,270 @@ struct Opt {
     )]
     search_term: String,
 
-    /// number of posts to show in search results (only for search mode)
+    /// number of posts to show in search results, 0 shows all matches (only for search mode)
     #[argh(option, short = 'N', long = "num-searches", default = "5")]
     num_searches: usize,
 
-    /// input file to process (either JSON with posts for code generation or storage for inference)
+    /// read queries from the terminal one line at a time and show the top results of
+    /// each with its timing, until an empty line or end of input (only for search mode)
+    #[argh(switch, long = "interactive")]
+    interactive: bool,
+
+    /// file with the queries to time, one per line (only for bench mode). By default,
+    /// the titles of the posts are searched.
+    #[argh(option, long = "queries")]
+    queries: Option<String>,
+
+    /// how often each query is run (only for bench mode)
+    #[argh(option, long = "iterations", default = "10")]
+    iterations: usize,
+
+    /// how the search term is interpreted (only for search mode). Valid modes are:
+    /// **simple** - all words are optional (default),
+    /// **advanced** - supports "exact phrases", AND, OR, and -excluded words.
+    #[argh(option, long = "query-mode", default = "QueryMode::Simple")]
+    query_mode: QueryMode,
+
+    /// maximum number of results of a post type, given as <type>=<limit>, e.g. blog=2.
+    /// The type of a post is the first segment of its URL path. Can be repeated.
+    /// In storage, crate, and wasm modes the limits are stored as defaults in the index,
+    /// in search mode they apply to the query.
+    #[argh(option, long = "type-limit", from_str_fn(parse_type_limit))]
+    type_limits: Vec<(String, usize)>,
+
+    /// meta field of posts to store as a facet, e.g. category, which searches can
+    /// filter by. Fields holding a JSON list like tags have a value per element.
+    /// Can be repeated.
+    #[argh(option, long = "facet-field")]
+    facet_fields: Vec<String>,
+
+    /// only return posts whose facet has the value, given as <field>=<value>,
+    /// e.g. category=rust (only for search mode). Can be repeated.
+    #[argh(option, long = "filter", from_str_fn(parse_filter))]
+    filters: Vec<(String, String)>,
+
+    /// only return posts of this section: their `section` meta field, or else the
+    /// first segment of their URL path (only for search mode). Can be repeated.
+    #[argh(option, long = "section")]
+    sections: Vec<String>,
+
+    /// only match the query against the given field: title, body, or a facet field
+    /// like tags (only for search mode). Can be repeated, all fields by default.
+    #[argh(option, long = "field")]
+    fields: Vec<String>,
+
+    /// meta field with the date of posts, e.g. date, which searches can sort by or
+    /// boost newer posts with. Understands dates like 2020-01-31 and RFC 3339 timestamps.
+    #[argh(option, long = "date-field")]
+    date_field: Option<String>,
+
+    /// also index pairs of adjacent words, and add the given points for every pair of
+    /// query words that is found in a post in the same order, e.g. "rust async"
+    /// over "async rust". 0 (the default) indexes single words only.
+    #[argh(option, long = "bigram-boost", default = "0")]
+    bigram_boost: usize,
+
+    /// store the words that occur in the same posts as each of the given number of
+    /// words found in the most posts, for suggesting related queries with
+    /// `relatedQueries`, e.g. 200. They're stored in plain text, so this can't be
+    /// combined with --hash-key. 0 (the default) stores none.
+    #[argh(option, long = "related-terms", default = "0")]
+    related_terms: usize,
+
+    /// secret key to hash the words of the index with, so that nobody without the key
+    /// can check whether a word is in the index. **random** generates a key and prints it.
+    /// Searches need the same key: --hash-key in search mode, the hash_key option in JavaScript.
+    #[argh(option, long = "hash-key")]
+    hash_key: Option<String>,
+
+    /// maximum size of the storage file (of each shard), e.g. 100KB or 1MiB. The index
+    /// is cut down as --fit-by says until it fits, and what was cut is printed.
+    #[argh(option, long = "target-size", from_str_fn(parse_size))]
+    target_size: Option<usize>,
+
+    /// how to fit --target-size: drop the words found in the most posts
+    /// (**common-words**, the default), drop those found in the fewest posts
+    /// (**rare-words**), or index only the first words of long bodies (**truncate-bodies**)
+    #[argh(option, long = "fit-by", default = "FitBy::CommonWords")]
+    fit_by: FitBy,
+
+    /// sort the posts by URL before building the index and fail unless a second build
+    /// gives the same bytes, e.g. for storage files tracked in git
+    #[argh(switch, long = "deterministic")]
+    deterministic: bool,
+
+    /// regular expression for text that must not end up in the index, like e-mail
+    /// addresses or internal host names. Matches are removed from titles and bodies
+    /// before indexing, and counted in a report. Can be repeated.
+    #[argh(option, long = "redact", from_str_fn(parse_regex))]
+    redact: Vec<Regex>,
+
+    /// leave out the posts whose URL, or the path of an absolute URL, matches a glob,
+    /// where * stands for any characters, e.g. "/tags/*" for tag pages. Can be repeated.
+    #[argh(option, long = "skip-url", from_str_fn(parse_glob))]
+    skip_urls: Vec<Regex>,
+
+    /// leave out the posts with a meta field of the given value, given as
+    /// <field>=<value>, e.g. draft=true. Can be repeated.
+    #[argh(option, long = "skip-if", from_str_fn(parse_filter))]
+    skip_if: Vec<(String, String)>,
+
+    /// what to do with posts whose URL an earlier post already has.
+    /// Valid strategies are: **keep-first** (default), **keep-last**, and **error** -
+    /// fail the build. Either way, the URLs of several posts are listed.
+    #[argh(option, long = "duplicates", default = "Duplicates::KeepFirst")]
+    duplicates: Duplicates,
+
+    /// order of the results (only for search mode). Valid orders are:
+    /// **relevance** - best matches first (default),
+    /// **date** - newest posts first, needs an index built with --date-field.
+    #[argh(option, long = "sort", default = "Sort::Relevance")]
+    sort: Sort,
+
+    /// order of results with the same score (only for search mode). Valid orders are:
+    /// **index** - the order of the posts in the index (default),
+    /// **title** or **url** - alphabetically,
+    /// **date** - newest posts first.
+    #[argh(option, long = "tie-break", default = "TieBreak::Index")]
+    tie_break: TieBreak,
+
+    /// boost of newer posts, the score of the newest post is multiplied by 1 + recency
+    /// and older posts get proportionally less (only for search mode)
+    #[argh(option, long = "recency", default = "0.0")]
+    recency: f32,
+
+    /// input file to process (either JSON with posts for code generation or storage for inference),
+    /// - for standard input
     #[argh(positional)]
     input_file: Option<PathBuf>,
 
-    /// output path for WASM module ("wasm_output" directory by default)
+    /// where posts are read from (only used in storage, crate, and wasm modes).
+    /// Valid sources are:
+    /// **json** - a JSON file with posts (default), or the same posts as JSON Lines, YAML (.yaml), or TOML (.toml),
+    /// **markdown** - a directory of Markdown files with YAML front matter (e.g. Jekyll's `_posts`),
+    /// **feed** - an RSS 2.0 or Atom feed, given as a file or an http(s) URL,
+    /// **sitemap** - a sitemap whose pages get crawled, given as a file or an http(s) URL,
+    /// **opml** - an OPML outline, e.g. of feeds or bookmarks,
+    /// **bookmarks** - a bookmarks HTML export of a browser,
+    /// **csv** - a CSV file with a header row, e.g. a product catalog (see --column),
+    /// **tsv** - the same with tab-separated columns.
+    #[argh(
+        option,
+        long = "source",
+        from_str_fn(parse_source),
+        default = "source::SOURCES[0]"
+    )]
+    source: &'static dyn Source,
+
+    /// CSS selector for the main content of crawled pages (only used with the sitemap source
+    /// and crawl mode).
+    /// Pages where nothing matches are indexed with their whole body.
+    #[argh(option, long = "content-selector", default = "\"main\".into()")]
+    content_selector: String,
+
+    /// column holding the title, url, or body of posts, e.g. title=product_name (only
+    /// used with the csv and tsv sources). Columns named title, url, and body are used
+    /// by default. Can be repeated to index several columns as the body; all other
+    /// columns become meta fields.
+    #[argh(option, long = "column", from_str_fn(parse_column))]
+    columns: Vec<(String, String)>,
+
+    /// markup of the post bodies, which is stripped before indexing.
+    /// Valid formats are: **markdown**, **html**, **plain**.
+    /// Defaults to plain for the feed, sitemap, csv, and tsv sources, and markdown otherwise.
+    #[argh(option, long = "content-format")]
+    content_format: Option<ContentFormat>,
+
+    /// language of the posts, which selects the stopwords left out of the index and
+    /// out of queries.
+    /// Valid languages are: **en** (default), **de**, **fr**, **es**, **pt**, **it**, **nl**, **ru**.
+    #[argh(option, long = "language", default = "Language::En")]
+    language: Language,
+
+    /// meta field with the language of each post, e.g. `lang` or `locale`, whose
+    /// stopwords are left out of the index of the post instead of those of
+    /// --language. Values like `de` or `de-AT` are understood; posts without a
+    /// supported language use --language.
+    #[argh(option, long = "language-field")]
+    language_field: Option<String>,
+
+    /// leave words with fewer characters out of the index and out of queries (default
+    /// 0, which keeps all words). In search mode, it overrides the minimum the index
+    /// was built with.
+    #[argh(option, long = "min-word-length")]
+    min_word_length: Option<usize>,
+
+    /// split Chinese, Japanese, and Korean text, which has no spaces between words,
+    /// into overlapping pairs of characters. Also enables the same splitting of queries
+    /// in the generated engine.
+    #[argh(switch, long = "cjk")]
+    cjk: bool,
+
+    /// fold diacritics and compatibility characters, so that "café" matches "cafe".
+    /// Also enables the same normalization of queries in the generated engine.
+    #[argh(switch, long = "normalize-unicode")]
+    normalize_unicode: bool,
+
+    /// keep digits in words, so that "wasm32" is indexed as is instead of as "wasm".
+    /// Queries are split the same way.
+    #[argh(switch, long = "digits")]
+    digits: bool,
+
+    /// punctuation that joins words instead of separating them, any of **-**, **_**,
+    /// and **.**, e.g. `--joiners -_` for "actix-web" and "my_var". Joined words are
+    /// indexed along with their parts. Queries are split the same way.
+    #[argh(option, long = "joiners", default = "Joiners::default()")]
+    joiners: Joiners,
+
+    /// weight of a query term found in the post title (default 3). In search mode, it
+    /// overrides the weight the index was built with.
+    #[argh(option, long = "title-boost")]
+    title_boost: Option<usize>,
+
+    /// weight of a query term found in the post body (default 1). In search mode, it
+    /// overrides the weight the index was built with.
+    #[argh(option, long = "body-boost")]
+    body_boost: Option<usize>,
+
+    /// weight of a query term found in a facet field of the post, like tags, but not in
+    /// the body (defaults to the body boost). In search mode, it overrides the weight
+    /// the index was built with.
+    #[argh(option, long = "facet-boost")]
+    facet_boost: Option<usize>,
+
+    /// maximum edit distance of typos that still match. In storage, crate, and wasm modes
+    /// this indexes the variants needed for fuzzy search (roughly doubling the storage size at distance 1),
+    /// in search mode it enables fuzzy search up to the distance the storage was built with.
+    /// Default 0 disables fuzzy matching.
+    #[argh(option, long = "fuzzy", default = "0")]
+    fuzzy: usize,
+
+    /// encoding of the storage file (only used in storage mode). Valid formats are:
+    /// **bincode** - compact binary format, which is embedded in the WASM module (default),
+    /// **json** - written to storage.json, for inspecting the index or loading it from other languages.
+    #[argh(option, long = "storage-format", default = "StorageFormat::Bincode")]
+    storage_format: StorageFormat,
+
+    /// compress the storage, which is decompressed when the WASM module starts.
+    /// Valid compressions are: **none** (default), **gzip**, **brotli**.
+    /// Brotli compresses better, but adds a larger decoder to the module.
+    #[argh(option, long = "compress", default = "Compression::None")]
+    compress: Compression,
+
+    /// split the index into this many shards of consecutive posts. In wasm mode the
+    /// shards are written next to the module and fetched by tinysearch.js when the
+    /// first search runs, instead of being embedded in the module. Default 1 doesn't split.
+    #[argh(option, long = "shards", default = "1")]
+    shards: usize,
+
+    /// don't embed the storage in the WASM module, but write it next to the module,
+    /// where tinysearch.js fetches it when the first search runs. The module then
+    /// stays the same when only the content changes, so it can be cached. Implied by --shards.
+    #[argh(switch, long = "external-storage")]
+    external_storage: bool,
+
+    /// output path for WASM module ("wasm_output" directory by default).
+    /// In storage mode, - writes the storage to standard output.
     #[argh(
         option,
         short = 'p',
@@ -144,10 +609,43 @@ struct Opt {
     )]
     engine_version: toml_edit::Table,
 
+    /// directory with a module to use instead of the prebuilt engine, built with
+    /// `-m engine` or with --external-storage (only for storage-only mode)
+    #[argh(option, long = "engine-dir")]
+    engine_dir: Option<PathBuf>,
+
+    /// URL of the search-manifest.json of the deployed site, e.g.
+    /// https://example.com/search/search-manifest.json (for verify-deploy mode), or
+    /// URL of the post to explain, as it was indexed (for explain mode)
+    #[argh(option, long = "url")]
+    url: Option<String>,
+
     /// this name will be used in Cargo.toml for the generated crate (only used in wasm and crate modes)
     #[argh(option, long = "crate-name", default = "\"tinysearch-engine\".into()")]
     crate_name: String,
 
+    /// leave the meta field of posts out of the index and out of the search results
+    /// of the generated engine, for smaller storage and result payloads
+    #[argh(switch, long = "no-meta")]
+    no_meta: bool,
+
+    /// meta field to include in the search results of the generated engine, e.g.
+    /// excerpt or date. Can be repeated. The other meta fields are left out of the
+    /// results, and out of the index unless they are needed for facets, dates, or
+    /// canonical URLs. By default, results include all meta fields.
+    #[argh(option, long = "result-field")]
+    result_fields: Vec<String>,
+
+    /// store a plain text excerpt of each post in the `excerpt` field of its meta,
+    /// so that search results can show a preview. This grows the index by about
+    /// the excerpt length per post.
+    #[argh(switch, long = "store-excerpt")]
+    store_excerpt: bool,
+
+    /// maximum length of the excerpts in characters (default 200)
+    #[argh(option, long = "excerpt-length", default = "200")]
+    excerpt_length: usize,
+
     /// removes all top-level configs from Cargo.toml of generated crate and makes it locally importable (only makes sense in crate mode)
     #[argh(switch, long = "non-top-level-crate")]
     non_top_level_crate: bool,
@@ -155,12 +653,93 @@ struct Opt {
     /// optimize the output using binaryen (only valid in wasm mode)
     #[argh(switch, short = 'o', long = "optimize")]
     optimize: bool,
+
+    /// keep the unoptimized module and write the optimized one next to it as
+    /// `<name>_bg.opt.wasm`, printing both sizes (only valid in wasm mode).
+    /// Skipped with a warning if wasm-opt is not installed.
+    #[argh(switch, long = "keep-unoptimized")]
+    keep_unoptimized: bool,
+
+    /// build the engine in debug mode with debug info and without optimizations,
+    /// so that panics in the browser can be symbolized (only valid in wasm mode)
+    #[argh(switch, long = "debug-engine")]
+    debug_engine: bool,
+
+    /// kind of JavaScript module generated by wasm-pack (only valid in wasm mode).
+    /// Valid targets are: **web** - an ES module for browsers, along with
+    /// the tinysearch.js loader and the demo (default), **bundler** - an ES module for
+    /// bundlers like webpack or Vite, **nodejs** - a CommonJS module for Node.js.
+    #[argh(option, long = "wasm-target", default = "WasmTarget::Web")]
+    wasm_target: WasmTarget,
+
+    /// what to write instead of the module when the wasm32-unknown-unknown target
+    /// isn't installed (only valid in wasm mode). Without it, the build fails.
+    /// Valid fallbacks are: **json** - the index as storage.json and tinysearch.nojs.js,
+    /// the pure JavaScript search of nojs mode.
+    #[argh(option, long = "fallback")]
+    fallback: Option<Fallback>,
+
+    /// run the engine in a Web Worker (only valid in wasm mode). tinysearch.js then
+    /// forwards searches to tinysearch.worker.js, so they don't block the page.
+    #[argh(switch, long = "worker")]
+    worker: bool,
+
+    /// style of the generated demo.html (only valid in wasm mode). Valid themes are:
+    /// **minimal** (default), **docs** - a modal opened with `/` or Ctrl+K,
+    /// **blog** - a search page showing dates and tags, **none** - no demo.
+    #[argh(option, long = "demo-theme", default = "DemoTheme::Minimal")]
+    demo_theme: DemoTheme,
+
+    /// how validate mode reports issues: **text** (default) or **json**
+    #[argh(option, long = "format", default = "ReportFormat::Text")]
+    format: ReportFormat,
+
+    /// write tinysearch-modal.js, which defines a <tinysearch-modal> search overlay
+    /// opened with `/` or Ctrl+K (only valid in wasm mode, implied by --demo-theme docs)
+    #[argh(switch, long = "modal")]
+    modal: bool,
+
+    /// JSON file with the texts of the demo and the modal, to translate them
+    /// (only valid in wasm mode). Keys are `placeholder`, `no_results`, `one_result`,
+    /// and `results`, where `{{n}}` is replaced by the number of results.
+    #[argh(option, long = "strings")]
+    strings: Option<PathBuf>,
 }
 
 trait Stage: Sized {
     fn from_opt(opt: &Opt) -> Result<Self, Error>;
 
     fn build(&self) -> Result<(), Error>;
+
+    /// Directories the stage writes files to, which get a build manifest
+    fn output_dirs(&self) -> Vec<&Path> {
+        Vec::new()
+    }
+}
+
+/// Run a stage and write a build manifest to each of its output directories
+fn build_stage(stage: impl Stage, opt: &Opt) -> Result<(), Error> {
+    let started = SystemTime::now();
+    stage.build()?;
+    for dir in stage.output_dirs() {
+        let schema = Schema {
+            storage_version: tinysearch::STORAGE_VERSION,
+            storage_format: Into::<&'static str>::into(opt.storage_format).to_string(),
+            facet_fields: opt.facet_fields.clone(),
+            date_field: opt.date_field.clone(),
+            result_fields: opt.result_fields.clone(),
+            no_meta: opt.no_meta,
+        };
+        BuildManifest::new(
+            Into::<&'static str>::into(&opt.output_mode),
+            opt.input_file.as_deref(),
+            schema,
+            dir,
+            started,
+        )?
+        .write(dir)?;
+    }
+    Ok(())
 }
 
 #[derive(Default)]
@@ -168,29 +747,40 @@ struct Search {
     storage_file: PathBuf,
     term: String,
     num_searches: usize,
+    interactive: bool,
+    options: SearchOptions,
 }
 
 impl Stage for Search {
     fn from_opt(opt: &Opt) -> Result<Self, Error> {
         let input = opt.input_file.clone().context("Missing input file")?;
         let term = opt.search_term.clone();
-        Ok(Self {
-            storage_file: input
+        let storage_file = match input == Path::new(STDIO) {
+            true if opt.interactive => {
+                bail!("Interactive search reads queries from stdin, so the index can't come from there")
+            }
+            true => input,
+            false => input
                 .canonicalize()
                 .with_context(|| format!("Failed to find file: {}", input.display()))?,
+        };
+        Ok(Self {
+            storage_file,
             term,
             num_searches: opt.num_searches,
+            interactive: opt.interactive,
+            options: Search::options(opt),
         })
     }
 
     fn build(&self) -> Result<(), Error> {
-        use tinysearch::{search as base_search, Storage};
-        let bytes = fs::read(&self.storage_file).with_context(|| {
-            format!("Failed to read input file: {}", self.storage_file.display())
-        })?;
-        let filters = Storage::from_bytes(&bytes)?.filters;
-        let results = base_search(&filters, self.term.clone(), self.num_searches);
-        for result in results {
+        let storage = storage::read(&self.storage_file)?;
+        if self.interactive {
+            return self.prompt(&storage);
+        }
+        let results =
+            storage.search_with_options(self.term.clone(), self.num_searches, &self.options);
+        for (result, _score) in results {
             println!(
                 "Title: {}, Url: {}, Meta: {:?}",
                 result.0, result.1, result.2
@@ -200,46 +790,407 @@ impl Stage for Search {
     }
 }
 
-#[derive(Default)]
+impl Search {
+    /// Query-time options given on the command line
+    fn options(opt: &Opt) -> SearchOptions {
+        let mut options = SearchOptions::default()
+            .with_query_mode(opt.query_mode)
+            .with_fuzzy(opt.fuzzy)
+            .with_sort(opt.sort)
+            .with_tie_break(opt.tie_break)
+            .with_recency(opt.recency);
+        for (post_type, limit) in &opt.type_limits {
+            options = options.with_type_limit(post_type, *limit);
+        }
+        for (field, value) in &opt.filters {
+            options = options.with_filter(field, value);
+        }
+        for section in &opt.sections {
+            options = options.with_section(section);
+        }
+        for field in &opt.fields {
+            options = options.with_field(field);
+        }
+        if let Some(key) = &opt.hash_key {
+            options = options.with_hash_key(key);
+        }
+        if let Some(boost) = opt.title_boost {
+            options = options.with_title_boost(boost);
+        }
+        if let Some(boost) = opt.body_boost {
+            options = options.with_body_boost(boost);
+        }
+        if let Some(boost) = opt.facet_boost {
+            options = options.with_facet_boost(boost);
+        }
+        if let Some(min_word_len) = opt.min_word_length {
+            options = options.with_min_word_len(min_word_len);
+        }
+        options
+    }
+
+    /// Search for each line read from stdin, e.g. to try out an index without a browser
+    fn prompt(&self, storage: &tinysearch::Storage) -> Result<(), Error> {
+        eprintln!(
+            "Searching {} posts, enter an empty line or press Ctrl-D to quit",
+            storage.filters.len()
+        );
+        let mut stdin = io::stdin().lock();
+        let mut line = String::new();
+        loop {
+            print!("> ");
+            io::stdout().flush()?;
+            line.clear();
+            if stdin.read_line(&mut line)? == 0 {
+                // Ctrl-D, move the shell prompt to its own line
+                println!();
+                return Ok(());
+            }
+            let query = line.trim();
+            if query.is_empty() {
+                return Ok(());
+            }
+            let start = Instant::now();
+            let results =
+                storage.search_with_options(query.to_string(), self.num_searches, &self.options);
+            let elapsed = start.elapsed();
+            for (rank, (result, score)) in results.iter().enumerate() {
+                println!(
+                    "{:>3}. {} ({}) score {:.3}",
+                    rank + 1,
+                    result.0,
+                    result.1,
+                    score
+                );
+            }
+            println!(
+                "{} results in {:.2} ms",
+                results.len(),
+                elapsed.as_secs_f64() * 1000.0
+            );
+        }
+    }
+}
+
 struct Storage {
     posts_index: PathBuf,
+    source: &'static dyn Source,
+    content_selector: String,
+    columns: Vec<(String, String)>,
+    index_options: IndexOptions,
+    no_meta: bool,
+    /// Meta fields of search results, all of them if empty
+    result_fields: Vec<String>,
+    /// Length of the excerpts to store, if any
+    excerpt_length: Option<usize>,
+    /// Patterns removed from titles and bodies
+    redact: Vec<Regex>,
+    skip_urls: Vec<Regex>,
+    skip_if: Vec<(String, String)>,
+    duplicates: Duplicates,
+    external_storage: bool,
     out_path: PathBuf,
 }
 
+impl Storage {
+    fn read_posts(&self) -> Result<Posts, Error> {
+        let options = ReadOptions {
+            content_selector: &self.content_selector,
+            columns: &self.columns,
+        };
+        self.source.read(&self.posts_index, &options)
+    }
+
+    fn posts(&self) -> Result<Posts, Error> {
+        let mut posts: Posts = self.read_posts()?;
+        if !self.skip_urls.is_empty() || !self.skip_if.is_empty() {
+            let skipped = storage::skip(&mut posts, &self.skip_urls, &self.skip_if);
+            info!("Skipped {} posts", skipped);
+        }
+        let conflicts = storage::dedup(&mut posts, self.duplicates)?;
+        if !conflicts.is_empty() {
+            let examples: Vec<&str> = conflicts.iter().take(20).map(String::as_str).collect();
+            warn!(
+                "Kept one post for each of {} URLs with several posts: {}{}",
+                conflicts.len(),
+                examples.join(", "),
+                if conflicts.len() > examples.len() {
+                    ", ..."
+                } else {
+                    ""
+                }
+            );
+        }
+        if !self.redact.is_empty() {
+            let counts = storage::redact(&mut posts, &self.redact);
+            for (pattern, count) in self.redact.iter().zip(counts) {
+                info!("Redacted {} matches of {}", count, pattern);
+            }
+        }
+        storage::resolve_aliases(&mut posts);
+        if self.no_meta || !self.result_fields.is_empty() {
+            // Facets and dates are read from the meta fields, and searches skip
+            // duplicates by their canonical URL
+            let options = &self.index_options;
+            for post in &mut posts {
+                post.meta.retain(|field, _| {
+                    self.result_fields.contains(field)
+                        || options.facet_fields.contains(field)
+                        || options.date_field.as_ref() == Some(field)
+                        || field == CANONICAL_KEY
+                        || field == SECTION_KEY
+                });
+            }
+        }
+        if let Some(length) = self.excerpt_length {
+            let added = storage::add_excerpts(&mut posts, self.index_options.format, length);
+            warn!(
+                "Excerpts add {} KB to the index before compression",
+                added.div_ceil(1024)
+            );
+        }
+        trace!("Generating storage from posts: {:#?}", posts);
+        Ok(posts)
+    }
+
+    /// Weights of the fields the index is built with, the defaults unless given
+    fn boosts(opt: &Opt) -> Boosts {
+        let defaults = Boosts::default();
+        let body = opt.body_boost.unwrap_or(defaults.body);
+        Boosts {
+            title: opt.title_boost.unwrap_or(defaults.title),
+            body,
+            facets: opt.facet_boost.unwrap_or(body),
+        }
+    }
+
+    /// The analyzer set up with --cjk, --normalize-unicode, --digits and --joiners
+    fn analyzer(opt: &Opt) -> Analyzer {
+        Analyzer {
+            cjk: opt.cjk,
+            normalize_unicode: opt.normalize_unicode,
+            digits: opt.digits,
+            joiners: opt.joiners,
+        }
+    }
+
+    /// The key given with --hash-key, or a new one for `random`
+    fn hash_key(opt: &Opt) -> String {
+        match opt.hash_key.as_deref() {
+            Some("random") => {
+                let key = format!(
+                    "{:016x}{:016x}",
+                    RandomState::new().build_hasher().finish(),
+                    RandomState::new().build_hasher().finish()
+                );
+                info!("Hashing the index with the key {}, searches need it", key);
+                key
+            }
+            key => key.unwrap_or_default().to_string(),
+        }
+    }
+
+    /// Whether the engine fetches the storage at runtime, as one or more shards,
+    /// instead of embedding it
+    fn loaded_at_runtime(&self) -> bool {
+        self.index_options.shards > 1 || self.external_storage
+    }
+
+    /// Names of the shard files written to the output directory, in order
+    fn shard_files(&self) -> Vec<String> {
+        let format = self.index_options.storage_format;
+        // There are fewer shards than requested if there are fewer posts
+        (0..self.index_options.shards.max(1))
+            .map(|shard| format.shard_file_name(shard))
+            .take_while(|name| self.out_path.join(name).exists())
+            .collect()
+    }
+
+    /// Fingerprint of the posts in the storage files written by `build`, see
+    /// `DeployManifest::corpus_hash`
+    fn corpus_hash(&self) -> Result<String, Error> {
+        let files = match self.loaded_at_runtime() {
+            true => self.shard_files(),
+            false => vec![self.index_options.storage_format.file_name().to_string()],
+        };
+        let hashes = files
+            .iter()
+            .map(|name| Ok(storage::read(&self.out_path.join(name))?.corpus_hash))
+            .collect::<Result<Vec<String>, Error>>()?;
+        Ok(match hashes.as_slice() {
+            [hash] => hash.clone(),
+            _ => storage::fingerprint(hashes.join(",").as_bytes()),
+        })
+    }
+
+    /// Describe the output in `search-manifest.json`, for `verify-deploy`
+    fn write_manifest(
+        &self,
+        dir: &Path,
+        module_hash: &str,
+        shard_hashes: &[String],
+    ) -> Result<(), Error> {
+        DeployManifest {
+            tinysearch: env!("CARGO_PKG_VERSION").to_string(),
+            corpus_hash: self.corpus_hash()?,
+            module_hash: module_hash.to_string(),
+            shards: shard_hashes.to_vec(),
+        }
+        .write(dir)
+    }
+
+    fn build_shards(&self) -> Result<(), Error> {
+        info!(
+            "Creating {} storage shard(s) for posts {} in {}",
+            self.index_options.shards.max(1),
+            self.posts_index.display(),
+            self.out_path.display()
+        );
+        let posts = self.posts()?;
+        let shards = storage::write_shards(posts, &self.out_path, &self.index_options)?;
+        for shard in shards {
+            info!("Storage ready in file {}", shard.display());
+        }
+        Ok(())
+    }
+}
+
 impl Stage for Storage {
     fn from_opt(opt: &Opt) -> Result<Self, Error> {
+        if opt.store_excerpt && opt.no_meta {
+            bail!("--store-excerpt stores the excerpts in the meta field, which --no-meta removes");
+        }
+        if opt.no_meta && !opt.result_fields.is_empty() {
+            bail!("--no-meta leaves out all meta fields, use either it or --result-field");
+        }
+        if opt.store_excerpt
+            && !opt.result_fields.is_empty()
+            && !opt.result_fields.iter().any(|field| field == "excerpt")
+        {
+            bail!("--store-excerpt needs --result-field excerpt to show the excerpts in results");
+        }
+        if opt.related_terms > 0 && opt.hash_key.is_some() {
+            bail!("--related-terms stores words as they are, so --hash-key can't hide them");
+        }
+        let out_path = if opt.out_path == Path::new(STDIO) {
+            if !matches!(opt.output_mode, OutputMode::Storage) {
+                bail!("Only storage mode can write to standard output, use a directory with -p/--path");
+            }
+            if opt.shards > 1 || opt.external_storage {
+                bail!("--shards and --external-storage write a file per shard, which can't go to standard output");
+            }
+            opt.out_path.clone()
+        } else {
+            ensure_exists(opt.out_path.clone())?
+        };
         Ok(Self {
             posts_index: opt.input_file.clone().context("No input file")?,
-            out_path: ensure_exists(opt.out_path.clone())?,
+            source: opt.source,
+            content_selector: opt.content_selector.clone(),
+            columns: opt.columns.clone(),
+            index_options: IndexOptions {
+                format: opt
+                    .content_format
+                    .unwrap_or_else(|| opt.source.content_format()),
+                language: opt.language,
+                language_field: opt.language_field.clone(),
+                min_word_len: opt.min_word_length.unwrap_or_default(),
+                boosts: Storage::boosts(opt),
+                typo_distance: opt.fuzzy,
+                analyzer: Storage::analyzer(opt),
+                type_limits: opt.type_limits.iter().cloned().collect(),
+                storage_format: opt.storage_format,
+                compression: opt.compress,
+                shards: opt.shards,
+                facet_fields: opt.facet_fields.clone(),
+                date_field: opt.date_field.clone(),
+                bigram_boost: opt.bigram_boost,
+                related_terms: opt.related_terms,
+                hash_key: Storage::hash_key(opt),
+                target_size: opt.target_size,
+                fit_by: opt.fit_by,
+                deterministic: opt.deterministic,
+            },
+            no_meta: opt.no_meta,
+            result_fields: opt.result_fields.clone(),
+            excerpt_length: opt.store_excerpt.then_some(opt.excerpt_length),
+            redact: opt.redact.clone(),
+            skip_urls: opt.skip_urls.clone(),
+            skip_if: opt.skip_if.clone(),
+            duplicates: opt.duplicates,
+            external_storage: opt.external_storage,
+            out_path,
         })
     }
 
     fn build(&self) -> Result<(), Error> {
-        let storage_file = self.out_path.join("storage");
-        println!(
+        if self.loaded_at_runtime() {
+            return self.build_shards();
+        }
+        if self.out_path == Path::new(STDIO) {
+            // Standard output only gets the storage, messages go to standard error
+            info!(
+                "Creating storage for posts {} on standard output",
+                self.posts_index.display()
+            );
+            let bytes = storage::build(self.posts()?, &self.index_options)?;
+            return io::stdout()
+                .write_all(&bytes)
+                .context("Failed to write the storage to standard output");
+        }
+        let storage_file = self
+            .out_path
+            .join(self.index_options.storage_format.file_name());
+        info!(
             "Creating storage file for posts {} in file {}",
             self.posts_index.display(),
             storage_file.display()
         );
-        let posts: Posts = index::read(
-            fs::read_to_string(&self.posts_index)
-                .with_context(|| format!("Failed to read file {}", self.posts_index.display()))?,
-        )
-        .with_context(|| format!("Failed to decode {}", self.posts_index.display()))?;
-        trace!("Generating storage from posts: {:#?}", posts);
-        storage::write(posts, &storage_file)?;
-        println!("Storage ready in file {}", storage_file.display());
+        let posts = self.posts()?;
+        storage::write(posts, &storage_file, &self.index_options)?;
+        info!("Storage ready in file {}", storage_file.display());
+        Ok(())
+    }
+
+    fn output_dirs(&self) -> Vec<&Path> {
+        match self.out_path == Path::new(STDIO) {
+            true => Vec::new(),
+            false => vec![&self.out_path],
+        }
+    }
+}
+
+struct Crawl {
+    sitemap: String,
+    content_selector: String,
+}
+
+impl Stage for Crawl {
+    fn from_opt(opt: &Opt) -> Result<Self, Error> {
+        let sitemap = opt.input_file.clone().context("Missing sitemap")?;
+        Ok(Self {
+            sitemap: sitemap.display().to_string(),
+            content_selector: opt.content_selector.clone(),
+        })
+    }
+
+    fn build(&self) -> Result<(), Error> {
+        let posts = crawl::read(&self.sitemap, &self.content_selector)?;
+        println!("{}", serde_json::to_string_pretty(&posts)?);
+        info!("Crawled {} pages", posts.len());
         Ok(())
     }
 }
 
-#[derive(Default)]
 struct Crate {
     s: Storage,
     out_path: PathBuf,
     crate_name: String,
     engine_version: toml_edit::Table,
     non_top_level: bool,
+    no_meta: bool,
+    /// Build an engine for any index rather than for this one, see `Engine`
+    generic: bool,
 }
 
 impl Stage for Crate {
@@ -247,6 +1198,9 @@ impl Stage for Crate {
         if opt.crate_path.is_some() {
             bail!("Don't use --crate-path to specify crate output dir!");
         }
+        if opt.storage_format != StorageFormat::Bincode {
+            bail!("The engine crate embeds its storage as bincode, use --storage-format only in storage mode");
+        }
         let out_path = ensure_exists(opt.out_path.clone())?;
         let storage_opt = {
             let mut ret: Opt = opt.clone();
@@ -260,11 +1214,13 @@ impl Stage for Crate {
             crate_name: opt.crate_name.clone(),
             engine_version: opt.engine_version.clone(),
             non_top_level: opt.non_top_level_crate,
+            no_meta: opt.no_meta,
+            generic: false,
         })
     }
 
     fn build(&self) -> Result<(), Error> {
-        println!(
+        info!(
             "Creating tinysearch implementation crate {} in directory {}",
             self.crate_name,
             self.out_path.display()
@@ -274,12 +1230,52 @@ impl Stage for Crate {
         cargo_toml_contents["package"]["name"] = value(self.crate_name.clone());
         cargo_toml_contents["dependencies"]["tinysearch"] =
             toml_edit::Item::Table(self.engine_version.clone());
+        // Queries need to be analyzed the same way as the posts. A generic engine
+        // supports all analyzers, and follows the settings stored in each index.
+        let analyzer = self.s.index_options.analyzer;
+        let mut features = toml_edit::Array::new();
+        if analyzer.cjk || self.generic {
+            features.push("cjk");
+        }
+        if analyzer.normalize_unicode || self.generic {
+            features.push("normalize");
+        }
+        // The engine has to decompress the storage
+        match self.s.index_options.compression {
+            _ if self.generic => {
+                features.push("gzip");
+                features.push("brotli");
+            }
+            Compression::None => {}
+            Compression::Gzip => features.push("gzip"),
+            Compression::Brotli => features.push("brotli"),
+        }
+        if !features.is_empty() {
+            cargo_toml_contents["dependencies"]["tinysearch"]["features"] = value(features);
+        }
         if self.non_top_level {
             cargo_toml_contents.as_table_mut().remove("workspace");
             cargo_toml_contents.as_table_mut().remove("profile");
             cargo_toml_contents.as_table_mut().remove("lib");
             cargo_toml_contents["lib"] = toml_edit::table();
         }
+        let result_fields = !self.s.result_fields.is_empty();
+        if self.no_meta || result_fields || self.s.loaded_at_runtime() {
+            let mut default_features = toml_edit::Array::new();
+            default_features.push("bind");
+            if !self.no_meta {
+                default_features.push("meta");
+            }
+            // The engine leaves the other meta fields out of the results
+            if result_fields {
+                default_features.push("result-fields");
+            }
+            // The shards are loaded at runtime instead of being embedded
+            if self.s.loaded_at_runtime() {
+                default_features.push("shards");
+            }
+            cargo_toml_contents["features"]["default"] = value(default_features);
+        }
         fs::write(cargo_toml, cargo_toml_contents.to_string())?;
 
         // let mut file = fs::OpenOptions::new().write(true).truncate(true).open(&cargo_toml)?;
@@ -290,17 +1286,33 @@ impl Stage for Crate {
             self.out_path.join("src").join("lib.rs"),
             assets::CRATE_LIB_RS,
         )?;
-        println!("Crate content generated in {}/", &self.out_path.display());
+        // Only read with the result-fields feature
+        fs::write(
+            self.out_path.join("src").join("result_fields"),
+            self.s.result_fields.join("\n"),
+        )?;
+        info!("Crate content generated in {}/", &self.out_path.display());
         Ok(())
     }
+
+    fn output_dirs(&self) -> Vec<&Path> {
+        vec![&self.out_path]
+    }
 }
 
-#[derive(Default)]
 struct Wasm {
     c: Crate,
     out_path: PathBuf,
     crate_path: DirOrTemp,
     optimize: bool,
+    keep_unoptimized: bool,
+    debug_engine: bool,
+    target: WasmTarget,
+    worker: bool,
+    demo_theme: DemoTheme,
+    modal: bool,
+    strings: Strings,
+    fallback: Option<Fallback>,
 }
 
 impl Wasm {
@@ -310,10 +1322,111 @@ impl Wasm {
             None => DirOrTemp::default(),
         })
     }
+
+    fn optimize_side_by_side(&self, wasm_file: &str, opt_file: &str) -> Result<(), Error> {
+        if Command::new("wasm-opt").arg("--version").output().is_err() {
+            warn!(
+                "wasm-opt not found, only the unoptimized module {} was written",
+                wasm_file
+            );
+            return Ok(());
+        }
+        run_output(
+            Command::new("wasm-opt")
+                .current_dir(&self.out_path)
+                .arg("-Oz")
+                .arg("-o")
+                .arg(opt_file)
+                .arg(wasm_file),
+        )?;
+        let size = |file: &str| -> Result<u64, Error> {
+            Ok(fs::metadata(self.out_path.join(file))
+                .with_context(|| format!("Failed to read size of {}", file))?
+                .len())
+        };
+        let (before, after) = (size(wasm_file)?, size(opt_file)?);
+        info!("{:>10} bytes  {}", before, wasm_file);
+        info!(
+            "{:>10} bytes  {} ({:.1}% smaller)",
+            after,
+            opt_file,
+            100.0 * (1.0 - after as f64 / before.max(1) as f64)
+        );
+        Ok(())
+    }
+
+    /// Write the index as storage.json along with the JavaScript search of nojs mode,
+    /// for builds without the wasm32 target
+    fn build_fallback(&self) -> Result<(), Error> {
+        let posts = self.c.s.posts()?;
+        let options = IndexOptions {
+            storage_format: StorageFormat::Json,
+            compression: Compression::None,
+            ..self.c.s.index_options.clone()
+        };
+        let storage_file = self.out_path.join(StorageFormat::Json.file_name());
+        storage::write(posts.clone(), &storage_file, &options)?;
+        let nojs_file = self.out_path.join("tinysearch.nojs.js");
+        nojs::write(posts, &nojs_file, &options)?;
+        info!(
+            "All done! Wrote {} and {}, import the latter to search from your site",
+            storage_file.display(),
+            nojs_file.display()
+        );
+        Ok(())
+    }
+
+    /// Copy the storage shards next to the module, returning the hash of each
+    fn copy_shards(&self) -> Result<Vec<String>, Error> {
+        let mut hashes = Vec::new();
+        if !self.c.s.loaded_at_runtime() {
+            return Ok(hashes);
+        }
+        for name in self.c.s.shard_files() {
+            let source = self.c.s.out_path.join(&name);
+            fs::copy(&source, self.out_path.join(&name))
+                .with_context(|| format!("Failed copying shard {}", name))?;
+            hashes.push(fs::read_to_string(source.with_extension("hash"))?);
+        }
+        Ok(hashes)
+    }
+
+    /// Run a query for the first word of the first post title in the built module
+    #[cfg(feature = "verify")]
+    fn verify(&self, wasm_file: &std::path::Path) -> Result<(), Error> {
+        let options = &self.c.s.index_options;
+        let storage_file = match self.c.s.loaded_at_runtime() {
+            false => options.storage_format.file_name().to_string(),
+            true => options.storage_format.shard_file_name(0),
+        };
+        let bytes = fs::read(self.c.s.out_path.join(storage_file))?;
+        let storage = tinysearch::Storage::from_bytes(&bytes)?;
+        let query = storage
+            .filters
+            .first()
+            .and_then(|(post_id, _)| post_id.0.split_whitespace().next())
+            .unwrap_or("search");
+        info!(
+            "Verifying {} with the query \"{}\"",
+            wasm_file.display(),
+            query
+        );
+        verify::run(wasm_file, query)
+            .with_context(|| format!("Verification of {} failed", wasm_file.display()))
+    }
 }
 
 impl Stage for Wasm {
     fn from_opt(opt: &Opt) -> Result<Self, Error> {
+        if opt.debug_engine && (opt.optimize || opt.keep_unoptimized) {
+            bail!("--debug-engine can't be combined with --optimize or --keep-unoptimized");
+        }
+        if opt.worker && opt.wasm_target != WasmTarget::Web {
+            bail!("--worker is only supported with the web target");
+        }
+        if opt.fallback.is_some() && opt.hash_key.is_some() {
+            bail!("The JavaScript fallback stores the words of posts as they are, so --hash-key can't hide them");
+        }
         let crate_path = Wasm::ensure_crate_path(&opt.crate_path)?;
         let crate_opt = {
             let mut ret: Opt = opt.clone();
@@ -326,27 +1439,58 @@ impl Stage for Wasm {
             out_path: ensure_exists(opt.out_path.clone())?,
             crate_path,
             optimize: opt.optimize,
+            keep_unoptimized: opt.keep_unoptimized,
+            debug_engine: opt.debug_engine,
+            target: opt.wasm_target,
+            worker: opt.worker,
+            demo_theme: opt.demo_theme,
+            // The docs demo is built around the modal
+            modal: opt.modal || opt.demo_theme == DemoTheme::Docs,
+            strings: match &opt.strings {
+                Some(path) => Strings::read(path)?,
+                None => Strings::default(),
+            },
+            fallback: opt.fallback,
         })
     }
 
     fn build(self: &Wasm) -> Result<(), Error> {
+        if doctor::wasm_target_installed() == Some(false) {
+            if self.fallback != Some(Fallback::Json) {
+                bail!(
+                    "The wasm32-unknown-unknown target isn't installed. Install it with \
+                     `rustup target add wasm32-unknown-unknown`, or pass --fallback json to \
+                     write a pure JavaScript search instead"
+                );
+            }
+            warn!("The wasm32-unknown-unknown target isn't installed, writing the JavaScript fallback instead");
+            return self.build_fallback();
+        }
         self.c.build().context("Failed generating crate")?;
-        println!("Compiling WASM module using wasm-pack");
+        info!("Compiling WASM module using wasm-pack");
         let crate_path = self.crate_path.path();
         run_output(
             Command::new("wasm-pack")
                 .arg("build")
                 .arg(&crate_path)
                 .arg("--target")
-                .arg("web")
-                .arg("--release")
+                .arg(Into::<&'static str>::into(self.target))
+                .arg(if self.debug_engine {
+                    "--dev"
+                } else {
+                    "--release"
+                })
                 .arg("--out-dir")
                 .arg(&self.out_path),
-        )?;
+        )
+        .context("Run `tinysearch -m doctor` to check that all required tools are installed")?;
         let wasm_name = self.c.crate_name.replace('-', "_");
 
-        if self.optimize {
-            let wasm_file = format!("{}_bg.wasm", &wasm_name);
+        let wasm_file = format!("{}_bg.wasm", &wasm_name);
+        let opt_file = format!("{}_bg.opt.wasm", &wasm_name);
+        if self.keep_unoptimized {
+            self.optimize_side_by_side(&wasm_file, &opt_file)?;
+        } else if self.optimize {
             run_output(
                 Command::new("wasm-opt")
                     .current_dir(&self.out_path)
@@ -356,25 +1500,685 @@ impl Stage for Wasm {
                     .arg(&wasm_file),
             )?;
         }
+        #[cfg(feature = "verify")]
+        for file in [&wasm_file, &opt_file] {
+            let path = self.out_path.join(file);
+            if path.exists() {
+                self.verify(&path)?;
+            }
+        }
+        let hash_file = self.out_path.join("storage.hash");
+        fs::copy(self.c.s.out_path.join("storage.hash"), &hash_file)
+            .with_context(|| format!("Failed writing {}", hash_file.display()))?;
+        let shard_hashes = self.copy_shards()?;
+        // A module without the storage only changes along with the engine
+        let module_hash = match self.c.s.loaded_at_runtime() {
+            true => storage::fingerprint(&fs::read(self.out_path.join(&wasm_file))?),
+            false => fs::read_to_string(&hash_file)?,
+        };
+        self.c
+            .s
+            .write_manifest(&self.out_path, &module_hash, &shard_hashes)?;
+        if self.target != WasmTarget::Web {
+            // The loader and the demo rely on the init function of the web target
+            info!(
+                "All done! Import {}.js from {}",
+                wasm_name,
+                self.out_path.display()
+            );
+            return Ok(());
+        }
+
+        let strings = self.strings.to_js()?;
+        let fill = |template: &str| {
+            assets::with_results(template)
+                .replace("{WASM_NAME}", &wasm_name)
+                .replace("{MODULE_HASH}", &module_hash)
+                .replace("{SHARDS}", &format!("{:?}", shard_hashes))
+                // Last, so that the strings themselves are left alone
+                .replace("{STRINGS}", &strings)
+        };
+        let loader_path = self.out_path.join("tinysearch.js");
+        if self.worker {
+            let worker_path = self.out_path.join("tinysearch.worker.js");
+            fs::write(&worker_path, fill(assets::LOADER_JS) + assets::WORKER_JS)
+                .with_context(|| format!("Failed writing {}", &worker_path.display()))?;
+            fs::write(&loader_path, assets::with_results(assets::WORKER_CLIENT_JS))
+        } else {
+            fs::write(&loader_path, fill(assets::LOADER_JS))
+        }
+        .with_context(|| format!("Failed writing {}", &loader_path.display()))?;
+        write_types(&self.out_path, self.c.no_meta, &self.c.s.result_fields)?;
+        if self.modal {
+            let modal_path = self.out_path.join("tinysearch-modal.js");
+            fs::write(&modal_path, fill(assets::MODAL_JS))
+                .with_context(|| format!("Failed writing {}", &modal_path.display()))?;
+        }
+        let demo = match self.demo_theme.html() {
+            Some(demo) => demo,
+            None => {
+                info!("All done! Import tinysearch.js to search from your site.");
+                return Ok(());
+            }
+        };
         let html_path = self.out_path.join("demo.html");
-        fs::write(
-            &html_path,
-            assets::DEMO_HTML.replace("{WASM_NAME}", &wasm_name),
+        fs::write(&html_path, fill(demo))
+            .with_context(|| format!("Failed writing demo.html to {}", &html_path.display()))?;
+        info!("All done! Open the output folder with a web server to try the demo.");
+        Ok(())
+    }
+
+    fn output_dirs(&self) -> Vec<&Path> {
+        vec![&self.out_path]
+    }
+}
+
+/// Write tinysearch.d.ts, the types of the loader
+fn write_types(out_path: &Path, no_meta: bool, result_fields: &[String]) -> Result<(), Error> {
+    let types_path = out_path.join("tinysearch.d.ts");
+    let result_type = if no_meta {
+        "[title: string, url: string]".to_string()
+    } else if !result_fields.is_empty() {
+        let fields: Vec<String> = result_fields
+            .iter()
+            .map(|field| format!("{:?}?: string", field))
+            .collect();
+        format!(
+            "[title: string, url: string, meta: {{ {} }}]",
+            fields.join("; ")
+        )
+    } else {
+        "[title: string, url: string, meta: Record<string, string>]".to_string()
+    };
+    fs::write(
+        &types_path,
+        assets::LOADER_D_TS.replace("{RESULT}", &result_type),
+    )
+    .with_context(|| format!("Failed writing {}", &types_path.display()))
+}
+
+struct StorageOnly {
+    s: Storage,
+    engine_dir: Option<PathBuf>,
+    no_meta: bool,
+}
+
+impl StorageOnly {
+    /// Name, module, and JavaScript bindings of the engine: the one in --engine-dir,
+    /// or the prebuilt one
+    fn engine(&self) -> Result<(String, Vec<u8>, String), Error> {
+        let engine_dir = match &self.engine_dir {
+            Some(engine_dir) => engine_dir,
+            None => {
+                let (wasm, js) = engine::prebuilt().context(
+                    "This build of tinysearch has no prebuilt engine. Pass --engine-dir \
+                     with a module built with `tinysearch -m engine`, or install tinysearch \
+                     with `--features bin,engine`",
+                )?;
+                return Ok((engine::PREBUILT_NAME.into(), wasm.to_vec(), js.into()));
+            }
+        };
+        for entry in fs::read_dir(engine_dir)
+            .with_context(|| format!("Failed to read {}", engine_dir.display()))?
+        {
+            let name = entry?.file_name().to_string_lossy().into_owned();
+            if let Some(wasm_name) = name.strip_suffix("_bg.wasm") {
+                let js_file = engine_dir.join(format!("{}.js", wasm_name));
+                return Ok((
+                    wasm_name.to_string(),
+                    fs::read(engine_dir.join(&name))?,
+                    fs::read_to_string(&js_file)
+                        .with_context(|| format!("Failed to read {}", js_file.display()))?,
+                ));
+            }
+        }
+        bail!(
+            "No WASM module found in {}, build one with `tinysearch -m engine`",
+            engine_dir.display()
+        )
+    }
+}
+
+impl Stage for StorageOnly {
+    fn from_opt(opt: &Opt) -> Result<Self, Error> {
+        let storage_opt = {
+            let mut ret: Opt = opt.clone();
+            ret.external_storage = true;
+            ret
+        };
+        let engine_dir = match &opt.engine_dir {
+            Some(dir) => Some(
+                dir.canonicalize()
+                    .with_context(|| format!("Failed to find engine: {}", dir.display()))?,
+            ),
+            None => None,
+        };
+        Ok(Self {
+            s: Storage::from_opt(&storage_opt)?,
+            engine_dir,
+            no_meta: opt.no_meta,
+        })
+    }
+
+    fn build(&self) -> Result<(), Error> {
+        let (wasm_name, wasm, js) = self.engine()?;
+        let wasm_file = format!("{}_bg.wasm", wasm_name);
+        if !engine::exports(&wasm)?
+            .iter()
+            .any(|name| name == "load_shard")
+        {
+            bail!(
+                "{} embeds its index, build an engine with `tinysearch -m engine`",
+                wasm_file
+            );
+        }
+        self.s.build().context("Failed building storage")?;
+        let out_path = &self.s.out_path;
+        fs::write(out_path.join(&wasm_file), &wasm)
+            .with_context(|| format!("Failed writing {}", wasm_file))?;
+        fs::write(out_path.join(format!("{}.js", wasm_name)), js)
+            .with_context(|| format!("Failed writing {}.js", wasm_name))?;
+        let shard_hashes = self
+            .s
+            .shard_files()
+            .iter()
+            .map(|name| fs::read_to_string(out_path.join(name).with_extension("hash")))
+            .collect::<Result<Vec<_>, _>>()?;
+        let module_hash = storage::fingerprint(&wasm);
+        self.s
+            .write_manifest(out_path, &module_hash, &shard_hashes)?;
+        let loader_path = out_path.join("tinysearch.js");
+        let loader = assets::with_results(assets::LOADER_JS)
+            .replace("{WASM_NAME}", &wasm_name)
+            .replace("{MODULE_HASH}", &module_hash)
+            .replace("{SHARDS}", &format!("{:?}", shard_hashes));
+        fs::write(&loader_path, loader)
+            .with_context(|| format!("Failed writing {}", &loader_path.display()))?;
+        write_types(out_path, self.no_meta, &self.s.result_fields)?;
+        info!("All done! Import tinysearch.js to search from your site.");
+        Ok(())
+    }
+
+    fn output_dirs(&self) -> Vec<&Path> {
+        self.s.output_dirs()
+    }
+}
+
+/// Builds the module storage-only mode pairs with storage files. It loads the
+/// index at runtime and supports all analyzer and compression options, so it
+/// works with any index. The crate is built from an empty placeholder index.
+struct Engine {
+    wasm: Wasm,
+    out_path: PathBuf,
+    // Keeps the placeholder index and the output of wasm mode until the build
+    _temp: TempDir,
+}
+
+impl Stage for Engine {
+    fn from_opt(opt: &Opt) -> Result<Self, Error> {
+        let temp = TempDir::new()?;
+        let posts_index = temp.path().join("index.json");
+        fs::write(&posts_index, "[]")?;
+        let wasm_opt = {
+            let mut ret: Opt = opt.clone();
+            ret.input_file = Some(posts_index);
+            ret.source = source::find("json").context("Missing JSON source")?;
+            ret.external_storage = true;
+            ret.no_meta = false;
+            ret.result_fields.clear();
+            // The loader of storage-only mode relies on the init function of the web target
+            ret.wasm_target = WasmTarget::Web;
+            ret.demo_theme = DemoTheme::None;
+            ret.out_path = temp.path().join("wasm_output");
+            ret
+        };
+        let mut wasm = Wasm::from_opt(&wasm_opt)?;
+        wasm.c.generic = true;
+        Ok(Self {
+            wasm,
+            out_path: ensure_exists(opt.out_path.clone())?,
+            _temp: temp,
+        })
+    }
+
+    fn build(&self) -> Result<(), Error> {
+        self.wasm.build()?;
+        let wasm_name = self.wasm.c.crate_name.replace('-', "_");
+        for file in [
+            format!("{}_bg.wasm", wasm_name),
+            format!("{}.js", wasm_name),
+        ] {
+            fs::copy(self.wasm.out_path.join(&file), self.out_path.join(&file))
+                .with_context(|| format!("Failed copying {}", file))?;
+        }
+        info!("Engine written to {}", self.out_path.display());
+        Ok(())
+    }
+
+    fn output_dirs(&self) -> Vec<&Path> {
+        vec![&self.out_path]
+    }
+}
+
+struct Batch {
+    /// Builds the module shared by all sites
+    engine: Engine,
+    sites: Vec<StorageOnly>,
+}
+
+impl Batch {
+    /// Options of a site: its arguments from the manifest, in storage-only mode with
+    /// the shared module
+    fn site_opt(opt: &Opt, site: &Site) -> Result<Opt, Error> {
+        let mut args = vec![
+            "-m".to_string(),
+            "storage-only".to_string(),
+            "--engine-dir".to_string(),
+            opt.out_path.display().to_string(),
+            "-p".to_string(),
+            site.out.display().to_string(),
+        ];
+        args.extend(site.args.iter().cloned());
+        args.push(site.input.display().to_string());
+        let args: Vec<&str> = args.iter().map(String::as_str).collect();
+        Opt::from_args(&["tinysearch"], &args)
+            .map_err(|exit| anyhow::anyhow!("{}", exit.output.trim()))
+    }
+}
+
+impl Stage for Batch {
+    fn from_opt(opt: &Opt) -> Result<Self, Error> {
+        let path = opt.input_file.clone().context("Missing manifest file")?;
+        let manifest = Manifest::read(&path)?;
+        if manifest.sites.is_empty() {
+            bail!("{} lists no sites", path.display());
+        }
+        // The engine works with the options of any site. Creates the directory
+        // of the module, which the sites look up.
+        let engine = Engine::from_opt(opt)?;
+        let mut sites = Vec::new();
+        for site in &manifest.sites {
+            let site_opt = Self::site_opt(opt, site)
+                .with_context(|| format!("Invalid options for {}", site.input.display()))?;
+            sites.push(StorageOnly::from_opt(&site_opt)?);
+        }
+        Ok(Self { engine, sites })
+    }
+
+    fn build(&self) -> Result<(), Error> {
+        self.engine.build().context("Failed building the module")?;
+        for site in &self.sites {
+            site.build()
+                .with_context(|| format!("Failed building {}", site.s.posts_index.display()))?;
+        }
+        info!("All done! Built {} sites", self.sites.len());
+        Ok(())
+    }
+
+    fn output_dirs(&self) -> Vec<&Path> {
+        let mut dirs = self.engine.output_dirs();
+        for site in &self.sites {
+            dirs.extend(site.output_dirs());
+        }
+        dirs
+    }
+}
+
+struct Nojs {
+    s: Storage,
+}
+
+impl Stage for Nojs {
+    fn from_opt(opt: &Opt) -> Result<Self, Error> {
+        if opt.hash_key.is_some() {
+            bail!("The JavaScript fallback stores the words of posts as they are, so --hash-key can't hide them");
+        }
+        Ok(Self {
+            s: Storage::from_opt(opt)?,
+        })
+    }
+
+    fn build(&self) -> Result<(), Error> {
+        let path = self.s.out_path.join("tinysearch.nojs.js");
+        info!(
+            "Creating JavaScript search for posts {} in file {}",
+            self.s.posts_index.display(),
+            path.display()
+        );
+        let posts = self.s.posts()?;
+        nojs::write(posts, &path, &self.s.index_options)?;
+        info!("JavaScript search ready in file {}", path.display());
+        Ok(())
+    }
+
+    fn output_dirs(&self) -> Vec<&Path> {
+        self.s.output_dirs()
+    }
+}
+
+struct VerifyDeploy {
+    local: PathBuf,
+    url: String,
+}
+
+impl Stage for VerifyDeploy {
+    fn from_opt(opt: &Opt) -> Result<Self, Error> {
+        Ok(Self {
+            local: opt.out_path.join(MANIFEST_FILE),
+            url: opt
+                .url
+                .clone()
+                .context("Missing --url of the deployed search-manifest.json")?,
+        })
+    }
+
+    fn build(&self) -> Result<(), Error> {
+        let local = DeployManifest::read(&self.local.to_string_lossy())?;
+        let deployed = DeployManifest::read(&self.url)?;
+        let differences = local.differences(&deployed);
+        if differences.is_empty() {
+            println!("{} is up to date", self.url);
+            return Ok(());
+        }
+        for difference in &differences {
+            println!("{}", difference);
+        }
+        bail!(
+            "The deployed search differs from the build in {}",
+            self.local.display()
+        )
+    }
+}
+
+struct Bench {
+    s: Storage,
+    queries: Vec<String>,
+    iterations: usize,
+    num_results: usize,
+    options: SearchOptions,
+}
+
+impl Stage for Bench {
+    fn from_opt(opt: &Opt) -> Result<Self, Error> {
+        if opt.storage_format != StorageFormat::Bincode {
+            bail!("Bench mode measures the bincode storage the engine embeds, leave out --storage-format");
+        }
+        if opt.shards > 1 || opt.external_storage {
+            bail!(
+                "Bench mode measures a single storage, leave out --shards and --external-storage"
+            );
+        }
+        let queries = match &opt.queries {
+            Some(location) => http::read_to_string(location)?
+                .lines()
+                .map(str::trim)
+                .filter(|query| !query.is_empty())
+                .map(String::from)
+                .collect(),
+            None => Vec::new(),
+        };
+        let s = Storage::from_opt(opt)?;
+        // A random key is only known once the storage options are read
+        let options = Search::options(opt).with_hash_key(&s.index_options.hash_key);
+        Ok(Self {
+            s,
+            queries,
+            iterations: opt.iterations,
+            num_results: opt.num_searches,
+            options,
+        })
+    }
+
+    fn build(&self) -> Result<(), Error> {
+        let posts = self.s.posts()?;
+        let options = BenchOptions {
+            queries: &self.queries,
+            iterations: self.iterations,
+            num_results: self.num_results,
+            search: &self.options,
+        };
+        bench::run(posts, &self.s.index_options, &options)
+    }
+}
+
+struct Inspect {
+    storage_file: PathBuf,
+    terms: String,
+    hash_key: String,
+}
+
+impl Stage for Inspect {
+    fn from_opt(opt: &Opt) -> Result<Self, Error> {
+        Ok(Self {
+            storage_file: opt.input_file.clone().context("Missing storage file")?,
+            terms: opt.search_term.clone(),
+            hash_key: opt.hash_key.clone().unwrap_or_default(),
+        })
+    }
+
+    fn build(&self) -> Result<(), Error> {
+        inspect::run(&self.storage_file, &self.terms, &self.hash_key)
+    }
+}
+
+struct Explain {
+    storage_file: PathBuf,
+    term: String,
+    url: String,
+    options: SearchOptions,
+}
+
+impl Stage for Explain {
+    fn from_opt(opt: &Opt) -> Result<Self, Error> {
+        Ok(Self {
+            storage_file: opt.input_file.clone().context("Missing storage file")?,
+            term: opt.search_term.clone(),
+            url: opt
+                .url
+                .clone()
+                .context("Missing --url of the post to explain")?,
+            options: Search::options(opt),
+        })
+    }
+
+    fn build(&self) -> Result<(), Error> {
+        let storage = storage::read(&self.storage_file)?;
+        let explanation = storage
+            .explain(&self.term, &self.url, &self.options)
+            .with_context(|| format!("No post has the URL {}", self.url))?;
+        println!("Post: {}", explanation.post_id.0);
+        for term in &explanation.terms {
+            println!(
+                "  {}: title {}, body {}{}",
+                term.term,
+                term.title,
+                term.body,
+                if term.facets { ", facets" } else { "" }
+            );
+        }
+        println!(
+            "Points: title {}, body {}, word pairs {}, facets {}",
+            explanation.title_points,
+            explanation.body_points,
+            explanation.bigram_points,
+            explanation.facet_points
+        );
+        println!(
+            "Matched {} of {} query terms, coverage factor {:.3}",
+            explanation.matched,
+            explanation.terms.len(),
+            explanation.coverage
+        );
+        println!("Recency factor: {:.3}", explanation.recency);
+        println!("Score: {:.3}", explanation.score);
+        match (explanation.rank, explanation.reason) {
+            (Some(rank), _) => println!("Rank {} of {} results", rank, explanation.results),
+            (None, Some(reason)) => println!(
+                "Not among the {} results, because {}",
+                explanation.results, reason
+            ),
+            (None, None) => println!("Not among the {} results", explanation.results),
+        }
+        Ok(())
+    }
+}
+
+struct Analyze {
+    text: String,
+    storage_file: Option<PathBuf>,
+    index_options: IndexOptions,
+}
+
+impl Stage for Analyze {
+    fn from_opt(opt: &Opt) -> Result<Self, Error> {
+        Ok(Self {
+            text: opt.search_term.clone(),
+            storage_file: opt.input_file.clone(),
+            index_options: IndexOptions {
+                format: opt
+                    .content_format
+                    .unwrap_or_else(|| opt.source.content_format()),
+                language: opt.language,
+                min_word_len: opt.min_word_length.unwrap_or_default(),
+                analyzer: Storage::analyzer(opt),
+                bigram_boost: opt.bigram_boost,
+                ..IndexOptions::default()
+            },
+        })
+    }
+
+    fn build(&self) -> Result<(), Error> {
+        analyze::run(
+            &self.text,
+            self.storage_file.as_deref(),
+            &self.index_options,
         )
-        .with_context(|| format!("Failed writing demo.html to {}", &html_path.display()))?;
-        println!("All done! Open the output folder with a web server to try the demo.");
+    }
+}
+
+struct Validate {
+    input: String,
+    format: ReportFormat,
+}
+
+impl Stage for Validate {
+    fn from_opt(opt: &Opt) -> Result<Self, Error> {
+        let input = opt.input_file.clone().context("Missing input file")?;
+        Ok(Self {
+            input: input.display().to_string(),
+            format: opt.format,
+        })
+    }
+
+    fn build(&self) -> Result<(), Error> {
+        let raw = http::read_to_string(&self.input)?;
+        let report = validate::validate(&raw)?;
+        match self.format {
+            ReportFormat::Json => println!("{}", serde_json::to_string_pretty(&report)?),
+            ReportFormat::Text => {
+                for issue in &report.issues {
+                    println!(
+                        "{}: post {}{}: {}",
+                        match issue.severity {
+                            validate::Severity::Error => "error",
+                            validate::Severity::Warning => "warning",
+                        },
+                        issue.post,
+                        issue
+                            .url
+                            .as_ref()
+                            .map_or(String::new(), |url| format!(" ({})", url)),
+                        issue.message
+                    );
+                }
+                println!(
+                    "{} posts, {} errors, {} warnings",
+                    report.posts, report.errors, report.warnings
+                );
+            }
+        }
+        if report.errors > 0 {
+            bail!("{} has {} errors", self.input, report.errors);
+        }
         Ok(())
     }
 }
 
+struct Doctor;
+
+impl Stage for Doctor {
+    fn from_opt(_opt: &Opt) -> Result<Self, Error> {
+        Ok(Doctor)
+    }
+
+    fn build(&self) -> Result<(), Error> {
+        doctor::run()
+    }
+}
+
+/// Parse the command line like `argh::from_env`. argh takes a lone `-` for an unknown
+/// option, so an input file `-` is retried behind `--`, where it's positional.
+fn parse_args() -> Opt {
+    let args: Vec<String> = env::args_os()
+        .map(|arg| arg.into_string())
+        .collect::<Result<_, _>>()
+        .unwrap_or_else(|arg| {
+            eprintln!("Invalid utf8: {}", arg.to_string_lossy());
+            std::process::exit(1)
+        });
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    let (cmd, args) = args.split_first().expect("argv contains the program name");
+    let cmd = Path::new(cmd)
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or(cmd);
+    let mut result = Opt::from_args(&[cmd], args);
+    for (i, _) in args.iter().enumerate().filter(|(_, arg)| **arg == STDIO) {
+        if result.is_ok() {
+            break;
+        }
+        let mut moved = args.to_vec();
+        moved.remove(i);
+        moved.extend(["--", STDIO]);
+        if let Ok(opt) = Opt::from_args(&[cmd], &moved) {
+            result = Ok(opt);
+        }
+    }
+    result.unwrap_or_else(|exit| {
+        std::process::exit(match exit.status {
+            Ok(()) => {
+                println!("{}", exit.output);
+                0
+            }
+            Err(()) => {
+                eprintln!("{}\nRun {} --help for more information.", exit.output, cmd);
+                1
+            }
+        })
+    })
+}
+
 pub fn main() -> Result<(), Error> {
-    let opt: Opt = argh::from_env();
+    let opt = parse_args();
 
     if opt.version {
         println!("tinysearch {}", env!("CARGO_PKG_VERSION"));
         std::process::exit(0);
     }
+    progress::init(opt.progress, opt.quiet);
+    logger::init(logger::level(opt.verbose, opt.quiet), opt.log_format);
 
+    let result = run(&opt);
+    if let (Err(err), LogFormat::Json) = (&result, opt.log_format) {
+        // Keep standard error machine-readable
+        error!("{:#}", err);
+        std::process::exit(1);
+    }
+    result
+}
+
+/// Run the stage of the output mode
+fn run(opt: &Opt) -> Result<(), Error> {
     let parse_ctx = || {
         format!(
             "Failed to parse options for {} mode",
@@ -383,10 +2187,26 @@ pub fn main() -> Result<(), Error> {
     };
 
     match opt.output_mode {
-        OutputMode::Search => Search::from_opt(&opt).with_context(parse_ctx)?.build(),
-        OutputMode::Storage => Storage::from_opt(&opt).with_context(parse_ctx)?.build(),
-        OutputMode::Crate => Crate::from_opt(&opt).with_context(parse_ctx)?.build(),
-        OutputMode::Wasm => Wasm::from_opt(&opt).with_context(parse_ctx)?.build(),
+        OutputMode::Search => build_stage(Search::from_opt(opt).with_context(parse_ctx)?, opt),
+        OutputMode::Storage => build_stage(Storage::from_opt(opt).with_context(parse_ctx)?, opt),
+        OutputMode::Crawl => build_stage(Crawl::from_opt(opt).with_context(parse_ctx)?, opt),
+        OutputMode::Crate => build_stage(Crate::from_opt(opt).with_context(parse_ctx)?, opt),
+        OutputMode::Wasm => build_stage(Wasm::from_opt(opt).with_context(parse_ctx)?, opt),
+        OutputMode::StorageOnly => {
+            build_stage(StorageOnly::from_opt(opt).with_context(parse_ctx)?, opt)
+        }
+        OutputMode::Engine => build_stage(Engine::from_opt(opt).with_context(parse_ctx)?, opt),
+        OutputMode::Batch => build_stage(Batch::from_opt(opt).with_context(parse_ctx)?, opt),
+        OutputMode::Nojs => build_stage(Nojs::from_opt(opt).with_context(parse_ctx)?, opt),
+        OutputMode::Doctor => build_stage(Doctor::from_opt(opt).with_context(parse_ctx)?, opt),
+        OutputMode::VerifyDeploy => {
+            build_stage(VerifyDeploy::from_opt(opt).with_context(parse_ctx)?, opt)
+        }
+        OutputMode::Bench => build_stage(Bench::from_opt(opt).with_context(parse_ctx)?, opt),
+        OutputMode::Inspect => build_stage(Inspect::from_opt(opt).with_context(parse_ctx)?, opt),
+        OutputMode::Explain => build_stage(Explain::from_opt(opt).with_context(parse_ctx)?, opt),
+        OutputMode::Analyze => build_stage(Analyze::from_opt(opt).with_context(parse_ctx)?, opt),
+        OutputMode::Validate => build_stage(Validate::from_opt(opt).with_context(parse_ctx)?, opt),
     }
     .with_context(|| {
         format!(
@@ -397,16 +2217,13 @@ pub fn main() -> Result<(), Error> {
 }
 
 pub fn run_output(cmd: &mut Command) -> Result<String, Error> {
-    println!("running {:?}", cmd);
-    let output = cmd
-        .stderr(Stdio::inherit())
-        .output()
-        .with_context(|| format!("failed to run {:?}", cmd))?;
-
-    if !output.status.success() {
-        anyhow::bail!("failed to execute {:?}\nstatus: {}", cmd, output.status)
-    }
-    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    info!("Running {:?}", cmd);
+    let start = Instant::now();
+    // The progress of the tools is only shown along with ours, but their errors are
+    // always reported
+    let output = progress::run(cmd, !log_enabled!(log::Level::Info));
+    build_manifest::record(&cmd.get_program().to_string_lossy(), start.elapsed());
+    output
 }
 
 // #[cfg(test)]
@@ -0,0 +1,60 @@
+use anyhow::Error;
+use std::collections::HashSet;
+use std::path::Path;
+
+use super::storage::{self, Dropped, IndexOptions};
+use tinysearch::bigram;
+
+/// Print the terms `text` is indexed as, word by word, and why words are left out.
+/// With a storage file at `path`, its analyzer, languages and minimum word length are
+/// used instead of the ones of `options`, leaving out the words queries leave out.
+pub fn run(text: &str, path: Option<&Path>, options: &IndexOptions) -> Result<(), Error> {
+    let (stopwords, options): (HashSet<String>, _) = match path {
+        Some(path) => {
+            let storage = storage::read(path)?;
+            let options = IndexOptions {
+                analyzer: storage.analyzer,
+                min_word_len: storage.min_word_len,
+                bigram_boost: storage.bigram_boost,
+                ..options.clone()
+            };
+            let stopwords = storage::query_stopwords(&storage.languages)
+                .map(String::from)
+                .collect();
+            (stopwords, options)
+        }
+        None => (storage::stopwords(options.language), options.clone()),
+    };
+
+    let words = storage::analyze(text, &stopwords, &options);
+    let mut terms: Vec<&str> = Vec::new();
+    for (word, analyzed) in &words {
+        match analyzed {
+            Ok(indexed) if indexed == std::slice::from_ref(word) => println!("{}", word),
+            Ok(indexed) => println!("{} -> {}", word, indexed.join(", ")),
+            Err(Dropped::Stopword) => println!("{} (stopword, left out)", word),
+            Err(Dropped::TooShort) => println!(
+                "{} (shorter than {} characters, left out)",
+                word, options.min_word_len
+            ),
+        }
+        for term in analyzed.iter().flatten() {
+            if !terms.contains(&term.as_str()) {
+                terms.push(term);
+            }
+        }
+    }
+    println!("Terms: {}", terms.join(" "));
+    if options.bigram_boost > 0 {
+        let pairs: Vec<String> = words
+            .iter()
+            .filter_map(|(_, analyzed)| analyzed.as_ref().ok())
+            .flatten()
+            .collect::<Vec<_>>()
+            .windows(2)
+            .map(|pair| bigram(pair[0], pair[1]))
+            .collect();
+        println!("Word pairs: {}", pairs.join(", "));
+    }
+    Ok(())
+}
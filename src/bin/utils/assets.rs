@@ -10,5 +10,46 @@ pub static CRATE_LIB_RS: &str = include_str!(concat!(
 // Include a bare-bones HTML page template that demonstrates how tinysearch is used
 pub static DEMO_HTML: &str = include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/assets/demo.html"));
 
-pub static STOP_WORDS: &str =
-    include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/assets/stopwords"));
+// Demo styled like the search of a documentation site, opened with `/` or Ctrl+K
+pub static DEMO_DOCS_HTML: &str = include_str!(concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/assets/demo_docs.html"
+));
+
+// Demo styled like the search page of a blog, showing the date and tags of posts
+pub static DEMO_BLOG_HTML: &str = include_str!(concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/assets/demo_blog.html"
+));
+
+// ES module that loads the WASM module on demand and can warm it up in idle time
+pub static LOADER_JS: &str = include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/assets/loader.js"));
+
+// Message handler appended to the loader to run it in a Web Worker
+pub static WORKER_JS: &str = include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/assets/worker.js"));
+
+// Main-thread wrapper with the API of the loader, which forwards calls to the worker
+pub static WORKER_CLIENT_JS: &str = include_str!(concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/assets/worker_client.js"
+));
+
+// URL template, URL checks, and HTML escaping of results, which the loader, the
+// worker client, and the pure JavaScript search share, see `with_results`
+pub static RESULTS_JS: &str =
+    include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/assets/results.js"));
+
+/// `template` with the shared result handling of [`RESULTS_JS`] in place of `{RESULTS_JS}`
+pub fn with_results(template: &str) -> String {
+    template.replacen("{RESULTS_JS}", RESULTS_JS, 1)
+}
+
+// Pure JavaScript search, which is written along with its index in nojs mode
+pub static NOJS_JS: &str = include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/assets/nojs.js"));
+
+// <tinysearch-modal> web component, a search overlay opened with `/` or Ctrl+K
+pub static MODAL_JS: &str = include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/assets/modal.js"));
+
+// TypeScript definitions of the loader, written next to it
+pub static LOADER_D_TS: &str =
+    include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/assets/loader.d.ts"));
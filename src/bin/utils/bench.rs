@@ -0,0 +1,111 @@
+use anyhow::Error;
+use std::time::{Duration, Instant};
+
+use super::index::Posts;
+use super::storage::{self, IndexOptions};
+use tinysearch::{SearchOptions, Storage};
+use xorf::Filter;
+
+/// What to measure besides building the index
+pub struct BenchOptions<'a> {
+    /// Queries to time, the titles of the posts if empty
+    pub queries: &'a [String],
+    /// How often each query is run
+    pub iterations: usize,
+    pub num_results: usize,
+    pub search: &'a SearchOptions,
+}
+
+/// Build the storage of the posts and time queries against it, printing how long the
+/// build took, how big the storage is, and the latency of the queries
+pub fn run(posts: Posts, index: &IndexOptions, options: &BenchOptions) -> Result<(), Error> {
+    let titles: Vec<String> = posts.iter().map(|post| post.title.clone()).collect();
+    let start = Instant::now();
+    let bytes = storage::build(posts, index)?;
+    let build_time = start.elapsed();
+    let storage = Storage::from_bytes(&bytes)?;
+    let stats = storage.stats();
+    println!(
+        "Built the index of {} posts in {}",
+        stats.posts,
+        ms(build_time)
+    );
+    println!(
+        "Storage: {} bytes, with {} bytes of post filters and {} of typo filters before compression",
+        bytes.len(),
+        stats.filter_bytes,
+        stats.typo_filter_bytes
+    );
+    let mut filter_sizes: Vec<usize> = storage
+        .filters
+        .iter()
+        .map(|(_, filter)| filter.len())
+        .collect();
+    filter_sizes.sort_unstable();
+    if !filter_sizes.is_empty() {
+        println!(
+            "Filter bytes per post: min {}, p50 {}, p90 {}, max {}",
+            filter_sizes[0],
+            percentile(&filter_sizes, 50.0),
+            percentile(&filter_sizes, 90.0),
+            filter_sizes[filter_sizes.len() - 1]
+        );
+    }
+
+    let queries = match options.queries.is_empty() {
+        true => &titles[..],
+        false => options.queries,
+    };
+    let mut latencies = Vec::with_capacity(queries.len() * options.iterations);
+    let mut results = 0;
+    for _ in 0..options.iterations {
+        for query in queries {
+            let start = Instant::now();
+            let found =
+                storage.search_with_options(query.clone(), options.num_results, options.search);
+            latencies.push(start.elapsed());
+            results += found.len();
+        }
+    }
+    if latencies.is_empty() {
+        println!("No queries to run");
+        return Ok(());
+    }
+    latencies.sort_unstable();
+    println!(
+        "Ran {} queries {} times, {:.1} results on average: p50 {}, p99 {}, max {}",
+        queries.len(),
+        options.iterations,
+        results as f64 / latencies.len() as f64,
+        ms(percentile(&latencies, 50.0)),
+        ms(percentile(&latencies, 99.0)),
+        ms(latencies[latencies.len() - 1])
+    );
+    Ok(())
+}
+
+/// The value below which `p` percent of the sorted, non-empty `values` fall
+/// (nearest rank)
+fn percentile<T: Copy>(values: &[T], p: f64) -> T {
+    let rank = (p / 100.0 * values.len() as f64).ceil() as usize;
+    values[rank.clamp(1, values.len()) - 1]
+}
+
+fn ms(duration: Duration) -> String {
+    format!("{:.3} ms", duration.as_secs_f64() * 1000.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_percentile() {
+        let values: Vec<usize> = (1..=100).collect();
+        assert_eq!(percentile(&values, 50.0), 50);
+        assert_eq!(percentile(&values, 99.0), 99);
+        assert_eq!(percentile(&values, 100.0), 100);
+        assert_eq!(percentile(&[7], 99.0), 7);
+        assert_eq!(percentile(&[1, 2], 0.0), 1);
+    }
+}
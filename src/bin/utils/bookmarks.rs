@@ -0,0 +1,153 @@
+use anyhow::{Context, Error};
+use scraper::{ElementRef, Html, Selector};
+
+use super::http;
+use super::index::{Post, Posts};
+use super::strip_html::text;
+use tinysearch::Meta;
+
+/// Read the outlines of an OPML file, e.g. a list of subscribed feeds or an
+/// exported bookmark collection, from a local file or an http(s) URL.
+///
+/// Every outline with a URL becomes a post. Its description, and the titles of
+/// the outlines it's nested in, make up the body.
+pub fn read_opml(location: &str) -> Result<Posts, Error> {
+    let raw = http::read_to_string(location)?;
+    opml(&raw).with_context(|| format!("Failed to parse OPML file {}", location))
+}
+
+fn opml(raw: &str) -> Result<Posts, Error> {
+    let doc = roxmltree::Document::parse(raw)?;
+    let mut posts = Posts::new();
+    for outline in doc.descendants().filter(|n| n.has_tag_name("outline")) {
+        let url = ["htmlUrl", "url", "xmlUrl"]
+            .iter()
+            .find_map(|attribute| outline.attribute(*attribute));
+        let url = match url {
+            Some(url) => url.to_string(),
+            // Folders only group other outlines
+            None => continue,
+        };
+        let title = outline
+            .attribute("title")
+            .or_else(|| outline.attribute("text"))
+            .unwrap_or(&url)
+            .to_string();
+        let folders = outline
+            .ancestors()
+            .skip(1)
+            .filter(|n| n.has_tag_name("outline"))
+            .filter_map(|n| n.attribute("title").or_else(|| n.attribute("text")));
+        let body: Vec<&str> = outline
+            .attribute("description")
+            .into_iter()
+            .chain(folders)
+            .collect();
+        posts.push(Post {
+            title,
+            url,
+            meta: Meta::new(),
+            body: (!body.is_empty()).then(|| body.join("\n")),
+        });
+    }
+    Ok(posts)
+}
+
+/// Read a bookmarks export of a browser (the Netscape bookmark file format written
+/// by Firefox, Chrome, Safari, and most bookmark services) from a local file or an
+/// http(s) URL.
+///
+/// The description of a bookmark becomes the body, its tags are stored in the meta field.
+pub fn read_html(location: &str) -> Result<Posts, Error> {
+    Ok(bookmarks(&http::read_to_string(location)?))
+}
+
+fn bookmarks(html: &str) -> Posts {
+    let document = Html::parse_document(html);
+    let links = Selector::parse("a[href]").expect("valid selector");
+    let mut posts = Posts::new();
+    for link in document.select(&links) {
+        let url = link.value().attr("href").unwrap_or_default().to_string();
+        // Firefox exports smart folders and bookmarklets as well
+        if url.starts_with("place:") || url.starts_with("javascript:") {
+            continue;
+        }
+        // The description follows the <DT> of the link in a <DD>
+        let description = link
+            .parent()
+            .into_iter()
+            .flat_map(|dt| dt.next_siblings())
+            .find_map(ElementRef::wrap)
+            .filter(|sibling| sibling.value().name() == "dd")
+            .map(text);
+        let tags: Vec<&str> = link
+            .value()
+            .attr("tags")
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|tag| !tag.is_empty())
+            .collect();
+        let mut meta = Meta::new();
+        if !tags.is_empty() {
+            meta.insert("tags".into(), serde_json::json!(tags).to_string());
+        }
+        let title = text(link).trim().to_string();
+        posts.push(Post {
+            title: if title.is_empty() { url.clone() } else { title },
+            url,
+            meta,
+            body: description.map(|d| d.trim().to_string()),
+        });
+    }
+    posts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_opml() {
+        let raw = r#"<?xml version="1.0"?>
+            <opml version="2.0"><head><title>Reading list</title></head><body>
+              <outline text="Rust">
+                <outline text="This Week in Rust" type="rss" xmlUrl="https://this-week-in-rust.org/rss.xml"
+                         htmlUrl="https://this-week-in-rust.org/" description="Weekly news"/>
+              </outline>
+              <outline title="Endler" url="https://endler.dev/"/>
+            </body></opml>"#;
+        let posts = opml(raw).unwrap();
+        assert_eq!(posts.len(), 2);
+        assert_eq!(posts[0].title, "This Week in Rust");
+        assert_eq!(posts[0].url, "https://this-week-in-rust.org/");
+        assert_eq!(posts[0].body.as_deref(), Some("Weekly news\nRust"));
+        assert_eq!(posts[1].url, "https://endler.dev/");
+        assert_eq!(posts[1].body, None);
+    }
+
+    #[test]
+    fn test_bookmarks() {
+        let html = r#"<!DOCTYPE NETSCAPE-Bookmark-file-1>
+            <TITLE>Bookmarks</TITLE>
+            <DL><p>
+                <DT><H3>Rust</H3>
+                <DL><p>
+                    <DT><A HREF="https://www.rust-lang.org/" ADD_DATE="1600000000" TAGS="rust,lang">Rust</A>
+                    <DD>A language empowering everyone
+                    <DT><A HREF="place:sort=8">Recent Tags</A>
+                    <DT><A HREF="https://docs.rs/">Docs.rs</A>
+                </DL><p>
+            </DL>"#;
+        let posts = bookmarks(html);
+        assert_eq!(posts.len(), 2);
+        assert_eq!(posts[0].title, "Rust");
+        assert_eq!(
+            posts[0].body.as_deref(),
+            Some("A language empowering everyone")
+        );
+        assert_eq!(posts[0].meta["tags"], r#"["rust","lang"]"#);
+        assert_eq!(posts[1].url, "https://docs.rs/");
+        assert_eq!(posts[1].body, None);
+    }
+}
@@ -0,0 +1,194 @@
+use anyhow::{Context, Error};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use super::http::STDIO;
+use super::storage::fingerprint;
+
+/// Name of the build manifest written to each output directory
+pub const BUILD_MANIFEST_FILE: &str = "tinysearch-build.json";
+
+/// Version of the layout of the build manifest. It's increased when a field changes
+/// or goes away, but not when one is added.
+pub const BUILD_MANIFEST_VERSION: u32 = 1;
+
+/// Durations of the steps of the build so far, see `record`
+static TIMINGS: Mutex<Vec<(String, Duration)>> = Mutex::new(Vec::new());
+
+/// Remember how long a step of the build took, e.g. building the index or running
+/// wasm-pack, for the build manifest. Steps that run several times add up.
+pub fn record(step: &str, duration: Duration) {
+    TIMINGS.lock().unwrap().push((step.to_string(), duration));
+}
+
+/// What a build read and wrote, so that CI pipelines can audit and cache builds and
+/// other tools can find the files
+#[derive(Serialize, Debug)]
+pub struct BuildManifest {
+    /// See `BUILD_MANIFEST_VERSION`
+    pub manifest_version: u32,
+    /// Version of tinysearch that ran the build
+    pub tinysearch: String,
+    /// Output mode of the build, e.g. `wasm`
+    pub mode: String,
+    /// Arguments of the command line, which include all options
+    pub args: Vec<String>,
+    pub input: Option<Input>,
+    pub schema: Schema,
+    /// Files written to the directory of the manifest, sorted by path
+    pub outputs: Vec<Output>,
+    /// Milliseconds each step took, and `total` for the whole build
+    pub timings_ms: BTreeMap<String, f64>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct Input {
+    /// Path or URL as given on the command line, `-` for standard input
+    pub location: String,
+    /// Size and fingerprint of local files, see `storage::fingerprint`
+    pub bytes: Option<u64>,
+    pub hash: Option<String>,
+}
+
+/// How the posts are stored and what searches return
+#[derive(Serialize, Debug, Default)]
+pub struct Schema {
+    /// Layout of the storage, see `tinysearch::STORAGE_VERSION`
+    pub storage_version: u16,
+    pub storage_format: String,
+    pub facet_fields: Vec<String>,
+    pub date_field: Option<String>,
+    /// Meta fields of results, empty for all of them
+    pub result_fields: Vec<String>,
+    pub no_meta: bool,
+}
+
+#[derive(Serialize, Debug, PartialEq, Eq)]
+pub struct Output {
+    /// Path relative to the directory of the manifest, with `/` separators
+    pub path: String,
+    pub bytes: u64,
+    /// See `storage::fingerprint`
+    pub hash: String,
+}
+
+impl BuildManifest {
+    /// Describe a build that started at `started` and wrote to `dir`
+    pub fn new(
+        mode: &str,
+        input: Option<&Path>,
+        schema: Schema,
+        dir: &Path,
+        started: SystemTime,
+    ) -> Result<Self, Error> {
+        let mut timings_ms = BTreeMap::new();
+        for (step, duration) in TIMINGS.lock().unwrap().iter() {
+            *timings_ms.entry(step.clone()).or_default() += duration.as_secs_f64() * 1000.0;
+        }
+        timings_ms.insert(
+            "total".to_string(),
+            started.elapsed().unwrap_or_default().as_secs_f64() * 1000.0,
+        );
+        Ok(Self {
+            manifest_version: BUILD_MANIFEST_VERSION,
+            tinysearch: env!("CARGO_PKG_VERSION").to_string(),
+            mode: mode.to_string(),
+            args: std::env::args().skip(1).collect(),
+            input: input.map(Input::new).transpose()?,
+            schema,
+            outputs: outputs(dir, started)?,
+            timings_ms,
+        })
+    }
+
+    pub fn write(&self, dir: &Path) -> Result<(), Error> {
+        let path = dir.join(BUILD_MANIFEST_FILE);
+        fs::write(&path, serde_json::to_string_pretty(self)?)
+            .with_context(|| format!("Failed writing {}", path.display()))
+    }
+}
+
+impl Input {
+    fn new(location: &Path) -> Result<Self, Error> {
+        let (bytes, hash) = match location.is_file() && location != Path::new(STDIO) {
+            true => {
+                let content = fs::read(location)
+                    .with_context(|| format!("Failed to read {}", location.display()))?;
+                (Some(content.len() as u64), Some(fingerprint(&content)))
+            }
+            false => (None, None),
+        };
+        Ok(Self {
+            location: location.display().to_string(),
+            bytes,
+            hash,
+        })
+    }
+}
+
+/// The files in `dir` and its subdirectories written since `started`, except for an
+/// earlier build manifest
+fn outputs(dir: &Path, started: SystemTime) -> Result<Vec<Output>, Error> {
+    // Some file systems only store whole seconds
+    let started = UNIX_EPOCH + Duration::from_secs(started.duration_since(UNIX_EPOCH)?.as_secs());
+    let mut outputs = Vec::new();
+    let mut dirs = vec![PathBuf::from(dir)];
+    while let Some(current) = dirs.pop() {
+        for entry in fs::read_dir(&current)? {
+            let entry = entry?;
+            let metadata = entry.metadata()?;
+            if metadata.is_dir() {
+                dirs.push(entry.path());
+                continue;
+            }
+            let path = entry.path();
+            let relative = path.strip_prefix(dir)?;
+            if relative == Path::new(BUILD_MANIFEST_FILE) || metadata.modified()? < started {
+                continue;
+            }
+            outputs.push(Output {
+                path: relative
+                    .components()
+                    .map(|component| component.as_os_str().to_string_lossy())
+                    .collect::<Vec<_>>()
+                    .join("/"),
+                bytes: metadata.len(),
+                hash: fingerprint(&fs::read(&path)?),
+            });
+        }
+    }
+    outputs.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(outputs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_outputs() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("old"), "before").unwrap();
+        let started = SystemTime::now();
+        let old = fs::File::options()
+            .write(true)
+            .open(dir.path().join("old"))
+            .unwrap();
+        old.set_modified(started - Duration::from_secs(10)).unwrap();
+        fs::create_dir(dir.path().join("src")).unwrap();
+        fs::write(dir.path().join("src").join("storage"), "index").unwrap();
+        fs::write(dir.path().join("demo.html"), "demo").unwrap();
+        fs::write(dir.path().join(BUILD_MANIFEST_FILE), "{}").unwrap();
+
+        let paths: Vec<String> = outputs(dir.path(), started)
+            .unwrap()
+            .into_iter()
+            .map(|output| output.path)
+            .collect();
+        assert_eq!(paths, ["demo.html", "src/storage"]);
+    }
+}
@@ -0,0 +1,120 @@
+use anyhow::{anyhow, Context, Error};
+use scraper::{Html, Selector};
+
+use super::http;
+use super::index::{Post, Posts};
+use super::strip_html::text;
+use tinysearch::Meta;
+
+/// Fetch all pages listed in a sitemap (or sitemap index) and turn them into posts.
+///
+/// The body of a page is the text of the first element matching `content_selector`,
+/// falling back to the whole `<body>` for pages where it doesn't match. Pages that
+/// can't be fetched are skipped with a warning.
+pub fn read(sitemap: &str, content_selector: &str) -> Result<Posts, Error> {
+    let selector = Selector::parse(content_selector)
+        .map_err(|e| anyhow!("Invalid content selector {}: {:?}", content_selector, e))?;
+    let mut urls = Vec::new();
+    collect_urls(sitemap, &mut urls)?;
+
+    let mut posts = Posts::new();
+    for url in urls {
+        info!("Crawling {}", url);
+        match http::read_to_string(&url) {
+            Ok(html) => posts.push(page(url, &html, &selector)),
+            Err(e) => warn!("Skipping {}: {:#}", url, e),
+        }
+    }
+    Ok(posts)
+}
+
+/// Collect page URLs, following nested sitemaps of a sitemap index
+fn collect_urls(sitemap: &str, urls: &mut Vec<String>) -> Result<(), Error> {
+    let raw = http::read_to_string(sitemap)?;
+    let doc = roxmltree::Document::parse(&raw)
+        .with_context(|| format!("Failed to parse sitemap {}", sitemap))?;
+    for loc in doc.descendants().filter(|n| n.has_tag_name("loc")) {
+        let location = loc.text().unwrap_or_default().trim().to_string();
+        match loc.parent_element().map(|p| p.tag_name().name()) {
+            Some("sitemap") => collect_urls(&location, urls)?,
+            _ => urls.push(location),
+        }
+    }
+    Ok(())
+}
+
+fn page(url: String, html: &str, content_selector: &Selector) -> Post {
+    let document = Html::parse_document(html);
+    let first_text = |selector: &str| {
+        let selector = Selector::parse(selector).expect("valid selector");
+        document.select(&selector).next().map(text)
+    };
+
+    let title = first_text("title")
+        .or_else(|| first_text("h1"))
+        .unwrap_or_else(|| url.clone());
+    let body = document
+        .select(content_selector)
+        .next()
+        .map(text)
+        .or_else(|| first_text("body"));
+
+    Post {
+        title: title.trim().to_string(),
+        url,
+        meta: Meta::new(),
+        body,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_page() {
+        let html = r#"<html><head><title>Hello</title><style>.x{}</style></head>
+            <body><nav>Home About</nav><main><h1>Hello</h1><p>Crawled <b>text</b></p>
+            <script>var x;</script></main><footer>Imprint</footer></body></html>"#;
+        let post = page(
+            "https://example.com/".into(),
+            html,
+            &Selector::parse("main").unwrap(),
+        );
+        assert_eq!(post.title, "Hello");
+        let body = post.body.unwrap();
+        let words: Vec<&str> = body.split_whitespace().collect();
+        assert_eq!(words, ["Hello", "Crawled", "text"]);
+
+        let post = page(
+            "https://example.com/".into(),
+            html,
+            &Selector::parse("article").unwrap(),
+        );
+        let body = post.body.unwrap();
+        let words: Vec<&str> = body.split_whitespace().collect();
+        assert_eq!(words, ["Hello", "Crawled", "text"]);
+    }
+
+    #[test]
+    fn test_read_skips_failed_pages() {
+        let dir = tempfile::tempdir().unwrap();
+        let found = dir.path().join("found.html");
+        std::fs::write(&found, "<title>Found</title><main>Crawled</main>").unwrap();
+        let missing = dir.path().join("missing.html");
+        let sitemap = dir.path().join("sitemap.xml");
+        std::fs::write(
+            &sitemap,
+            format!(
+                r#"<urlset><url><loc>{}</loc></url><url><loc>{}</loc></url></urlset>"#,
+                missing.display(),
+                found.display()
+            ),
+        )
+        .unwrap();
+
+        let posts = read(&sitemap.display().to_string(), "main").unwrap();
+        assert_eq!(posts.len(), 1);
+        assert_eq!(posts[0].title, "Found");
+    }
+}
@@ -0,0 +1,180 @@
+use anyhow::{bail, Context, Error};
+use std::mem;
+
+use super::http;
+use super::index::{Post, Posts};
+use tinysearch::Meta;
+
+/// Fields of a post that can be read from columns
+pub const FIELDS: [&str; 3] = ["title", "url", "body"];
+
+/// Read the rows of a CSV or TSV file with a header row, e.g. a product catalog,
+/// from a local file or an http(s) URL.
+///
+/// `columns` maps the fields of posts to the headers of the columns holding them.
+/// Unmapped fields are read from the column of the same name. The cells of
+/// several title or body columns are joined, and all other columns become meta
+/// fields.
+pub fn read(location: &str, delimiter: char, columns: &[(String, String)]) -> Result<Posts, Error> {
+    let raw = http::read_to_string(location)?;
+    let records =
+        records(&raw, delimiter).with_context(|| format!("Failed to parse {}", location))?;
+    posts(records, columns).with_context(|| format!("Failed to read posts from {}", location))
+}
+
+/// Split the input into records of cells, following RFC 4180: cells may be quoted,
+/// and quoted cells may contain delimiters, line breaks, and doubled quotes.
+fn records(raw: &str, delimiter: char) -> Result<Vec<Vec<String>>, Error> {
+    let raw = raw.strip_prefix('\u{feff}').unwrap_or(raw);
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+    let mut cell = String::new();
+    let mut quoted = false;
+    let mut line = 1;
+    let mut chars = raw.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                chars.next();
+                cell.push('"');
+            }
+            '"' if quoted => quoted = false,
+            '"' if cell.is_empty() => quoted = true,
+            c if c == delimiter && !quoted => record.push(mem::take(&mut cell)),
+            '\r' if !quoted && chars.peek() == Some(&'\n') => {}
+            '\n' if !quoted => {
+                record.push(mem::take(&mut cell));
+                records.push(mem::take(&mut record));
+                line += 1;
+            }
+            c => {
+                if c == '\n' {
+                    line += 1;
+                }
+                cell.push(c);
+            }
+        }
+    }
+    if quoted {
+        bail!("Quoted cell isn't closed at the end of line {}", line);
+    }
+    if !cell.is_empty() || !record.is_empty() {
+        record.push(cell);
+        records.push(record);
+    }
+    // Blank lines
+    records.retain(|record| record.len() > 1 || !record[0].is_empty());
+    Ok(records)
+}
+
+fn posts(records: Vec<Vec<String>>, columns: &[(String, String)]) -> Result<Posts, Error> {
+    let mut records = records.into_iter();
+    let header = records.next().context("The header row is missing")?;
+    let column = |header_name: &str| header.iter().position(|name| name == header_name);
+    // Indices of the columns of each field
+    let mut field_columns: Vec<Vec<usize>> = Vec::new();
+    for field in FIELDS {
+        let mapped: Vec<&str> = columns
+            .iter()
+            .filter(|(mapped_field, _)| mapped_field == field)
+            .map(|(_, name)| name.as_str())
+            .collect();
+        let indices = match mapped.is_empty() {
+            true => column(field).into_iter().collect(),
+            false => mapped
+                .iter()
+                .map(|name| column(name).with_context(|| format!("No column {}", name)))
+                .collect::<Result<_, _>>()?,
+        };
+        field_columns.push(indices);
+    }
+    let [title_columns, url_columns, body_columns] = &field_columns[..] else {
+        unreachable!("a list of columns for each field");
+    };
+    if title_columns.is_empty() || url_columns.is_empty() {
+        bail!("No title or url column, map them with e.g. --column title=name --column url=link");
+    }
+    if url_columns.len() > 1 {
+        bail!("Only one column can hold the url");
+    }
+    let join = |record: &[String], indices: &[usize], separator: &str| {
+        let cells: Vec<&str> = indices
+            .iter()
+            .map(|&i| record[i].trim())
+            .filter(|cell| !cell.is_empty())
+            .collect();
+        cells.join(separator)
+    };
+
+    let mut posts = Posts::new();
+    for (i, record) in records.enumerate() {
+        if record.len() != header.len() {
+            bail!(
+                "Row {} has {} cells, but there are {} columns",
+                i + 1,
+                record.len(),
+                header.len()
+            );
+        }
+        let body = join(&record, body_columns, "\n");
+        let meta: Meta = header
+            .iter()
+            .zip(&record)
+            .enumerate()
+            .filter(|(i, (_, cell))| {
+                !cell.is_empty() && !field_columns.iter().any(|indices| indices.contains(i))
+            })
+            .map(|(_, (name, cell))| (name.clone(), cell.clone()))
+            .collect();
+        posts.push(Post {
+            title: join(&record, title_columns, " "),
+            url: record[url_columns[0]].trim().to_string(),
+            meta,
+            body: (!body.is_empty()).then_some(body),
+        });
+    }
+    Ok(posts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_csv() {
+        let raw = "sku,name,link,description,features\r\n\
+                   1,Kettle,/kettle,\"Boils water, fast\",\"1.7 l\n\"\"quiet\"\"\"\r\n\
+                   \r\n\
+                   2,Mug,/mug,,\n";
+        let rows = records(raw, ',').unwrap();
+        assert_eq!(rows.len(), 3);
+        assert_eq!(rows[1][4], "1.7 l\n\"quiet\"");
+
+        let columns = [
+            ("title", "name"),
+            ("url", "link"),
+            ("body", "description"),
+            ("body", "features"),
+        ]
+        .map(|(field, name)| (field.to_string(), name.to_string()));
+        let posts = super::posts(rows, &columns).unwrap();
+        assert_eq!(posts[0].title, "Kettle");
+        assert_eq!(posts[0].url, "/kettle");
+        assert_eq!(
+            posts[0].body.as_deref(),
+            Some("Boils water, fast\n1.7 l\n\"quiet\"")
+        );
+        assert_eq!(
+            posts[0].meta,
+            Meta::from([("sku".to_string(), "1".to_string())])
+        );
+        assert_eq!(posts[1].body, None);
+
+        let tsv = records("title\turl\tprice\nKettle\t/kettle\t25\n", '\t').unwrap();
+        let posts = super::posts(tsv, &[]).unwrap();
+        assert_eq!(posts[0].meta["price"], "25");
+
+        assert!(super::posts(vec![vec!["name".to_string()]], &[]).is_err());
+        assert!(records("\"open", ',').is_err());
+    }
+}
@@ -0,0 +1,82 @@
+use anyhow::{Context, Error};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+use super::http;
+
+/// Name of the manifest written next to the module
+pub const MANIFEST_FILE: &str = "search-manifest.json";
+
+/// What a build put into the output directory, so that a deployed copy can be
+/// compared against it
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct DeployManifest {
+    /// Version of tinysearch that built the output
+    pub tinysearch: String,
+    /// Fingerprint of the posts, see `Storage::corpus_hash`. Sharded indexes combine
+    /// the fingerprints of their shards.
+    pub corpus_hash: String,
+    /// Fingerprint of the module, as in the URL tinysearch.js loads it from
+    pub module_hash: String,
+    /// Fingerprints of the shards fetched at runtime, empty if the index is embedded
+    #[serde(default)]
+    pub shards: Vec<String>,
+}
+
+impl DeployManifest {
+    pub fn write(&self, dir: &Path) -> Result<(), Error> {
+        let path = dir.join(MANIFEST_FILE);
+        fs::write(&path, serde_json::to_string_pretty(self)?)
+            .with_context(|| format!("Failed writing {}", path.display()))
+    }
+
+    /// Read a manifest from a local file or an http(s) URL
+    pub fn read(location: &str) -> Result<Self, Error> {
+        let raw = http::read_to_string(location)?;
+        serde_json::from_str(&raw).with_context(|| format!("Failed to parse {}", location))
+    }
+
+    /// Descriptions of what differs between this manifest and the deployed one
+    pub fn differences(&self, deployed: &DeployManifest) -> Vec<String> {
+        let mut differences = Vec::new();
+        let mut compare = |what: &str, local: &str, deployed: &str| {
+            if local != deployed {
+                differences.push(format!("{}: built {}, deployed {}", what, local, deployed));
+            }
+        };
+        compare("posts", &self.corpus_hash, &deployed.corpus_hash);
+        compare("module", &self.module_hash, &deployed.module_hash);
+        compare(
+            "shards",
+            &self.shards.join(", "),
+            &deployed.shards.join(", "),
+        );
+        compare("tinysearch", &self.tinysearch, &deployed.tinysearch);
+        differences
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_differences() {
+        let manifest = |corpus_hash: &str| DeployManifest {
+            tinysearch: "0.8.0".to_string(),
+            corpus_hash: corpus_hash.to_string(),
+            module_hash: "m".to_string(),
+            shards: Vec::new(),
+        };
+        let dir = tempfile::tempdir().unwrap();
+        manifest("a").write(dir.path()).unwrap();
+        let path = dir.path().join(MANIFEST_FILE);
+        let read = DeployManifest::read(path.to_str().unwrap()).unwrap();
+        assert!(read.differences(&manifest("a")).is_empty());
+        assert_eq!(
+            read.differences(&manifest("b")),
+            ["posts: built a, deployed b"]
+        );
+    }
+}
@@ -0,0 +1,112 @@
+use anyhow::{bail, Error};
+use std::process::Command;
+
+struct Check {
+    name: &'static str,
+    required: bool,
+    remedy: &'static str,
+    run: fn() -> Result<String, String>,
+}
+
+const CHECKS: &[Check] = &[
+    Check {
+        name: "cargo",
+        required: true,
+        remedy: "curl --proto '=https' --tlsv1.2 -sSf https://sh.rustup.rs | sh",
+        run: || version("cargo"),
+    },
+    Check {
+        name: "wasm32-unknown-unknown target",
+        required: true,
+        remedy: "rustup target add wasm32-unknown-unknown",
+        run: wasm_target,
+    },
+    Check {
+        name: "wasm-pack",
+        required: true,
+        remedy: "cargo install wasm-pack",
+        run: || version("wasm-pack"),
+    },
+    Check {
+        name: "wasm-opt (only needed for --optimize)",
+        required: false,
+        remedy: "install binaryen, e.g. `brew install binaryen` or `apt install binaryen`",
+        run: || version("wasm-opt"),
+    },
+    Check {
+        name: "crates.io registry access",
+        required: true,
+        remedy: "check your network and proxy settings (HTTPS_PROXY), or vendor the engine \
+                 dependencies and use --engine-version 'path=\"...\"'",
+        run: registry,
+    },
+];
+
+/// Check that everything needed to build the WASM module is installed,
+/// printing a remediation command for every missing component
+pub fn run() -> Result<(), Error> {
+    let mut missing = 0;
+    for check in CHECKS {
+        match (check.run)() {
+            Ok(found) => println!("[ok]      {}: {}", check.name, found),
+            Err(problem) => {
+                let label = if check.required {
+                    missing += 1;
+                    "[missing]"
+                } else {
+                    "[warning]"
+                };
+                println!("{} {}: {}", label, check.name, problem);
+                println!("          fix: {}", check.remedy);
+            }
+        }
+    }
+    if missing > 0 {
+        bail!("{} required component(s) missing", missing);
+    }
+    println!("All set! tinysearch is ready to build WASM modules.");
+    Ok(())
+}
+
+fn version(program: &str) -> Result<String, String> {
+    let output = Command::new(program)
+        .arg("--version")
+        .output()
+        .map_err(|_| format!("`{}` not found in PATH", program))?;
+    if !output.status.success() {
+        return Err(format!("`{} --version` failed", program));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Whether rustup lists the wasm32-unknown-unknown target as installed, `None`
+/// without rustup, e.g. for toolchains installed by a distribution
+pub fn wasm_target_installed() -> Option<bool> {
+    let output = Command::new("rustup")
+        .args(["target", "list", "--installed"])
+        .output()
+        .ok()?;
+    let installed = String::from_utf8_lossy(&output.stdout);
+    Some(
+        installed
+            .lines()
+            .any(|t| t.trim() == "wasm32-unknown-unknown"),
+    )
+}
+
+fn wasm_target() -> Result<String, String> {
+    match wasm_target_installed() {
+        Some(true) => Ok("installed".into()),
+        Some(false) => Err("not installed".into()),
+        None => Err("`rustup` not found in PATH, can't check installed targets".into()),
+    }
+}
+
+fn registry() -> Result<String, String> {
+    let url = "https://index.crates.io/config.json";
+    ureq::get(url)
+        .timeout(std::time::Duration::from_secs(10))
+        .call()
+        .map(|_| format!("{} reachable", url))
+        .map_err(|e| format!("can't reach {}: {}", url, e))
+}
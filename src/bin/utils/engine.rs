@@ -0,0 +1,139 @@
+use anyhow::{bail, Error};
+
+/// Name of the prebuilt module, as wasm-pack names the files of the default crate
+pub const PREBUILT_NAME: &str = "tinysearch_engine";
+
+/// The module and JavaScript bindings built with `make engine` before tinysearch
+/// itself, if it was built with the engine feature
+#[cfg(feature = "engine")]
+pub fn prebuilt() -> Option<(&'static [u8], &'static str)> {
+    Some((
+        include_bytes!(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/engine/tinysearch_engine_bg.wasm"
+        )),
+        include_str!(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/engine/tinysearch_engine.js"
+        )),
+    ))
+}
+
+#[cfg(not(feature = "engine"))]
+pub fn prebuilt() -> Option<(&'static [u8], &'static str)> {
+    None
+}
+
+/// Id of the section listing the exports of a WASM module
+const EXPORT_SECTION: u8 = 7;
+
+/// Names of the functions and other items a WASM module exports
+pub fn exports(wasm: &[u8]) -> Result<Vec<String>, Error> {
+    let mut reader = match wasm.strip_prefix(b"\0asm") {
+        Some(rest) if rest.len() >= 4 => Reader(&rest[4..]),
+        _ => bail!("Not a WASM module"),
+    };
+    while !reader.0.is_empty() {
+        let id = reader.byte()?;
+        let size = reader.leb128()? as usize;
+        let mut section = Reader(reader.take(size)?);
+        if id != EXPORT_SECTION {
+            continue;
+        }
+        let mut names = Vec::new();
+        for _ in 0..section.leb128()? {
+            let len = section.leb128()? as usize;
+            names.push(String::from_utf8_lossy(section.take(len)?).into_owned());
+            // The kind of the export and the index of the item
+            section.byte()?;
+            section.leb128()?;
+        }
+        return Ok(names);
+    }
+    Ok(Vec::new())
+}
+
+/// Reads the bytes of a WASM module front to back
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], Error> {
+        if len > self.0.len() {
+            bail!("Truncated WASM module");
+        }
+        let (taken, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(taken)
+    }
+
+    fn byte(&mut self) -> Result<u8, Error> {
+        Ok(self.take(1)?[0])
+    }
+
+    /// An unsigned LEB128 number, as used for all sizes and counts
+    fn leb128(&mut self) -> Result<u32, Error> {
+        let mut value = 0u32;
+        for shift in (0..35).step_by(7) {
+            let byte = self.byte()?;
+            value |= u32::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        bail!("Invalid number in WASM module")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A module with the given sections, each an id and its contents
+    fn module(sections: &[(u8, Vec<u8>)]) -> Vec<u8> {
+        let mut wasm = b"\0asm\x01\0\0\0".to_vec();
+        for (id, contents) in sections {
+            wasm.push(*id);
+            wasm.push(contents.len() as u8);
+            wasm.extend(contents);
+        }
+        wasm
+    }
+
+    /// An export section exporting functions with the given names
+    fn export_section(names: &[&str]) -> Vec<u8> {
+        let mut contents = vec![names.len() as u8];
+        for (index, name) in names.iter().enumerate() {
+            contents.push(name.len() as u8);
+            contents.extend(name.as_bytes());
+            contents.extend([0, index as u8]);
+        }
+        contents
+    }
+
+    #[test]
+    fn test_exports() {
+        let wasm = module(&[
+            (1, vec![0]),
+            (EXPORT_SECTION, export_section(&["search", "load_shard"])),
+        ]);
+        assert_eq!(exports(&wasm).unwrap(), ["search", "load_shard"]);
+    }
+
+    #[test]
+    fn test_exports_ignores_other_sections() {
+        // A custom section, like the name section of a debug build
+        let mut custom = vec![4];
+        custom.extend(b"name");
+        custom.extend(b"load_shard");
+        let wasm = module(&[(0, custom), (EXPORT_SECTION, export_section(&["search"]))]);
+        assert_eq!(exports(&wasm).unwrap(), ["search"]);
+    }
+
+    #[test]
+    fn test_exports_of_invalid_modules() {
+        assert!(exports(b"load_shard").is_err());
+        let mut wasm = module(&[(EXPORT_SECTION, export_section(&["search"]))]);
+        wasm.truncate(wasm.len() - 3);
+        assert!(exports(&wasm).is_err());
+    }
+}
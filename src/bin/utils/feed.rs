@@ -0,0 +1,21 @@
+use anyhow::{Context, Error};
+
+use super::http;
+use super::index::{Post, Posts};
+
+/// Read an RSS 2.0 or Atom feed from a local file or an http(s) URL,
+/// see [`tinysearch::parse_posts_from_feed`]
+pub fn read(location: &str) -> Result<Posts, Error> {
+    let raw = http::read_to_string(location)?;
+    let posts = tinysearch::parse_posts_from_feed(raw.as_bytes())
+        .with_context(|| format!("Failed to parse feed {}", location))?;
+    Ok(posts
+        .into_iter()
+        .map(|((title, url, meta), body)| Post {
+            title,
+            url,
+            meta,
+            body: (!body.is_empty()).then_some(body),
+        })
+        .collect())
+}
@@ -0,0 +1,34 @@
+use anyhow::{Context, Error};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read};
+
+/// Location of standard input, or of standard output for `-p`
+pub const STDIO: &str = "-";
+
+/// Open a local file, standard input for `-`, or, for http(s) locations, fetch it
+/// over the network
+pub fn open(location: &str) -> Result<Box<dyn BufRead>, Error> {
+    if location == STDIO {
+        Ok(Box::new(io::stdin().lock()))
+    } else if location.starts_with("http://") || location.starts_with("https://") {
+        debug!("Fetching {}", location);
+        let response = ureq::get(location)
+            .call()
+            .with_context(|| format!("Failed to fetch {}", location))?;
+        Ok(Box::new(BufReader::new(response.into_reader())))
+    } else {
+        let file =
+            File::open(location).with_context(|| format!("Failed to read file {}", location))?;
+        Ok(Box::new(BufReader::new(file)))
+    }
+}
+
+/// Read a local file, standard input for `-`, or, for http(s) locations, fetch it
+/// over the network
+pub fn read_to_string(location: &str) -> Result<String, Error> {
+    let mut raw = String::new();
+    open(location)?
+        .read_to_string(&mut raw)
+        .with_context(|| format!("Failed to read {}", location))?;
+    Ok(raw)
+}
@@ -1,10 +1,18 @@
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::{Map, Value};
+use std::io::{self, BufRead};
+use tinysearch::Meta;
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Post {
     pub title: String,
     pub url: String,
-    pub meta: Option<String>,
+    #[serde(
+        default,
+        deserialize_with = "deserialize_meta",
+        skip_serializing_if = "Meta::is_empty"
+    )]
+    pub meta: Meta,
     pub body: Option<String>,
 }
 
@@ -13,3 +21,141 @@ pub type Posts = Vec<Post>;
 pub fn read(raw: String) -> Result<Posts, serde_json::Error> {
     serde_json::from_str(&raw)
 }
+
+/// Read a YAML sequence of posts
+pub fn read_yaml(raw: &str) -> Result<Posts, serde_yaml::Error> {
+    serde_yaml::from_str(raw)
+}
+
+/// Posts of a TOML file, which can't be a list itself
+#[derive(Deserialize)]
+struct TomlPosts {
+    posts: Posts,
+}
+
+/// Read a TOML file with a `[[posts]]` table per post
+pub fn read_toml(raw: &str) -> Result<Posts, toml_edit::de::Error> {
+    Ok(toml_edit::de::from_str::<TomlPosts>(raw)?.posts)
+}
+
+/// Read JSON Lines with a post per line. Each post is parsed as it's read, so the
+/// input never has to fit into memory as a whole.
+pub fn read_ndjson(reader: impl BufRead) -> Result<Posts, serde_json::Error> {
+    serde_json::Deserializer::from_reader(reader)
+        .into_iter::<Post>()
+        .collect()
+}
+
+/// Whether the input is JSON Lines rather than a JSON array, i.e. starts with an
+/// object. Only the leading whitespace is consumed.
+pub fn is_ndjson(reader: &mut impl BufRead) -> io::Result<bool> {
+    loop {
+        let buf = reader.fill_buf()?;
+        let Some(&first) = buf.first() else {
+            return Ok(false);
+        };
+        if !first.is_ascii_whitespace() {
+            return Ok(first == b'{');
+        }
+        reader.consume(1);
+    }
+}
+
+/// Key TOML dates are wrapped in when they're read as JSON values
+const TOML_DATETIME_KEY: &str = "$__toml_private_datetime";
+
+/// Metadata from the fields of a JSON object. Strings are kept as they are,
+/// other values like lists of tags are stored as JSON. TOML dates are kept as
+/// they were written.
+pub fn meta(fields: Map<String, Value>) -> Meta {
+    fields
+        .into_iter()
+        .filter(|(_, value)| !value.is_null())
+        .map(|(key, value)| match value {
+            Value::String(value) => (key, value),
+            Value::Object(object) if object.contains_key(TOML_DATETIME_KEY) => (
+                key,
+                object[TOML_DATETIME_KEY]
+                    .as_str()
+                    .unwrap_or_default()
+                    .to_string(),
+            ),
+            value => (key, value.to_string()),
+        })
+        .collect()
+}
+
+/// The meta field of a post is an object. Index files written for earlier versions
+/// have a string there instead, which is read as an object if it contains JSON,
+/// and kept as the `meta` entry otherwise.
+fn deserialize_meta<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Meta, D::Error> {
+    match Option::<Value>::deserialize(deserializer)? {
+        None | Some(Value::Null) => Ok(Meta::new()),
+        Some(Value::Object(fields)) => Ok(meta(fields)),
+        Some(Value::String(raw)) => match serde_json::from_str(&raw) {
+            Ok(Value::Object(fields)) => Ok(meta(fields)),
+            _ => Ok(Meta::from([("meta".to_string(), raw)])),
+        },
+        Some(other) => Err(serde::de::Error::custom(format!(
+            "meta must be an object or a string, found {}",
+            other
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_meta() {
+        let posts = read(
+            r#"[
+                {"title": "a", "url": "/a", "meta": {"date": "2020-01-01", "tags": ["rust"]}},
+                {"title": "b", "url": "/b", "meta": "{\"date\": \"2020-01-01\"}"},
+                {"title": "c", "url": "/c", "meta": "plain"},
+                {"title": "d", "url": "/d"}
+            ]"#
+            .to_string(),
+        )
+        .unwrap();
+        assert_eq!(posts[0].meta["date"], "2020-01-01");
+        assert_eq!(posts[0].meta["tags"], r#"["rust"]"#);
+        assert_eq!(posts[1].meta["date"], "2020-01-01");
+        assert_eq!(posts[2].meta["meta"], "plain");
+        assert!(posts[3].meta.is_empty());
+    }
+
+    #[test]
+    fn test_read_ndjson() {
+        let raw = "\n{\"title\": \"a\", \"url\": \"/a\"}\n\n{\"title\": \"b\", \"url\": \"/b\", \"body\": \"text\"}\n";
+        let mut reader = raw.as_bytes();
+        assert!(is_ndjson(&mut reader).unwrap());
+        let posts = read_ndjson(reader).unwrap();
+        assert_eq!(posts.len(), 2);
+        assert_eq!(posts[1].body.as_deref(), Some("text"));
+
+        assert!(!is_ndjson(&mut " [{}]".as_bytes()).unwrap());
+        let error = read_ndjson("{\"title\": \"a\", \"url\": \"/a\"}\n{\"title\": 1}".as_bytes());
+        assert_eq!(error.unwrap_err().line(), 2);
+    }
+
+    #[test]
+    fn test_read_yaml_and_toml() {
+        let yaml = read_yaml(
+            "- title: a\n  url: /a\n  meta:\n    date: 2020-01-01\n    tags: [rust]\n- title: b\n  url: /b\n  body: text\n",
+        )
+        .unwrap();
+        assert_eq!(yaml[0].meta["date"], "2020-01-01");
+        assert_eq!(yaml[0].meta["tags"], r#"["rust"]"#);
+        assert_eq!(yaml[1].body.as_deref(), Some("text"));
+
+        let toml = read_toml(
+            "[[posts]]\ntitle = \"a\"\nurl = \"/a\"\nmeta = { date = 2020-01-01, tags = [\"rust\"] }\n\n[[posts]]\ntitle = \"b\"\nurl = \"/b\"\n",
+        )
+        .unwrap();
+        assert_eq!(toml.len(), 2);
+        assert_eq!(toml[0].meta["date"], "2020-01-01");
+        assert_eq!(toml[0].meta["tags"], r#"["rust"]"#);
+    }
+}
@@ -0,0 +1,210 @@
+use anyhow::Error;
+use std::cmp::Reverse;
+use std::collections::BTreeSet;
+use std::path::Path;
+
+use super::storage::{self, BYTES_PER_WORD};
+use tinysearch::{hash_term_with_key, Compression, Storage, STORAGE_MAGIC};
+use xorf::Filter;
+
+/// Bytes an xor filter takes on top of `BYTES_PER_WORD` per term
+const FILTER_OVERHEAD: usize = 32;
+
+/// Probability that a filter claims to contain a term that isn't in it, about 1/256
+/// for filters with one byte per fingerprint
+const FALSE_POSITIVE_RATE: f64 = 1.0 / 256.0;
+
+/// Number of posts listed as the largest ones or as the matches of a term
+const LISTED_POSTS: usize = 10;
+
+/// Print what the storage at `path` holds. With `terms`, also list the posts whose
+/// filters contain each word of them, e.g. to find out why a post doesn't match.
+pub fn run(path: &Path, terms: &str, hash_key: &str) -> Result<(), Error> {
+    let bytes = storage::read_bytes(path)?;
+    println!("Format: {}", format(path, &bytes));
+    println!("Size: {} bytes", bytes.len());
+    let storage = storage::decode(path, &bytes)?;
+    let stats = storage.stats();
+
+    println!(
+        "Posts: {}, {} of them with meta",
+        stats.posts, stats.posts_with_meta
+    );
+    let types: Vec<String> = stats
+        .posts_per_type
+        .iter()
+        .map(|(post_type, count)| format!("{} {}", display_type(post_type), count))
+        .collect();
+    println!("Post types: {}", types.join(", "));
+    println!(
+        "Analyzer: cjk {}, normalize unicode {}, digits {}, joiners {}",
+        storage.analyzer.cjk,
+        storage.analyzer.normalize_unicode,
+        storage.analyzer.digits,
+        none_if_empty(&storage.analyzer.joiners.to_string())
+    );
+    println!(
+        "Typo filters: {} bytes, maximum distance {}",
+        stats.typo_filter_bytes, stats.max_typo_distance
+    );
+    let facet_fields: Vec<&str> = storage
+        .facets
+        .iter()
+        .flat_map(|facets| facets.keys().map(String::as_str))
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect();
+    println!("Facet fields: {}", none_if_empty(&facet_fields.join(", ")));
+    println!(
+        "Dates: {} posts",
+        storage.dates.iter().filter(|date| date.is_some()).count()
+    );
+    println!("Bigram boost: {}", storage.bigram_boost);
+    println!(
+        "Languages: {}, minimum word length {}",
+        storage
+            .languages
+            .iter()
+            .map(|language| language.to_string())
+            .collect::<Vec<_>>()
+            .join(", "),
+        storage.min_word_len
+    );
+    println!("Related terms: {} words", storage.related.len());
+    println!("Corpus hash: {}", none_if_empty(&storage.corpus_hash));
+
+    let mut terms_per_post: Vec<usize> = storage
+        .filters
+        .iter()
+        .map(|(_, filter)| estimated_terms(filter.len()))
+        .collect();
+    terms_per_post.sort_unstable();
+    if let (Some(min), Some(max)) = (terms_per_post.first(), terms_per_post.last()) {
+        println!(
+            "Post filters: {} bytes, about {} terms per post (min {}, median {}, max {})",
+            stats.filter_bytes,
+            terms_per_post.iter().sum::<usize>() / terms_per_post.len(),
+            min,
+            terms_per_post[terms_per_post.len() / 2],
+            max
+        );
+    }
+    println!(
+        "False positives: a word that isn't in a post matches it with a probability of \
+         {:.2}%, so each query word matches about {:.2} posts by mistake",
+        FALSE_POSITIVE_RATE * 100.0,
+        stats.posts as f64 * FALSE_POSITIVE_RATE
+    );
+
+    let mut largest: Vec<_> = storage.filters.iter().collect();
+    largest.sort_by_key(|(_, filter)| Reverse(filter.len()));
+    println!("Largest posts:");
+    for (post_id, filter) in largest.iter().take(LISTED_POSTS) {
+        println!(
+            "  {} bytes, about {} terms: {} ({})",
+            filter.len(),
+            estimated_terms(filter.len()),
+            post_id.0,
+            post_id.1
+        );
+    }
+
+    for word in storage.analyzer.tokenize(terms) {
+        let posts = containing(&storage, &word, hash_key);
+        println!(
+            "\"{}\" is in the filters of {} posts{}",
+            word,
+            posts.len(),
+            match posts.is_empty() {
+                true => " (is it a stopword, or was the index built with a --hash-key?)",
+                false => ":",
+            }
+        );
+        for title in posts.iter().take(LISTED_POSTS) {
+            println!("  {}", title);
+        }
+        if posts.len() > LISTED_POSTS {
+            println!("  and {} more", posts.len() - LISTED_POSTS);
+        }
+    }
+    Ok(())
+}
+
+/// Describe the header of the storage, which tells how to decode it
+fn format(path: &Path, bytes: &[u8]) -> String {
+    if storage::is_json(path, bytes) {
+        return "JSON".to_string();
+    }
+    match bytes.strip_prefix(&STORAGE_MAGIC) {
+        Some([low, high, compression, ..]) => {
+            let version = u16::from_le_bytes([*low, *high]);
+            // Version 1 has no compression byte
+            let compression = match Compression::from_byte(*compression) {
+                Ok(compression) if version > 1 => compression.to_string(),
+                _ => Compression::None.to_string(),
+            };
+            format!(
+                "bincode, storage version {}, compression {}",
+                version, compression
+            )
+        }
+        Some(_) => "bincode, truncated header".to_string(),
+        None => "bincode without a header, written before storage version 1".to_string(),
+    }
+}
+
+/// Number of terms of a post, judging by the size of its filter
+fn estimated_terms(filter_len: usize) -> usize {
+    (filter_len.saturating_sub(FILTER_OVERHEAD) as f64 / BYTES_PER_WORD).round() as usize
+}
+
+/// Titles of the posts whose filters contain the word, including false positives
+fn containing<'a>(storage: &'a Storage, word: &str, hash_key: &str) -> Vec<&'a str> {
+    let hash = hash_term_with_key(word, hash_key);
+    storage
+        .filters
+        .iter()
+        .filter(|(_, filter)| filter.contains_hash(hash))
+        .map(|(post_id, _)| post_id.0.as_str())
+        .collect()
+}
+
+fn display_type(post_type: &str) -> &str {
+    match post_type {
+        "" => "(root)",
+        post_type => post_type,
+    }
+}
+
+fn none_if_empty(s: &str) -> &str {
+    match s {
+        "" => "none",
+        s => s,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format() {
+        let path = Path::new("storage");
+        let storage = Storage::from_texts([(
+            ("Rust".to_string(), "/rust".to_string(), Default::default()),
+            "fearless concurrency".to_string(),
+        )]);
+        let bytes = storage.to_bytes().unwrap();
+        assert_eq!(
+            format(path, &bytes),
+            format!(
+                "bincode, storage version {}, compression none",
+                tinysearch::STORAGE_VERSION
+            )
+        );
+        assert_eq!(format(Path::new("storage.json"), b"{}"), "JSON");
+        assert!(containing(&storage, "fearless", "").contains(&"Rust"));
+        assert_eq!(estimated_terms(32 + 123), 100);
+        assert_eq!(estimated_terms(9), 0);
+    }
+}
@@ -0,0 +1,109 @@
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use serde_json::json;
+use std::io::{self, Write};
+use strum::{EnumString, IntoStaticStr};
+
+use super::progress;
+
+/// How log messages are written to standard error
+#[derive(IntoStaticStr, EnumString, Clone, Copy, Default, PartialEq, Eq, Debug)]
+#[strum(serialize_all = "snake_case")]
+pub enum LogFormat {
+    /// The message, prefixed with its level unless it's progress
+    #[default]
+    Text,
+    /// A JSON object per line with the time, level, target and message, e.g. for CI
+    Json,
+}
+
+/// Writes the messages of the `log` macros to standard error. Results go to standard
+/// output with `println!`, so they can be piped while the log stays visible.
+struct Logger {
+    format: LogFormat,
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        // The debug messages of dependencies, e.g. of the HTTP client, are noise
+        // unless asked for with -vv
+        metadata.level() <= log::max_level()
+            && (metadata.target().starts_with("tinysearch")
+                || metadata.level() <= Level::Warn
+                || log::max_level() == LevelFilter::Trace)
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let line = format(self.format, record);
+        // Like eprintln!, but a closed standard error isn't worth a panic
+        progress::suspend(|| writeln!(io::stderr().lock(), "{}", line)).ok();
+    }
+
+    fn flush(&self) {
+        let _ = io::stderr().flush();
+    }
+}
+
+fn format(format: LogFormat, record: &Record) -> String {
+    match format {
+        LogFormat::Text => match record.level() {
+            Level::Info => record.args().to_string(),
+            Level::Warn => format!("warning: {}", record.args()),
+            level => format!("{}: {}", level.as_str().to_lowercase(), record.args()),
+        },
+        LogFormat::Json => json!({
+            "time": chrono::Utc::now().to_rfc3339(),
+            "level": record.level().as_str().to_lowercase(),
+            "target": record.target(),
+            "message": record.args().to_string(),
+        })
+        .to_string(),
+    }
+}
+
+/// The level of the messages shown: progress and warnings by default, more with each
+/// -v and only warnings and errors with -q
+pub fn level(verbose: u8, quiet: bool) -> LevelFilter {
+    match (quiet, verbose) {
+        (true, _) => LevelFilter::Warn,
+        (false, 0) => LevelFilter::Info,
+        (false, 1) => LevelFilter::Debug,
+        (false, _) => LevelFilter::Trace,
+    }
+}
+
+/// Install the logger at startup. Later calls keep the first logger.
+pub fn init(level: LevelFilter, format: LogFormat) {
+    if log::set_logger(Box::leak(Box::new(Logger { format }))).is_ok() {
+        log::set_max_level(level);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format() {
+        let record = Record::builder()
+            .args(format_args!("Storage ready"))
+            .level(Level::Info)
+            .target("tinysearch")
+            .build();
+        assert_eq!(format(LogFormat::Text, &record), "Storage ready");
+        let line: serde_json::Value =
+            serde_json::from_str(&format(LogFormat::Json, &record)).unwrap();
+        assert_eq!(line["level"], "info");
+        assert_eq!(line["message"], "Storage ready");
+
+        let record = Record::builder()
+            .args(format_args!("No date"))
+            .level(Level::Warn)
+            .build();
+        assert_eq!(format(LogFormat::Text, &record), "warning: No date");
+        assert_eq!(level(2, false), LevelFilter::Trace);
+        assert_eq!(level(2, true), LevelFilter::Warn);
+    }
+}
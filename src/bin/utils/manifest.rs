@@ -0,0 +1,67 @@
+use anyhow::{Context, Error};
+use serde::Deserialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Sites built by batch mode, which share a single WASM module
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct Manifest {
+    pub sites: Vec<Site>,
+}
+
+#[derive(Deserialize, Debug, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Site {
+    /// Posts of the site, like the input file of the other modes
+    pub input: PathBuf,
+    /// Directory the index and the loader of the site are written to
+    pub out: PathBuf,
+    /// Options of the site, e.g. `["--source", "hugo", "--fuzzy", "1"]`
+    #[serde(default)]
+    pub args: Vec<String>,
+}
+
+impl Manifest {
+    /// Read a JSON manifest like `{ "sites": [{ "input": "blog/index.json", "out": "blog/search" }] }`.
+    /// Relative paths are resolved against the directory of the manifest.
+    pub fn read(path: &Path) -> Result<Self, Error> {
+        let raw = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read manifest {}", path.display()))?;
+        let mut manifest: Manifest = serde_json::from_str(&raw)
+            .with_context(|| format!("Failed to parse manifest {}", path.display()))?;
+        let dir = path.parent().unwrap_or(Path::new(""));
+        for site in &mut manifest.sites {
+            site.input = dir.join(&site.input);
+            site.out = dir.join(&site.out);
+        }
+        Ok(manifest)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_manifest() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("sites.json");
+        fs::write(
+            &path,
+            r#"{ "sites": [{ "input": "blog/index.json", "out": "/srv/blog", "args": ["--fuzzy", "1"] }] }"#,
+        )
+        .unwrap();
+        let manifest = Manifest::read(&path).unwrap();
+        assert_eq!(
+            manifest.sites,
+            [Site {
+                input: dir.path().join("blog/index.json"),
+                out: PathBuf::from("/srv/blog"),
+                args: vec!["--fuzzy".to_string(), "1".to_string()],
+            }]
+        );
+        fs::write(&path, r#"{ "sites": [{ "input": "a", "output": "b" }] }"#).unwrap();
+        assert!(Manifest::read(&path).is_err());
+    }
+}
@@ -0,0 +1,220 @@
+use anyhow::{Context, Error};
+use serde::Deserialize;
+use std::fs;
+use std::path::Path;
+
+use super::index::{self, Post, Posts};
+use super::storage;
+
+/// The subset of Jekyll front matter that tinysearch understands
+#[derive(Debug, Default, Deserialize)]
+struct FrontMatter {
+    title: Option<String>,
+    permalink: Option<String>,
+    date: Option<String>,
+    /// Date as it should be shown, e.g. "20. Juni 2019"
+    date_display: Option<String>,
+    /// Language tag of the post, e.g. "de-AT"
+    #[serde(alias = "locale")]
+    lang: Option<String>,
+    #[serde(default)]
+    tags: Tags,
+    /// Other URLs of the post, named `redirect_from` by jekyll-redirect-from
+    #[serde(default, alias = "redirect_from")]
+    aliases: Tags,
+    published: Option<bool>,
+}
+
+/// Jekyll accepts tags (and aliases) either as a YAML list or as a space-separated string
+#[derive(Debug, Default, Deserialize)]
+#[serde(untagged)]
+enum Tags {
+    #[default]
+    None,
+    List(Vec<String>),
+    Words(String),
+}
+
+impl Tags {
+    fn into_vec(self) -> Vec<String> {
+        match self {
+            Tags::None => Vec::new(),
+            Tags::List(tags) => tags,
+            Tags::Words(tags) => tags.split_whitespace().map(String::from).collect(),
+        }
+    }
+}
+
+/// Read all Markdown files (`.md`, `.markdown`) below `dir` into posts.
+/// Files with `published: false` in their front matter are skipped.
+pub fn read_dir(dir: &Path) -> Result<Posts, Error> {
+    let mut files = Vec::new();
+    collect_files(dir, &mut files)?;
+    // Keep the index stable regardless of directory iteration order
+    files.sort();
+
+    let mut posts = Posts::new();
+    for file in files {
+        let raw = fs::read_to_string(&file)
+            .with_context(|| format!("Failed to read file {}", file.display()))?;
+        let post = parse(&file, &raw)
+            .with_context(|| format!("Failed to parse front matter of {}", file.display()))?;
+        if let Some(post) = post {
+            posts.push(post);
+        }
+    }
+    Ok(posts)
+}
+
+fn collect_files(dir: &Path, files: &mut Vec<std::path::PathBuf>) -> Result<(), Error> {
+    for entry in
+        fs::read_dir(dir).with_context(|| format!("Failed to read directory {}", dir.display()))?
+    {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_files(&path, files)?;
+        } else if matches!(
+            path.extension().and_then(|e| e.to_str()),
+            Some("md" | "markdown")
+        ) {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Split a document into its YAML front matter (if any) and the Markdown body
+fn split_front_matter(raw: &str) -> (Option<&str>, &str) {
+    let rest = match raw
+        .strip_prefix("---\n")
+        .or_else(|| raw.strip_prefix("---\r\n"))
+    {
+        Some(rest) => rest,
+        None => return (None, raw),
+    };
+    let mut offset = 0;
+    for line in rest.split_inclusive('\n') {
+        if matches!(line.trim_end(), "---" | "...") {
+            return (Some(&rest[..offset]), &rest[offset + line.len()..]);
+        }
+        offset += line.len();
+    }
+    (None, raw)
+}
+
+/// Derive Jekyll's default (`date` style) permalink from a post file name,
+/// e.g. `2019-06-20-hello-world.md` becomes `/2019/06/20/hello-world.html`.
+fn default_permalink(stem: &str) -> String {
+    let parts: Vec<&str> = stem.splitn(4, '-').collect();
+    match parts.as_slice() {
+        [year, month, day, slug]
+            if [year, month, day]
+                .iter()
+                .all(|p| !p.is_empty() && p.chars().all(|c| c.is_ascii_digit())) =>
+        {
+            format!("/{}/{}/{}/{}.html", year, month, day, slug)
+        }
+        _ => format!("/{}.html", stem),
+    }
+}
+
+fn parse(path: &Path, raw: &str) -> Result<Option<Post>, Error> {
+    let (front_matter, body) = split_front_matter(raw);
+    let front_matter: FrontMatter = match front_matter {
+        Some(yaml) if !yaml.trim().is_empty() => serde_yaml::from_str(yaml)?,
+        _ => FrontMatter::default(),
+    };
+    if front_matter.published == Some(false) {
+        debug!("Skipping unpublished post {}", path.display());
+        return Ok(None);
+    }
+
+    let stem = path
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or_default();
+    let tags = front_matter.tags.into_vec();
+
+    let mut meta = serde_json::Map::new();
+    if let Some(date) = front_matter.date {
+        meta.insert("date".into(), date.into());
+    }
+    // Passed through for rendering results, e.g. with Intl.DateTimeFormat
+    if let Some(date_display) = front_matter.date_display {
+        meta.insert("date_display".into(), date_display.into());
+    }
+    if let Some(locale) = front_matter.lang {
+        meta.insert("locale".into(), locale.into());
+    }
+    if !tags.is_empty() {
+        meta.insert("tags".into(), tags.clone().into());
+    }
+    let aliases = front_matter.aliases.into_vec();
+    if !aliases.is_empty() {
+        meta.insert(storage::ALIASES_KEY.into(), aliases.into());
+    }
+
+    // Tags are appended to the body so that they are searchable as well
+    let mut body = body.to_string();
+    for tag in &tags {
+        body.push('\n');
+        body.push_str(tag);
+    }
+
+    Ok(Some(Post {
+        title: front_matter.title.unwrap_or_else(|| stem.to_string()),
+        url: front_matter
+            .permalink
+            .unwrap_or_else(|| default_permalink(stem)),
+        meta: index::meta(meta),
+        body: Some(body),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_front_matter() {
+        let raw =
+            "---\ntitle: Hello World\ntags: [rust, wasm]\ndate: 2019-06-20\n---\nSome *body*\n";
+        let post = parse(Path::new("_posts/2019-06-20-hello-world.md"), raw)
+            .unwrap()
+            .unwrap();
+        assert_eq!(post.title, "Hello World");
+        assert_eq!(post.url, "/2019/06/20/hello-world.html");
+        assert_eq!(post.meta["date"], "2019-06-20");
+        assert_eq!(post.meta["tags"], r#"["rust","wasm"]"#);
+        assert_eq!(post.body.as_deref(), Some("Some *body*\n\nrust\nwasm"));
+    }
+
+    #[test]
+    fn test_parse_locale() {
+        let raw =
+            "---\ntitle: Hallo\ndate: 2019-06-20\ndate_display: 20. Juni 2019\nlang: de-AT\n---\n";
+        let post = parse(Path::new("hallo.md"), raw).unwrap().unwrap();
+        assert_eq!(post.meta["date_display"], "20. Juni 2019");
+        assert_eq!(post.meta["locale"], "de-AT");
+    }
+
+    #[test]
+    fn test_parse_permalink_and_unpublished() {
+        let raw =
+            "---\ntitle: About\npermalink: /about/\ntags: one two\nredirect_from: /me/\n---\n";
+        let post = parse(Path::new("about.md"), raw).unwrap().unwrap();
+        assert_eq!(post.url, "/about/");
+        assert_eq!(post.meta["aliases"], r#"["/me/"]"#);
+
+        let raw = "---\ntitle: Draft\npublished: false\n---\nbody";
+        assert!(parse(Path::new("draft.md"), raw).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_no_front_matter() {
+        let post = parse(Path::new("notes.md"), "# Notes\n").unwrap().unwrap();
+        assert_eq!(post.title, "notes");
+        assert_eq!(post.url, "/notes.html");
+        assert!(post.meta.is_empty());
+    }
+}
@@ -1,3 +1,26 @@
+pub mod analyze;
 pub mod assets;
+pub mod bench;
+pub mod bookmarks;
+pub mod build_manifest;
+pub mod crawl;
+pub mod csv;
+pub mod deploy;
+pub mod doctor;
+pub mod engine;
+pub mod feed;
+pub mod http;
 pub mod index;
+pub mod inspect;
+pub mod logger;
+pub mod manifest;
+pub mod markdown;
+pub mod nojs;
+pub mod progress;
+pub mod source;
 pub mod storage;
+pub mod strings;
+pub mod strip_html;
+pub mod validate;
+#[cfg(feature = "verify")]
+pub mod verify;
@@ -0,0 +1,124 @@
+use anyhow::{Context, Error};
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs;
+use std::path::Path;
+
+use super::assets;
+use super::index::Posts;
+use super::storage::{
+    dates, facets, post_words, prepare_posts, query_stopwords, related_terms, IndexOptions,
+};
+use tinysearch::{Analyzer, Boosts, Facets, Meta, TypeLimits};
+
+/// The index of the JavaScript fallback. Instead of filters, it stores the words
+/// of each post as indices into a shared list of words.
+#[derive(Serialize)]
+struct Index<'a> {
+    words: Vec<&'a str>,
+    /// `[title, url, meta, words]` of each post
+    posts: Vec<(String, String, Meta, Vec<usize>)>,
+    boosts: Boosts,
+    type_limits: TypeLimits,
+    analyzer: Analyzer,
+    /// Facets of each post, in the same order as `posts`
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    facets: Vec<Facets>,
+    /// Unix timestamps of the posts, in the same order as `posts`
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    dates: Vec<Option<i64>>,
+    /// See `Storage::bigram_boost`, the pairs of words are among the `words`
+    bigram_boost: usize,
+    /// See `Storage::stopwords`
+    stopwords: BTreeSet<String>,
+    min_word_len: usize,
+    /// See `Storage::related`
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    related: BTreeMap<String, Vec<String>>,
+}
+
+/// Write a pure JavaScript implementation of the search along with the index
+/// of the posts, for browsers that can't run WebAssembly
+pub fn write(posts: Posts, path: &Path, options: &IndexOptions) -> Result<(), Error> {
+    let posts = post_words(prepare_posts(posts), options);
+    let words: BTreeSet<&str> = posts
+        .iter()
+        .flat_map(|(_, words)| words.iter().map(String::as_str))
+        .collect();
+    let words: Vec<&str> = words.into_iter().collect();
+    let positions: HashMap<&str, usize> = words
+        .iter()
+        .enumerate()
+        .map(|(i, word)| (*word, i))
+        .collect();
+    let post_facets = if options.facet_fields.is_empty() {
+        Vec::new()
+    } else {
+        posts
+            .iter()
+            .map(|((_, _, meta), _)| facets(meta, &options.facet_fields))
+            .collect()
+    };
+    let post_dates = match &options.date_field {
+        Some(field) => dates(posts.iter().map(|(post_id, _)| post_id), field),
+        None => Vec::new(),
+    };
+    let index = Index {
+        posts: posts
+            .iter()
+            .map(|((title, url, meta), post_words)| {
+                let mut ids: Vec<usize> = post_words
+                    .iter()
+                    .map(|word| positions[word.as_str()])
+                    .collect();
+                ids.sort_unstable();
+                (title.clone(), url.clone(), meta.clone(), ids)
+            })
+            .collect(),
+        words,
+        boosts: options.boosts,
+        type_limits: options.type_limits.clone(),
+        analyzer: options.analyzer,
+        facets: post_facets,
+        dates: post_dates,
+        bigram_boost: options.bigram_boost,
+        stopwords: query_stopwords(&options.languages(posts.iter().map(|((_, _, meta), _)| meta)))
+            .map(String::from)
+            .collect(),
+        min_word_len: options.min_word_len,
+        related: related_terms(&posts, options.related_terms),
+    };
+    let js = assets::with_results(assets::NOJS_JS).replacen(
+        "{INDEX}",
+        &serde_json::to_string(&index)?,
+        1,
+    );
+    fs::write(path, js).with_context(|| format!("Failed writing {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::index::Post;
+    use super::*;
+
+    #[test]
+    fn test_write() {
+        let posts = vec![Post {
+            title: "Hello".into(),
+            url: "/hello".into(),
+            meta: Meta::new(),
+            body: Some("rust and wasm".into()),
+        }];
+        let file = tempfile::NamedTempFile::new().unwrap();
+        write(posts, file.path(), &IndexOptions::default()).unwrap();
+        let js = fs::read_to_string(file.path()).unwrap();
+        // Stopwords are left out, the title is indexed as well
+        assert!(js.contains(
+            r#"{"words":["hello","rust","wasm"],"posts":[["Hello","/hello",{},[0,1,2]]]"#
+        ));
+        assert!(!js.contains("{INDEX}"));
+        // The result handling shared with the loader is filled in
+        assert!(!js.contains("{RESULTS_JS}"));
+        assert!(js.contains("export function safeUrl"));
+    }
+}
@@ -0,0 +1,163 @@
+use anyhow::{bail, Context, Error};
+use indicatif::{MultiProgress, ProgressBar, ProgressState, ProgressStyle};
+use std::fmt;
+use std::io::{self, BufRead, BufReader, IsTerminal, Read};
+use std::process::{Command, Stdio};
+use std::sync::OnceLock;
+use std::thread;
+use std::time::Duration;
+use strum::{EnumString, IntoStaticStr};
+
+/// When to show progress bars
+#[derive(IntoStaticStr, EnumString, Clone, Copy, Default, PartialEq, Eq, Debug)]
+#[strum(serialize_all = "snake_case")]
+pub enum ProgressMode {
+    /// When standard error is a terminal and progress isn't silenced with -q
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+/// The bars shown, if progress is enabled. Messages are logged through it, so that
+/// they don't end up in the middle of a bar.
+static BARS: OnceLock<MultiProgress> = OnceLock::new();
+
+/// Enable progress bars, once at startup. `quiet` tells whether progress messages
+/// are silenced.
+pub fn init(mode: ProgressMode, quiet: bool) {
+    let enabled = match mode {
+        ProgressMode::Auto => !quiet && io::stderr().is_terminal(),
+        ProgressMode::Always => true,
+        ProgressMode::Never => false,
+    };
+    if enabled {
+        let _ = BARS.set(MultiProgress::new());
+    }
+}
+
+/// A bar counting posts with their rate, or a hidden one if progress is disabled
+pub fn posts(len: usize, message: &'static str) -> ProgressBar {
+    let Some(bars) = BARS.get() else {
+        return ProgressBar::hidden();
+    };
+    let style =
+        ProgressStyle::with_template("{msg} [{bar:30}] {pos}/{len} posts ({rate}, {eta} left)")
+            .expect("valid template")
+            .with_key("rate", |state: &ProgressState, w: &mut dyn fmt::Write| {
+                let _ = write!(w, "{:.0}/s", state.per_sec());
+            })
+            .progress_chars("=> ");
+    bars.add(
+        ProgressBar::new(len as u64)
+            .with_style(style)
+            .with_message(message),
+    )
+}
+
+/// A spinner with the elapsed time for work of unknown length
+pub fn spinner(message: String) -> ProgressBar {
+    let Some(bars) = BARS.get() else {
+        return ProgressBar::hidden();
+    };
+    let style =
+        ProgressStyle::with_template("{spinner} {msg} ({elapsed})").expect("valid template");
+    let spinner = bars.add(
+        ProgressBar::new_spinner()
+            .with_style(style)
+            .with_message(message),
+    );
+    spinner.enable_steady_tick(Duration::from_millis(100));
+    spinner
+}
+
+/// Run `f`, e.g. printing a message, with the bars hidden
+pub fn suspend<R>(f: impl FnOnce() -> R) -> R {
+    match BARS.get() {
+        Some(bars) => bars.suspend(f),
+        None => f(),
+    }
+}
+
+/// Run a command and return its standard output. With progress enabled, the last
+/// line it printed to standard error, like the crate cargo is compiling, is shown
+/// next to a spinner instead of the full output, which is only shown if it fails.
+pub fn run(cmd: &mut Command, quiet: bool) -> Result<String, Error> {
+    if BARS.get().is_none() {
+        return run_plain(cmd, quiet);
+    }
+    let name = cmd.get_program().to_string_lossy().into_owned();
+    let mut child = cmd
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("failed to run {:?}", cmd))?;
+    let mut stdout = child.stdout.take().expect("stdout is piped");
+    let reader = thread::spawn(move || {
+        let mut output = String::new();
+        stdout.read_to_string(&mut output).map(|_| output)
+    });
+    let spinner = spinner(format!("Running {}", name));
+    let mut errors = Vec::new();
+    for line in BufReader::new(child.stderr.take().expect("stderr is piped")).lines() {
+        let line = line?;
+        if !line.trim().is_empty() {
+            spinner.set_message(format!("{}: {}", name, line.trim()));
+        }
+        errors.push(line);
+    }
+    let status = child.wait()?;
+    spinner.finish_and_clear();
+    let output = reader.join().expect("the reader doesn't panic")?;
+    if !status.success() {
+        bail!(
+            "failed to execute {:?}\nstatus: {}\n{}",
+            cmd,
+            status,
+            errors.join("\n")
+        );
+    }
+    Ok(output)
+}
+
+/// Like `run`, but the progress of the command is only shown if `quiet` is false
+fn run_plain(cmd: &mut Command, quiet: bool) -> Result<String, Error> {
+    let output = cmd
+        .stderr(if quiet {
+            Stdio::piped()
+        } else {
+            Stdio::inherit()
+        })
+        .output()
+        .with_context(|| format!("failed to run {:?}", cmd))?;
+    if !output.status.success() {
+        bail!(
+            "failed to execute {:?}\nstatus: {}{}",
+            cmd,
+            output.status,
+            String::from_utf8_lossy(&output.stderr)
+                .lines()
+                .map(|line| format!("\n{}", line))
+                .collect::<String>()
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_run() {
+        let output = run(Command::new("sh").args(["-c", "echo done"]), true).unwrap();
+        assert_eq!(output, "done\n");
+        let err = run(
+            Command::new("sh").args(["-c", "echo broken >&2; exit 1"]),
+            true,
+        )
+        .unwrap_err()
+        .to_string();
+        assert!(err.ends_with("\nbroken"), "{}", err);
+    }
+}
@@ -0,0 +1,211 @@
+//! Sources of posts, selected with `--source`.
+//!
+//! To add a source, implement [`Source`] in a new module (behind a feature if it
+//! needs extra dependencies) and list it in [`SOURCES`]. The rest of the pipeline
+//! only sees the posts it returns.
+
+use anyhow::{Context, Error};
+use std::io::Read;
+use std::path::Path;
+
+use super::index::{self, Posts};
+use super::storage::ContentFormat;
+use super::{bookmarks, crawl, csv, feed, http, markdown};
+
+/// Settings from the command line that sources may use
+pub struct ReadOptions<'a> {
+    /// CSS selector for the main content of HTML pages
+    pub content_selector: &'a str,
+    /// Fields of posts and the headers of the CSV columns holding them
+    pub columns: &'a [(String, String)],
+}
+
+pub trait Source: Sync {
+    /// Name of the source, as passed to `--source`
+    fn name(&self) -> &'static str;
+
+    /// Markup of the post bodies, unless overridden with `--content-format`
+    fn content_format(&self) -> ContentFormat {
+        ContentFormat::Markdown
+    }
+
+    /// Read the posts from the input file, directory, or URL
+    fn read(&self, input: &Path, options: &ReadOptions) -> Result<Posts, Error>;
+}
+
+/// All available sources. The first one is the default.
+pub static SOURCES: &[&dyn Source] = &[
+    &Json,
+    &Markdown,
+    &Feed,
+    &Sitemap,
+    &Opml,
+    &Bookmarks,
+    &Csv {
+        name: "csv",
+        delimiter: ',',
+    },
+    &Csv {
+        name: "tsv",
+        delimiter: '\t',
+    },
+];
+
+/// Look up a source by name
+pub fn find(name: &str) -> Option<&'static dyn Source> {
+    SOURCES.iter().copied().find(|source| source.name() == name)
+}
+
+/// A JSON file with an array of posts, or JSON Lines with a post per line.
+/// `.yaml` and `.toml` files hold the same posts in those formats.
+struct Json;
+
+impl Source for Json {
+    fn name(&self) -> &'static str {
+        "json"
+    }
+
+    fn read(&self, input: &Path, _options: &ReadOptions) -> Result<Posts, Error> {
+        let location = input.to_string_lossy();
+        let extension = input.extension().and_then(|extension| extension.to_str());
+        if let Some(extension @ ("yaml" | "yml" | "toml")) = extension {
+            let raw = http::read_to_string(&location)?;
+            let posts = match extension {
+                "toml" => index::read_toml(&raw).map_err(Error::from),
+                _ => index::read_yaml(&raw).map_err(Error::from),
+            };
+            return posts.with_context(|| format!("Failed to decode {}", input.display()));
+        }
+        let mut reader = http::open(&location)?;
+        let posts = if index::is_ndjson(&mut reader)? {
+            index::read_ndjson(reader)
+        } else {
+            let mut raw = String::new();
+            reader
+                .read_to_string(&mut raw)
+                .with_context(|| format!("Failed to read {}", location))?;
+            index::read(raw)
+        };
+        posts.with_context(|| format!("Failed to decode {}", input.display()))
+    }
+}
+
+/// A directory of Markdown files with YAML front matter
+struct Markdown;
+
+impl Source for Markdown {
+    fn name(&self) -> &'static str {
+        "markdown"
+    }
+
+    fn read(&self, input: &Path, _options: &ReadOptions) -> Result<Posts, Error> {
+        markdown::read_dir(input)
+    }
+}
+
+/// An RSS 2.0 or Atom feed
+struct Feed;
+
+impl Source for Feed {
+    fn name(&self) -> &'static str {
+        "feed"
+    }
+
+    fn content_format(&self) -> ContentFormat {
+        // The feed parser already extracts the text of each entry
+        ContentFormat::Plain
+    }
+
+    fn read(&self, input: &Path, _options: &ReadOptions) -> Result<Posts, Error> {
+        feed::read(&input.to_string_lossy())
+    }
+}
+
+/// The pages listed in a sitemap
+struct Sitemap;
+
+impl Source for Sitemap {
+    fn name(&self) -> &'static str {
+        "sitemap"
+    }
+
+    fn content_format(&self) -> ContentFormat {
+        // The crawler already extracts the text of each page
+        ContentFormat::Plain
+    }
+
+    fn read(&self, input: &Path, options: &ReadOptions) -> Result<Posts, Error> {
+        crawl::read(&input.to_string_lossy(), options.content_selector)
+    }
+}
+
+/// An OPML outline, e.g. a list of feeds
+struct Opml;
+
+impl Source for Opml {
+    fn name(&self) -> &'static str {
+        "opml"
+    }
+
+    fn content_format(&self) -> ContentFormat {
+        ContentFormat::Plain
+    }
+
+    fn read(&self, input: &Path, _options: &ReadOptions) -> Result<Posts, Error> {
+        bookmarks::read_opml(&input.to_string_lossy())
+    }
+}
+
+/// A bookmarks export of a browser
+struct Bookmarks;
+
+impl Source for Bookmarks {
+    fn name(&self) -> &'static str {
+        "bookmarks"
+    }
+
+    fn content_format(&self) -> ContentFormat {
+        // The descriptions were already extracted from the HTML
+        ContentFormat::Plain
+    }
+
+    fn read(&self, input: &Path, _options: &ReadOptions) -> Result<Posts, Error> {
+        bookmarks::read_html(&input.to_string_lossy())
+    }
+}
+
+/// A CSV or TSV file with a header row, e.g. a product catalog
+struct Csv {
+    name: &'static str,
+    delimiter: char,
+}
+
+impl Source for Csv {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn content_format(&self) -> ContentFormat {
+        ContentFormat::Plain
+    }
+
+    fn read(&self, input: &Path, options: &ReadOptions) -> Result<Posts, Error> {
+        csv::read(&input.to_string_lossy(), self.delimiter, options.columns)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find() {
+        assert_eq!(find("feed").map(|source| source.name()), Some("feed"));
+        assert!(find("notion").is_none());
+        let names: Vec<_> = SOURCES.iter().map(|source| source.name()).collect();
+        let mut unique = names.clone();
+        unique.sort();
+        unique.dedup();
+        assert_eq!(names.len(), unique.len(), "duplicate source names");
+    }
+}
@@ -1,80 +1,973 @@
-use anyhow::Error;
-use std::collections::{HashMap, HashSet};
+use anyhow::{bail, Context, Error};
+use chrono::{DateTime, NaiveDate, NaiveDateTime};
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+use regex::Regex;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fs;
+use std::io::{self, Read};
+use std::iter;
 use std::path;
+use std::time::Instant;
 
-use super::assets::STOP_WORDS;
-use super::index::Posts;
+use super::build_manifest;
+use super::http::STDIO;
+use super::index::{Post, Posts};
+use super::progress;
+use super::strip_html::strip_html;
+use sha2::{Digest, Sha256};
 use strip_markdown::strip_markdown;
-use tinysearch::{Filters, PostId, Storage};
-use xorf::HashProxy;
+use strum::{EnumString, IntoStaticStr};
+use tinysearch::{
+    bigram, Analyzer, Boosts, Compression, Facets, Filter, Filters, Language, Meta, PostId,
+    Storage, TypeLimits, Typos, CANONICAL_KEY, EXCERPT_KEY,
+};
 
-pub fn write(posts: Posts, path: &path::PathBuf) -> Result<(), Error> {
-    let filters = build(posts)?;
-    trace!("Storage::from");
-    let storage = Storage::from(filters);
-    trace!("Write");
-    fs::write(path, storage.to_bytes()?)?;
+/// Markup of the post bodies, which gets removed before tokenization
+#[derive(IntoStaticStr, EnumString, Clone, Copy, Debug, Default)]
+#[strum(serialize_all = "snake_case")]
+pub enum ContentFormat {
+    #[default]
+    Markdown,
+    Html,
+    Plain,
+}
+
+impl ContentFormat {
+    fn strip(self, content: &str) -> String {
+        match self {
+            ContentFormat::Markdown => strip_markdown(content),
+            ContentFormat::Html => strip_html(content),
+            ContentFormat::Plain => content.to_string(),
+        }
+    }
+}
+
+/// Plain text of the first `length` characters of `content`, cut at a word boundary
+pub fn excerpt(content: &str, format: ContentFormat, length: usize) -> String {
+    let text = format.strip(content);
+    let mut excerpt = String::new();
+    let mut len = 0;
+    for word in text.split_whitespace() {
+        let word_len = word.chars().count();
+        let separator = usize::from(len > 0);
+        if len + separator + word_len > length {
+            if len == 0 {
+                excerpt.extend(word.chars().take(length));
+            }
+            excerpt.push('…');
+            break;
+        }
+        if separator > 0 {
+            excerpt.push(' ');
+        }
+        excerpt.push_str(word);
+        len += separator + word_len;
+    }
+    excerpt
+}
+
+/// Remove the matches of the patterns from the titles and bodies of the posts, so
+/// they are neither indexed nor stored. Returns the number of matches of each pattern.
+pub fn redact(posts: &mut Posts, patterns: &[Regex]) -> Vec<usize> {
+    let mut counts = vec![0; patterns.len()];
+    for post in posts.iter_mut() {
+        for text in iter::once(&mut post.title).chain(post.body.as_mut()) {
+            for (pattern, count) in patterns.iter().zip(&mut counts) {
+                let matches = pattern.find_iter(text).count();
+                if matches > 0 {
+                    *count += matches;
+                    *text = pattern.replace_all(text, " ").into_owned();
+                }
+            }
+        }
+    }
+    counts
+}
+
+/// Leave out the posts whose URL matches one of `urls`, or that have one of the meta
+/// `fields` with the given value, e.g. drafts or tag pages. Returns the number of
+/// posts left out.
+pub fn skip(posts: &mut Posts, urls: &[Regex], fields: &[(String, String)]) -> usize {
+    let before = posts.len();
+    posts.retain(|post| {
+        let path = url_path(&post.url);
+        let skipped = urls
+            .iter()
+            .any(|url| url.is_match(&post.url) || url.is_match(path))
+            || fields
+                .iter()
+                .any(|(field, value)| post.meta.get(field) == Some(value));
+        if skipped {
+            debug!("Skipping {}", post.url);
+        }
+        !skipped
+    });
+    before - posts.len()
+}
+
+/// The part of an absolute URL from the path on, e.g. `/tags/rust/` of
+/// `https://example.com/tags/rust/`. Relative URLs are returned as they are.
+fn url_path(url: &str) -> &str {
+    match url.split_once("://") {
+        Some((_, rest)) => rest.find('/').map_or("/", |start| &rest[start..]),
+        None => url,
+    }
+}
+
+/// What to do with posts whose URL an earlier post already has
+#[derive(IntoStaticStr, EnumString, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[strum(serialize_all = "kebab-case")]
+pub enum Duplicates {
+    #[default]
+    KeepFirst,
+    /// Keep the last post with the URL, in place of the first one
+    KeepLast,
+    Error,
+}
+
+/// Keep one post per URL as `duplicates` says. Returns the URLs that several posts
+/// had, in the order they first appear.
+pub fn dedup(posts: &mut Posts, duplicates: Duplicates) -> Result<Vec<String>, Error> {
+    let mut positions: HashMap<String, usize> = HashMap::new();
+    let mut conflicts: Vec<String> = Vec::new();
+    let mut kept: Posts = Vec::with_capacity(posts.len());
+    for post in posts.drain(..) {
+        match positions.get(&post.url) {
+            Some(&i) => {
+                if !conflicts.contains(&post.url) {
+                    conflicts.push(post.url.clone());
+                }
+                if duplicates == Duplicates::KeepLast {
+                    kept[i] = post;
+                }
+            }
+            None => {
+                positions.insert(post.url.clone(), kept.len());
+                kept.push(post);
+            }
+        }
+    }
+    *posts = kept;
+    if duplicates == Duplicates::Error && !conflicts.is_empty() {
+        bail!("Several posts have the URL {}", conflicts.join(", "));
+    }
+    Ok(conflicts)
+}
+
+/// Store an excerpt of the body of each post in the `excerpt` field of its meta,
+/// returning the number of bytes added
+pub fn add_excerpts(posts: &mut Posts, format: ContentFormat, length: usize) -> usize {
+    let mut added = 0;
+    for post in posts.iter_mut() {
+        if let Some(body) = &post.body {
+            let excerpt = excerpt(body, format, length);
+            added += EXCERPT_KEY.len() + excerpt.len();
+            post.meta.insert(EXCERPT_KEY.into(), excerpt);
+        }
+    }
+    added
+}
+
+/// Encoding of the storage file
+#[derive(IntoStaticStr, EnumString, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[strum(serialize_all = "snake_case")]
+pub enum StorageFormat {
+    /// Compact binary format, which is embedded in the WASM module
+    #[default]
+    Bincode,
+    /// For inspecting and diffing the index, or loading it from other languages
+    Json,
+}
+
+impl StorageFormat {
+    /// Name of the storage file in the output directory
+    pub fn file_name(self) -> &'static str {
+        match self {
+            StorageFormat::Bincode => "storage",
+            StorageFormat::Json => "storage.json",
+        }
+    }
+
+    /// Name of the file of a shard of the storage
+    pub fn shard_file_name(self, shard: usize) -> String {
+        match self {
+            StorageFormat::Bincode => format!("storage-{}", shard),
+            StorageFormat::Json => format!("storage-{}.json", shard),
+        }
+    }
+}
+
+/// Read a storage file in either format, telling them apart by the file extension
+pub fn read(path: &path::Path) -> Result<Storage, Error> {
+    decode(path, &read_bytes(path)?)
+}
+
+/// Decode the bytes of a storage file read from `path`
+pub fn decode(path: &path::Path, bytes: &[u8]) -> Result<Storage, Error> {
+    if is_json(path, bytes) {
+        Ok(Storage::from_json(std::str::from_utf8(bytes)?)?)
+    } else {
+        Ok(Storage::from_bytes(bytes)?)
+    }
+}
+
+/// Read a storage file, or standard input for `-`, without decoding it
+pub fn read_bytes(path: &path::Path) -> Result<Vec<u8>, Error> {
+    if path == path::Path::new(STDIO) {
+        let mut bytes = Vec::new();
+        io::stdin()
+            .read_to_end(&mut bytes)
+            .context("Failed to read the storage from standard input")?;
+        Ok(bytes)
+    } else {
+        fs::read(path).with_context(|| format!("Failed to read input file: {}", path.display()))
+    }
+}
+
+/// Whether the storage read from `path` is JSON rather than bincode
+pub fn is_json(path: &path::Path, bytes: &[u8]) -> bool {
+    // Standard input has no extension to go by, but only JSON storage starts with a brace
+    match path.extension() {
+        Some(extension) => extension == "json",
+        None => path == path::Path::new(STDIO) && bytes.first() == Some(&b'{'),
+    }
+}
+
+/// The stopwords of a language, left out of the index
+pub fn stopwords(language: Language) -> HashSet<String> {
+    language.stopwords().map(String::from).collect()
+}
+
+/// The words queries leave out, which are stopwords in all of the `languages`,
+/// since the others are indexed for some posts
+pub fn query_stopwords(languages: &[Language]) -> impl Iterator<Item = &'static str> + '_ {
+    languages
+        .iter()
+        .flat_map(|language| language.stopwords())
+        .filter(|word| languages.iter().all(|language| language.is_stopword(word)))
+}
+
+/// The language of a locale like `de` or `de-AT`, if it's supported
+fn language_from_locale(locale: &str) -> Option<Language> {
+    let code = locale.split(['-', '_']).next()?.trim().to_lowercase();
+    code.parse().ok()
+}
+
+/// How the index is cut down to fit `IndexOptions::target_size`
+#[derive(IntoStaticStr, EnumString, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[strum(serialize_all = "kebab-case")]
+pub enum FitBy {
+    /// Drop the words found in the most posts, which rarely tell posts apart
+    #[default]
+    CommonWords,
+    /// Drop the words found in the fewest posts, e.g. typos and identifiers
+    RareWords,
+    /// Index only the first words of the longest bodies
+    TruncateBodies,
+}
+
+/// What was left out of the index to fit `IndexOptions::target_size`
+#[derive(Debug, PartialEq, Eq)]
+enum Cut {
+    Nothing,
+    /// The dropped words, in the order they were dropped
+    Words(Vec<String>),
+    /// Number of posts whose bodies were cut to their first `words` words
+    Bodies {
+        posts: usize,
+        words: usize,
+    },
+}
+
+/// Settings for turning posts into a storage
+#[derive(Clone, Debug, Default)]
+pub struct IndexOptions {
+    pub format: ContentFormat,
+    pub language: Language,
+    /// Meta field with the language of a post, e.g. `lang`, which selects its
+    /// stopwords instead of `language`
+    pub language_field: Option<String>,
+    /// Words with fewer characters are left out of the index and out of queries
+    pub min_word_len: usize,
+    pub boosts: Boosts,
+    /// Maximum edit distance for fuzzy search, 0 leaves out the typo filters
+    pub typo_distance: usize,
+    pub analyzer: Analyzer,
+    /// Default result limits per post type
+    pub type_limits: TypeLimits,
+    pub storage_format: StorageFormat,
+    /// Compression of the bincode storage
+    pub compression: Compression,
+    /// Number of shards to split the storage into, 0 and 1 write a single file
+    pub shards: usize,
+    /// Meta fields to store as facets, which searches can filter by
+    pub facet_fields: Vec<String>,
+    /// Meta field with the date of a post, which searches can sort or boost by
+    pub date_field: Option<String>,
+    /// Points for query words in the same order as in a post, 0 doesn't index
+    /// the pairs of adjacent words
+    pub bigram_boost: usize,
+    /// Number of words found in the most posts to store related terms for, see
+    /// `related_terms`
+    pub related_terms: usize,
+    /// Secret key the words are hashed with, empty for none
+    pub hash_key: String,
+    /// Maximum size of the storage file in bytes, which the index is cut down to fit
+    /// into as `fit_by` says
+    pub target_size: Option<usize>,
+    pub fit_by: FitBy,
+    /// Sort the posts by URL and their words, and check that a second build gives the
+    /// same bytes, so that unchanged posts give an unchanged storage
+    pub deterministic: bool,
+}
+
+impl IndexOptions {
+    /// Language of the post with `meta`: the one its `language_field` names if that's
+    /// supported, `language` otherwise
+    pub fn post_language(&self, meta: &Meta) -> Language {
+        self.language_field
+            .as_ref()
+            .and_then(|field| meta.get(field))
+            .and_then(|locale| language_from_locale(locale))
+            .unwrap_or(self.language)
+    }
+
+    /// The distinct languages of the posts with the given meta, which the index
+    /// records so that queries leave out their stopwords, or `language` without posts
+    pub fn languages<'a>(&self, metas: impl Iterator<Item = &'a Meta>) -> Vec<Language> {
+        let mut languages = vec![];
+        for language in metas.map(|meta| self.post_language(meta)) {
+            if !languages.contains(&language) {
+                languages.push(language);
+            }
+        }
+        if languages.is_empty() {
+            languages.push(self.language);
+        }
+        languages
+    }
+}
+
+pub fn write(posts: Posts, path: &path::PathBuf, options: &IndexOptions) -> Result<(), Error> {
+    let bytes = build(posts, options)?;
+    fs::write(path, &bytes)?;
+    fs::write(path.with_extension("hash"), fingerprint(&bytes))?;
     trace!("ok");
     Ok(())
 }
 
-fn build(posts: Posts) -> Result<Filters, Error> {
-    let posts = prepare_posts(posts);
-    generate_filters(posts)
+/// Build the storage of the posts, ready to be written
+pub fn build(posts: Posts, options: &IndexOptions) -> Result<Vec<u8>, Error> {
+    let start = Instant::now();
+    let (bytes, dropped) = match options.deterministic {
+        true => {
+            let (bytes, dropped) = build_words(posts.clone(), options)?;
+            if build_words(posts, options)?.0 != bytes {
+                bail!("Two builds of the same posts differ, even with --deterministic");
+            }
+            (bytes, dropped)
+        }
+        false => build_words(posts, options)?,
+    };
+    let target = options.target_size.unwrap_or_default();
+    match dropped {
+        Cut::Nothing => {}
+        Cut::Words(dropped) => {
+            let examples: Vec<&str> = dropped.iter().take(20).map(String::as_str).collect();
+            warn!(
+                "Dropped {} words found in the {} posts to fit {} bytes: {}{}",
+                dropped.len(),
+                match options.fit_by {
+                    FitBy::RareWords => "fewest",
+                    _ => "most",
+                },
+                target,
+                examples.join(", "),
+                if dropped.len() > examples.len() {
+                    ", ..."
+                } else {
+                    ""
+                }
+            );
+        }
+        Cut::Bodies { posts, words } => warn!(
+            "Indexed only the first {} words of the bodies of {} posts to fit {} bytes",
+            words, posts, target
+        ),
+    }
+    build_manifest::record("index", start.elapsed());
+    Ok(bytes)
+}
+
+/// Build the storage of the posts, cut down to fit `options.target_size` if given.
+/// Returns the storage and what was cut.
+fn build_words(posts: Posts, options: &IndexOptions) -> Result<(Vec<u8>, Cut), Error> {
+    let corpus_hash = corpus_hash(&posts)?;
+    match (options.target_size, options.fit_by) {
+        (Some(target), FitBy::TruncateBodies) => truncate(&posts, target, options, &corpus_hash),
+        (Some(target), _) => fit(&mut words(posts, options), target, options, &corpus_hash),
+        (None, _) => Ok((
+            encode(&words(posts, options), options, &corpus_hash)?,
+            Cut::Nothing,
+        )),
+    }
+}
+
+/// The words of each post, sorted with `options.deterministic`
+fn words(posts: Posts, options: &IndexOptions) -> Vec<(PostId, Vec<String>)> {
+    let mut words = post_words(prepare_posts(posts), options);
+    if options.deterministic {
+        // Posts and words come out of hash maps in an order that changes from run to run
+        words.sort_by(|(a, _), (b, _)| (&a.1, &a.0).cmp(&(&b.1, &b.0)));
+        for (_, post_words) in &mut words {
+            post_words.sort_unstable();
+        }
+    }
+    words
+}
+
+/// Encode the storage of the words of each post
+fn encode(
+    words: &[(PostId, Vec<String>)],
+    options: &IndexOptions,
+    corpus_hash: &str,
+) -> Result<Vec<u8>, Error> {
+    let typos = (options.typo_distance > 0).then(|| generate_typos(words, options));
+    let bar = progress::posts(words.len(), "Building filters");
+    let filters: Filters = map_posts(words.iter().collect(), |(post_id, words)| {
+        let filter = Filter::with_key(words, &options.hash_key);
+        bar.inc(1);
+        (post_id.clone(), filter)
+    });
+    bar.finish_and_clear();
+    trace!("Storage::from");
+    let facets = if options.facet_fields.is_empty() {
+        Vec::new()
+    } else {
+        filters
+            .iter()
+            .map(|(post_id, _)| facets(&post_id.2, &options.facet_fields))
+            .collect()
+    };
+    let dates = match &options.date_field {
+        Some(field) => dates(filters.iter().map(|(post_id, _)| post_id), field),
+        None => Vec::new(),
+    };
+    let storage = Storage {
+        filters,
+        boosts: options.boosts,
+        typos,
+        type_limits: options.type_limits.clone(),
+        analyzer: options.analyzer,
+        facets,
+        dates,
+        bigram_boost: options.bigram_boost,
+        corpus_hash: corpus_hash.to_string(),
+        languages: options.languages(words.iter().map(|(post_id, _)| &post_id.2)),
+        min_word_len: options.min_word_len,
+        related: related_terms(words, options.related_terms),
+    };
+    trace!("Write");
+    Ok(match (options.storage_format, options.compression) {
+        (StorageFormat::Bincode, compression) => storage.to_compressed_bytes(compression)?,
+        (StorageFormat::Json, Compression::None) => storage.to_json()?.into_bytes(),
+        (StorageFormat::Json, _) => bail!("Only bincode storage can be compressed"),
+    })
+}
+
+/// Fingerprint of the posts of an index, see [`Storage::corpus_hash`]
+pub fn corpus_hash(posts: &Posts) -> Result<String, Error> {
+    Ok(fingerprint(&serde_json::to_vec(posts)?))
+}
+
+/// Roughly the bytes an xor filter takes per word
+pub const BYTES_PER_WORD: f64 = 1.23;
+
+/// Drop the words found in the most posts, or in the fewest for `FitBy::RareWords`,
+/// until the storage fits in `target` bytes. Returns the storage and the dropped words.
+fn fit(
+    words: &mut [(PostId, Vec<String>)],
+    target: usize,
+    options: &IndexOptions,
+    corpus_hash: &str,
+) -> Result<(Vec<u8>, Cut), Error> {
+    let mut frequencies: HashMap<String, usize> = HashMap::new();
+    for (_, post_words) in words.iter() {
+        for word in post_words {
+            *frequencies.entry(word.clone()).or_default() += 1;
+        }
+    }
+    let mut frequencies: Vec<(String, usize)> = frequencies.into_iter().collect();
+    frequencies.sort_by(|(a, a_count), (b, b_count)| match options.fit_by {
+        FitBy::RareWords => a_count.cmp(b_count).then(a.cmp(b)),
+        _ => b_count.cmp(a_count).then(a.cmp(b)),
+    });
+    let mut frequencies = frequencies.into_iter();
+    let mut dropped = Vec::new();
+    loop {
+        let bytes = encode(words, options, corpus_hash)?;
+        if bytes.len() <= target {
+            let cut = match dropped.is_empty() {
+                true => Cut::Nothing,
+                false => Cut::Words(dropped),
+            };
+            return Ok((bytes, cut));
+        }
+        // Drop about as many words as needed at once, instead of encoding after each
+        let mut excess = (bytes.len() - target) as f64;
+        let mut batch = HashSet::new();
+        while excess > 0.0 {
+            let Some((word, count)) = frequencies.next() else {
+                break;
+            };
+            excess -= count as f64 * BYTES_PER_WORD;
+            dropped.push(word.clone());
+            batch.insert(word);
+        }
+        if batch.is_empty() {
+            bail!(
+                "The storage takes {} bytes even without any words, which is more than --target-size {}",
+                bytes.len(),
+                target
+            );
+        }
+        for (_, post_words) in words.iter_mut() {
+            post_words.retain(|word| !batch.contains(word));
+        }
+    }
+}
+
+/// Index only the first words of the bodies of the posts, with fewer words each time,
+/// until the storage fits in `target` bytes. Returns the storage and how many posts
+/// were cut to how many words.
+fn truncate(
+    posts: &Posts,
+    target: usize,
+    options: &IndexOptions,
+    corpus_hash: &str,
+) -> Result<(Vec<u8>, Cut), Error> {
+    let lengths: Vec<usize> = posts
+        .iter()
+        .map(|post| {
+            post.body
+                .as_deref()
+                .map_or(0, |body| body.split_whitespace().count())
+        })
+        .collect();
+    let longest = lengths.iter().copied().max().unwrap_or_default();
+    let mut limit = longest;
+    loop {
+        let truncated: Posts = posts
+            .iter()
+            .zip(&lengths)
+            .map(|(post, &length)| match (&post.body, length > limit) {
+                (Some(body), true) => Post {
+                    body: Some(
+                        body.split_whitespace()
+                            .take(limit)
+                            .collect::<Vec<_>>()
+                            .join(" "),
+                    ),
+                    ..post.clone()
+                },
+                _ => post.clone(),
+            })
+            .collect();
+        let bytes = encode(&words(truncated, options), options, corpus_hash)?;
+        if bytes.len() <= target {
+            let cut = match limit < longest {
+                true => Cut::Bodies {
+                    posts: lengths.iter().filter(|&&length| length > limit).count(),
+                    words: limit,
+                },
+                false => Cut::Nothing,
+            };
+            return Ok((bytes, cut));
+        }
+        if limit == 0 {
+            bail!(
+                "The storage takes {} bytes even with only the titles of the posts, which is more than --target-size {}",
+                bytes.len(),
+                target
+            );
+        }
+        // Fewer words by the share the storage is too large, and at least one
+        limit = ((limit as f64 * target as f64 / bytes.len() as f64) as usize).min(limit - 1);
+    }
+}
+
+/// Split the posts into `options.shards` consecutive batches and write a storage for
+/// each of them to `dir`. Besides the hash of each shard, `storage.hash` contains a
+/// hash of all of them. Returns the paths of the shards.
+pub fn write_shards(
+    mut posts: Posts,
+    dir: &path::Path,
+    options: &IndexOptions,
+) -> Result<Vec<path::PathBuf>, Error> {
+    let batch_size = posts.len().div_ceil(options.shards.max(1)).max(1);
+    let mut batches = Vec::new();
+    while !posts.is_empty() {
+        let rest = posts.split_off(batch_size.min(posts.len()));
+        batches.push(std::mem::replace(&mut posts, rest));
+    }
+    let mut paths = Vec::new();
+    let mut hashes = String::new();
+    for (shard, batch) in batches.into_iter().enumerate() {
+        let path = dir.join(options.storage_format.shard_file_name(shard));
+        write(batch, &path, options)?;
+        hashes.push_str(&fs::read_to_string(path.with_extension("hash"))?);
+        paths.push(path);
+    }
+    // Remove shards of earlier runs, which had more posts
+    for shard in paths.len()..options.shards {
+        let path = dir.join(options.storage_format.shard_file_name(shard));
+        if path.exists() {
+            fs::remove_file(&path)?;
+        }
+    }
+    fs::write(dir.join("storage.hash"), fingerprint(hashes.as_bytes()))?;
+    Ok(paths)
+}
+
+/// The elements of a meta value holding a JSON list, like tags, or the value itself
+fn meta_values(value: &str) -> Vec<String> {
+    serde_json::from_str(value).unwrap_or_else(|_| vec![value.to_string()])
 }
 
-/// Remove non-ascii characters from string
-/// Keep apostrophe (e.g. for words like "don't")
-fn cleanup(s: String) -> String {
-    s.replace(|c: char| !(c.is_alphabetic() || c == '\''), " ")
+/// Number of related terms stored for each word, see `related_terms`
+const RELATED_PER_TERM: usize = 5;
+
+/// The words that occur in the same posts as each of the `count` words found in the
+/// most posts, see `Storage::related`. Words are related by the share of the posts
+/// with either of them that have both (their Jaccard index), and only if they're
+/// found together in at least two posts.
+pub fn related_terms(
+    words: &[(PostId, Vec<String>)],
+    count: usize,
+) -> BTreeMap<String, Vec<String>> {
+    if count == 0 {
+        return BTreeMap::new();
+    }
+    // Pairs of words are indexed with a space between them, see `bigram`
+    let posts: Vec<HashSet<&str>> = words
+        .iter()
+        .map(|(_, post_words)| {
+            post_words
+                .iter()
+                .map(String::as_str)
+                .filter(|word| !word.contains(' '))
+                .collect()
+        })
+        .collect();
+    let mut frequencies: HashMap<&str, usize> = HashMap::new();
+    for word in posts.iter().flatten() {
+        *frequencies.entry(word).or_default() += 1;
+    }
+    let mut frequent: Vec<(&str, usize)> = frequencies
+        .iter()
+        .map(|(word, count)| (*word, *count))
+        .filter(|(_, count)| *count >= 2)
+        .collect();
+    frequent.sort_by(|(a, a_count), (b, b_count)| b_count.cmp(a_count).then(a.cmp(b)));
+    frequent.truncate(count);
+    frequent
+        .into_iter()
+        .filter_map(|(word, count)| {
+            let mut together: HashMap<&str, usize> = HashMap::new();
+            for post in posts.iter().filter(|post| post.contains(word)) {
+                for other in post.iter().filter(|other| **other != word) {
+                    *together.entry(other).or_default() += 1;
+                }
+            }
+            let mut related: Vec<(&str, f64)> = together
+                .into_iter()
+                .filter(|(_, both)| *both >= 2)
+                .map(|(other, both)| {
+                    let either = count + frequencies[other] - both;
+                    (other, both as f64 / either as f64)
+                })
+                .collect();
+            related.sort_by(|(a, a_share), (b, b_share)| b_share.total_cmp(a_share).then(a.cmp(b)));
+            let related: Vec<String> = related
+                .into_iter()
+                .take(RELATED_PER_TERM)
+                .map(|(other, _)| other.to_string())
+                .collect();
+            (!related.is_empty()).then(|| (word.to_string(), related))
+        })
+        .collect()
 }
 
-fn tokenize(words: &str, stopwords: &HashSet<String>) -> HashSet<String> {
-    cleanup(strip_markdown(words))
-        .split_whitespace()
-        .filter(|&word| !word.trim().is_empty())
-        .map(str::to_lowercase)
-        .filter(|word| !stopwords.contains(word))
+/// The values of the facet `fields` in the meta of a post. Fields holding a JSON list,
+/// like tags, have one value per element.
+pub fn facets(meta: &Meta, fields: &[String]) -> Facets {
+    fields
+        .iter()
+        .filter_map(|field| Some((field.clone(), meta_values(meta.get(field)?))))
         .collect()
 }
 
+/// Parse a date as a Unix timestamp. Besides RFC 3339 (`2020-01-31T12:00:00+01:00`),
+/// plain dates (`2020-01-31`) and Jekyll's `2020-01-31 12:00:00 +0100` are understood.
+/// Dates without a time zone are taken as UTC.
+pub fn parse_date(value: &str) -> Option<i64> {
+    let value = value.trim();
+    if let Ok(date) = DateTime::parse_from_rfc3339(value) {
+        return Some(date.timestamp());
+    }
+    if let Ok(date) = DateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S %z") {
+        return Some(date.timestamp());
+    }
+    ["%Y-%m-%d %H:%M:%S", "%Y-%m-%dT%H:%M:%S"]
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(value, format).ok())
+        .or_else(|| {
+            let date = NaiveDate::parse_from_str(value, "%Y-%m-%d").ok()?;
+            date.and_hms_opt(0, 0, 0)
+        })
+        .map(|date| date.and_utc().timestamp())
+}
+
+/// The parsed date `field` of each post. Posts without the field have no date,
+/// dates that can't be parsed are left out with a warning.
+pub fn dates<'a>(posts: impl Iterator<Item = &'a PostId>, field: &str) -> Vec<Option<i64>> {
+    let mut invalid = Vec::new();
+    let dates = posts
+        .map(|(_, url, meta)| {
+            let value = meta.get(field)?;
+            let date = parse_date(value);
+            if date.is_none() {
+                invalid.push(format!("{} ({})", value, url));
+            }
+            date
+        })
+        .collect();
+    if !invalid.is_empty() {
+        warn!(
+            "Ignoring the {} field of {} posts, which isn't a date, e.g. {}",
+            field,
+            invalid.len(),
+            invalid[0]
+        );
+    }
+    dates
+}
+
+/// Meta field with the other URLs of a post, e.g. the old paths it redirects from
+pub const ALIASES_KEY: &str = "aliases";
+
+/// Give posts that are available under several URLs a common [`CANONICAL_KEY`],
+/// so that searches return only one of them. Posts belong together if the URL
+/// of one is among the aliases of the other or if they share an alias. The
+/// canonical URL is the URL of the first of them that isn't an alias.
+pub fn resolve_aliases(posts: &mut Posts) {
+    fn find(parents: &mut [usize], mut i: usize) -> usize {
+        while parents[i] != i {
+            parents[i] = parents[parents[i]];
+            i = parents[i];
+        }
+        i
+    }
+
+    let aliases: Vec<Vec<String>> = posts
+        .iter()
+        .map(|post| match post.meta.get(ALIASES_KEY) {
+            Some(value) => meta_values(value)
+                .into_iter()
+                .filter(|alias| *alias != post.url)
+                .collect(),
+            None => Vec::new(),
+        })
+        .collect();
+    if aliases.iter().all(Vec::is_empty) {
+        return;
+    }
+
+    // Union-find over all URLs and aliases
+    let mut ids: HashMap<&str, usize> = HashMap::new();
+    for (post, post_aliases) in posts.iter().zip(&aliases) {
+        for url in std::iter::once(&post.url).chain(post_aliases) {
+            let next = ids.len();
+            ids.entry(url).or_insert(next);
+        }
+    }
+    let mut parents: Vec<usize> = (0..ids.len()).collect();
+    for (post, post_aliases) in posts.iter().zip(&aliases) {
+        for alias in post_aliases {
+            let root = find(&mut parents, ids[post.url.as_str()]);
+            let alias_root = find(&mut parents, ids[alias.as_str()]);
+            parents[alias_root] = root;
+        }
+    }
+    let mut groups: HashMap<usize, Vec<usize>> = HashMap::new();
+    for (i, post) in posts.iter().enumerate() {
+        groups
+            .entry(find(&mut parents, ids[post.url.as_str()]))
+            .or_default()
+            .push(i);
+    }
+
+    let all_aliases: HashSet<&str> = aliases.iter().flatten().map(String::as_str).collect();
+    let mut canonical: Vec<Option<String>> = vec![None; posts.len()];
+    for group in groups.values().filter(|group| group.len() > 1) {
+        let url = group
+            .iter()
+            .map(|&i| &posts[i].url)
+            .find(|url| !all_aliases.contains(url.as_str()))
+            .unwrap_or(&posts[group[0]].url);
+        for &i in group {
+            canonical[i] = Some(url.clone());
+        }
+    }
+    for (post, canonical) in posts.iter_mut().zip(canonical) {
+        if let Some(canonical) = canonical {
+            post.meta.insert(CANONICAL_KEY.to_string(), canonical);
+        }
+    }
+}
+
+/// Short content hash of the storage (the first 16 hex digits of its SHA-256),
+/// used to give generated assets cache-friendly names
+pub fn fingerprint(bytes: &[u8]) -> String {
+    let digest = format!("{:x}", Sha256::digest(bytes));
+    digest[..16].to_string()
+}
+
+/// Index the deletion variants of all words for typo-tolerant search
+fn generate_typos(words: &[(PostId, Vec<String>)], options: &IndexOptions) -> Typos {
+    let max_distance = options.typo_distance;
+    debug!("Generate typo filters");
+    let filters = map_posts(words.iter().collect(), |(_post_id, words)| {
+        // Pairs of words (see `bigram`) are only matched exactly
+        let words: Vec<String> = words
+            .iter()
+            .filter(|word| !word.contains(' '))
+            .cloned()
+            .collect();
+        Typos::filter_with_key(&words, max_distance, &options.hash_key)
+    });
+    Typos {
+        max_distance,
+        filters,
+    }
+}
+
+/// Why a word is left out of the index, see `analyze`
+#[derive(Debug, PartialEq, Eq)]
+pub enum Dropped {
+    Stopword,
+    TooShort,
+}
+
+/// The words of `text` as the analyzer splits them, each with the terms it's indexed
+/// as, or why it's left out
+#[allow(clippy::type_complexity)]
+pub fn analyze(
+    text: &str,
+    stopwords: &HashSet<String>,
+    options: &IndexOptions,
+) -> Vec<(String, Result<Vec<String>, Dropped>)> {
+    options
+        .analyzer
+        .split(&options.format.strip(text))
+        .into_iter()
+        .map(|word| {
+            if stopwords.contains(&word) {
+                return (word, Err(Dropped::Stopword));
+            }
+            let terms: Vec<String> = options
+                .analyzer
+                .words(&word)
+                .into_iter()
+                .filter(|term| !term.is_empty() && term.chars().count() >= options.min_word_len)
+                .collect();
+            match terms.is_empty() {
+                true => (word, Err(Dropped::TooShort)),
+                false => (word, Ok(terms)),
+            }
+        })
+        .collect()
+}
+
+fn tokenize(words: &str, stopwords: &HashSet<String>, options: &IndexOptions) -> HashSet<String> {
+    let words: Vec<String> = analyze(words, stopwords, options)
+        .into_iter()
+        .filter_map(|(_, terms)| terms.ok())
+        .flatten()
+        .collect();
+    let bigrams: Vec<String> = match options.bigram_boost {
+        0 => Vec::new(),
+        _ => words
+            .windows(2)
+            .map(|pair| bigram(&pair[0], &pair[1]))
+            .collect(),
+    };
+    words.into_iter().chain(bigrams).collect()
+}
+
 // Read all posts and generate Bloomfilters from them.
 #[no_mangle]
-pub fn generate_filters(posts: HashMap<PostId, Option<String>>) -> Result<Filters, Error> {
+pub fn generate_filters(
+    posts: HashMap<PostId, Option<String>>,
+    options: &IndexOptions,
+) -> Result<Filters, Error> {
+    let filters = post_words(posts, options)
+        .into_iter()
+        .map(|(post_id, words)| (post_id, Filter::from(&words)))
+        .collect();
+    trace!("Done");
+    Ok(filters)
+}
+
+/// The normalized set of words of each post, including its title
+pub fn post_words(
+    posts: HashMap<PostId, Option<String>>,
+    options: &IndexOptions,
+) -> Vec<(PostId, Vec<String>)> {
     // Create a dictionary of {"post name": "lowercase word set"}. split_posts =
     // {name: set(re.split("\W+", contents.lower())) for name, contents in
     // posts.items()}
     debug!("Generate filters");
 
-    let stopwords: HashSet<String> = STOP_WORDS.split_whitespace().map(String::from).collect();
-
-    let split_posts: HashMap<PostId, Option<HashSet<String>>> = posts
+    let languages: HashSet<Language> = posts
+        .keys()
+        .map(|post_id| options.post_language(&post_id.2))
+        .collect();
+    let stopwords: HashMap<Language, HashSet<String>> = languages
         .into_iter()
-        .map(|(post, content)| {
-            debug!("Generating {:?}", post);
-            (post, content.map(|content| tokenize(&content, &stopwords)))
-        })
+        .map(|language| (language, stopwords(language)))
         .collect();
 
-    // At this point, we have a dictionary of posts and a normalized set of
-    // words in each. We could do more things, like stemming, removing common
-    // words (a, the, etc), but we’re going for naive, so let’s just create the
-    // filters for now:
-    let mut filters = Vec::new();
-    for (post_id, body) in split_posts {
+    // At this point, we have a dictionary of posts. We could do more things, like
+    // stemming, removing common words (a, the, etc), but we’re going for naive, so
+    // let’s just normalize the words of each post for the filters:
+    let bar = progress::posts(posts.len(), "Analyzing posts");
+    let words = map_posts(posts.into_iter().collect(), |(post_id, body)| {
+        debug!("Generating {:?}", post_id);
+        let stopwords = &stopwords[&options.post_language(&post_id.2)];
+        let body = body.map(|body| tokenize(&body, stopwords, options));
         // Also add title to filter
-        let title: HashSet<String> = tokenize(&post_id.0, &stopwords);
+        let title: HashSet<String> = tokenize(&post_id.0, stopwords, options);
         let content: Vec<String> = if let Some(body) = body {
             body.union(&title).cloned().collect()
         } else {
             title.into_iter().collect()
         };
-        let filter = HashProxy::from(&content);
-        filters.push((post_id, filter));
-    }
-    trace!("Done");
-    Ok(filters)
+        bar.inc(1);
+        (post_id, content)
+    });
+    bar.finish_and_clear();
+    words
+}
+
+/// Apply `f` to each post, on all cores with the `parallel` feature. The results are
+/// in the order of the posts either way.
+fn map_posts<T: Send, U: Send>(posts: Vec<T>, f: impl Fn(T) -> U + Sync + Send) -> Vec<U> {
+    #[cfg(feature = "parallel")]
+    return posts.into_par_iter().map(f).collect();
+    #[cfg(not(feature = "parallel"))]
+    return posts.into_iter().map(f).collect();
 }
 
 // prepares the files in the given directory to be consumed by the generator
@@ -89,8 +982,6 @@ pub fn prepare_posts(posts: Posts) -> HashMap<PostId, Option<String>> {
 
 #[cfg(test)]
 mod tests {
-    use xorf::Filter;
-
     use super::*;
 
     #[test]
@@ -100,28 +991,465 @@ mod tests {
             (
                 "Maybe You Don't Need Kubernetes, Or Excel - You Know".to_string(), //title
                 "".to_string(),                                                     //url
-                None,                                                               //meta
+                Meta::new(),                                                        //meta
             ),
             None, //body
         );
-        let filters = generate_filters(posts).unwrap();
+        let filters = generate_filters(posts, &IndexOptions::default()).unwrap();
         assert_eq!(filters.len(), 1);
         let (_post_id, filter) = filters.first().unwrap();
+        let contains = |term| filter.contains_hash(tinysearch::hash_term(term));
 
-        assert!(!filter.contains(&" ".to_owned()));
-        assert!(!filter.contains(&"    ".to_owned()));
-        assert!(!filter.contains(&"foo".to_owned()));
-        assert!(!filter.contains(&"-".to_owned()));
-        assert!(!filter.contains(&",".to_owned()));
-        assert!(!filter.contains(&"'".to_owned()));
+        assert!(!contains(" "));
+        assert!(!contains("    "));
+        assert!(!contains("foo"));
+        assert!(!contains("-"));
+        assert!(!contains(","));
+        assert!(!contains("'"));
 
         // "you", "don't", and "need" get stripped out because they are stopwords
-        assert!(!filter.contains(&"you".to_owned()));
-        assert!(!filter.contains(&"don't".to_owned()));
-        assert!(!filter.contains(&"need".to_owned()));
+        assert!(!contains("you"));
+        assert!(!contains("don't"));
+        assert!(!contains("need"));
+
+        assert!(contains("maybe"));
+        assert!(contains("kubernetes"));
+        assert!(contains("excel"));
+    }
 
-        assert!(filter.contains(&"maybe".to_owned()));
-        assert!(filter.contains(&"kubernetes".to_owned()));
-        assert!(filter.contains(&"excel".to_owned()));
+    #[test]
+    fn test_language_stopwords() {
+        let title = "Warum man nicht immer Kubernetes braucht";
+        let options = IndexOptions {
+            language: Language::De,
+            ..IndexOptions::default()
+        };
+        let words = tokenize(title, &stopwords(options.language), &options);
+        let expected: HashSet<String> = ["warum", "immer", "kubernetes", "braucht"]
+            .iter()
+            .map(|t| t.to_string())
+            .collect();
+        assert_eq!(words, expected);
+        assert!(Language::Ru.is_stopword("и"));
+    }
+
+    #[test]
+    fn test_language_field() {
+        let options = IndexOptions {
+            language_field: Some("lang".to_string()),
+            ..IndexOptions::default()
+        };
+        let german: Meta = [("lang".to_string(), "de-AT".to_string())].into();
+        let mut posts = HashMap::new();
+        posts.insert(
+            (
+                "The cat in the hat".to_string(),
+                "/en".to_string(),
+                Meta::new(),
+            ),
+            None,
+        );
+        posts.insert(
+            (
+                "Die Katze und der Hut".to_string(),
+                "/de".to_string(),
+                german.clone(),
+            ),
+            None,
+        );
+        let words: HashMap<String, HashSet<String>> = post_words(posts, &options)
+            .into_iter()
+            .map(|(post_id, words)| (post_id.1, words.into_iter().collect()))
+            .collect();
+        let expected = |words: &[&str]| words.iter().map(|w| w.to_string()).collect();
+        assert_eq!(words["/en"], expected(&["cat", "hat"]));
+        assert_eq!(words["/de"], expected(&["katze", "hut"]));
+
+        let languages = options.languages([Meta::new(), german].iter());
+        assert_eq!(languages, [Language::En, Language::De]);
+        let stopwords: Vec<&str> = query_stopwords(&languages).collect();
+        assert!(stopwords.contains(&"in"));
+        assert!(!stopwords.contains(&"the"));
+        assert!(!stopwords.contains(&"die"));
+    }
+
+    #[test]
+    fn test_cjk() {
+        let options = IndexOptions {
+            analyzer: Analyzer {
+                cjk: true,
+                ..Analyzer::default()
+            },
+            ..IndexOptions::default()
+        };
+        let words = tokenize("東京のrust勉強会。", &HashSet::new(), &options);
+        let expected: HashSet<String> = ["東京", "京の", "rust", "勉強", "強会"]
+            .iter()
+            .map(|t| t.to_string())
+            .collect();
+        assert_eq!(words, expected);
+    }
+
+    #[test]
+    fn test_normalize_unicode() {
+        let options = IndexOptions {
+            language: Language::Fr,
+            analyzer: Analyzer {
+                normalize_unicode: true,
+                ..Analyzer::default()
+            },
+            ..IndexOptions::default()
+        };
+        let words = tokenize("Un été au café", &stopwords(options.language), &options);
+        let expected: HashSet<String> = ["cafe"].iter().map(|t| t.to_string()).collect();
+        assert_eq!(words, expected);
+    }
+
+    #[test]
+    fn test_analyze() {
+        let options = IndexOptions {
+            language: Language::Fr,
+            min_word_len: 4,
+            analyzer: Analyzer {
+                normalize_unicode: true,
+                ..Analyzer::default()
+            },
+            ..IndexOptions::default()
+        };
+        let words = analyze("Un café au lac", &stopwords(options.language), &options);
+        assert_eq!(
+            words,
+            [
+                ("un".to_string(), Err(Dropped::Stopword)),
+                ("café".to_string(), Ok(vec!["cafe".to_string()])),
+                ("au".to_string(), Err(Dropped::Stopword)),
+                ("lac".to_string(), Err(Dropped::TooShort)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_related_terms() {
+        let post = |url: &str, words: &[&str]| {
+            let post_id: PostId = (url.to_string(), url.to_string(), Meta::new());
+            (post_id, words.iter().map(|word| word.to_string()).collect())
+        };
+        let words = vec![
+            post("/a", &["rust", "wasm", "search", "rust wasm"]),
+            post("/b", &["rust", "wasm", "async"]),
+            post("/c", &["rust", "async", "search"]),
+            post("/d", &["rust", "wasm"]),
+            post("/e", &["go"]),
+        ];
+        let related = related_terms(&words, 1);
+        assert_eq!(related.len(), 1);
+        // "wasm" is in 3 of the 4 posts with "rust", "async" and "search" in 2
+        assert_eq!(related["rust"], ["wasm", "async", "search"]);
+        assert!(related_terms(&words, 0).is_empty());
+    }
+
+    #[test]
+    fn test_html_content_format() {
+        let stopwords = HashSet::new();
+        let html =
+            r#"<div class="post"><a href="/x">Link&amp;text</a><script>var s;</script></div>"#;
+        let options = IndexOptions {
+            format: ContentFormat::Html,
+            ..IndexOptions::default()
+        };
+        let tokens = tokenize(html, &stopwords, &options);
+        let expected: HashSet<String> = ["link", "text"].iter().map(|t| t.to_string()).collect();
+        assert_eq!(tokens, expected);
+
+        let options = IndexOptions {
+            format: ContentFormat::Plain,
+            ..IndexOptions::default()
+        };
+        let tokens = tokenize(html, &stopwords, &options);
+        assert!(tokens.contains("div"));
+        assert!(tokens.contains("href"));
+    }
+
+    #[test]
+    fn test_add_excerpts() {
+        assert_eq!(
+            excerpt("# Title\n\nSome *long*   text", ContentFormat::Markdown, 10),
+            "Title Some…"
+        );
+        assert_eq!(
+            excerpt("short text", ContentFormat::Plain, 10),
+            "short text"
+        );
+        assert_eq!(excerpt("overlong", ContentFormat::Plain, 4), "over…");
+
+        let post = |meta: &[(&str, &str)], body: Option<&str>| Post {
+            title: "Title".to_string(),
+            url: "/".to_string(),
+            meta: meta
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect(),
+            body: body.map(str::to_string),
+        };
+        let mut posts = vec![
+            post(&[], Some("Body text")),
+            post(&[("date", "2020-01-01")], Some("Body text")),
+            post(&[], None),
+        ];
+        let added = add_excerpts(&mut posts, ContentFormat::Plain, 200);
+        assert_eq!(posts[0].meta["excerpt"], "Body text");
+        assert_eq!(posts[1].meta["date"], "2020-01-01");
+        assert_eq!(posts[1].meta["excerpt"], "Body text");
+        assert!(posts[2].meta.is_empty());
+        assert_eq!(added, 2 * ("excerpt".len() + "Body text".len()));
+    }
+
+    #[test]
+    fn test_redact() {
+        let mut posts = vec![Post {
+            title: "Ask admin@example.com".to_string(),
+            url: "/".to_string(),
+            meta: Meta::new(),
+            body: Some("Mail admin@example.com or ops@example.com on db1.internal".to_string()),
+        }];
+        let patterns = [
+            Regex::new(r"[\w.+-]+@[\w-]+\.[\w.]+").unwrap(),
+            Regex::new(r"\w+\.internal").unwrap(),
+            Regex::new("secret").unwrap(),
+        ];
+        assert_eq!(redact(&mut posts, &patterns), [3, 1, 0]);
+        assert_eq!(posts[0].title, "Ask  ");
+        assert_eq!(posts[0].body.as_deref(), Some("Mail   or   on  "));
+    }
+
+    #[test]
+    fn test_skip() {
+        let post = |url: &str, draft: &str| Post {
+            title: "Post".to_string(),
+            url: url.to_string(),
+            meta: [("draft".to_string(), draft.to_string())].into(),
+            body: None,
+        };
+        let mut posts = vec![
+            post("/rust/", "false"),
+            post("/tags/rust/", "false"),
+            post("/wasm/", "true"),
+            post("https://example.com/tags/wasm/", "false"),
+            post("https://example.com/about/", "false"),
+        ];
+        let urls = [Regex::new("^/tags/.*$").unwrap()];
+        let fields = [("draft".to_string(), "true".to_string())];
+        assert_eq!(skip(&mut posts, &urls, &fields), 3);
+        let urls: Vec<&str> = posts.iter().map(|post| post.url.as_str()).collect();
+        assert_eq!(urls, ["/rust/", "https://example.com/about/"]);
+    }
+
+    #[test]
+    fn test_dedup() {
+        let post = |title: &str, url: &str| Post {
+            title: title.to_string(),
+            url: url.to_string(),
+            meta: Meta::new(),
+            body: None,
+        };
+        let posts = vec![
+            post("First", "/a"),
+            post("Other", "/b"),
+            post("Second", "/a"),
+        ];
+        let titles = |posts: &Posts| -> Vec<String> {
+            posts.iter().map(|post| post.title.clone()).collect()
+        };
+
+        let mut first = posts.clone();
+        assert_eq!(dedup(&mut first, Duplicates::KeepFirst).unwrap(), ["/a"]);
+        assert_eq!(titles(&first), ["First", "Other"]);
+        let mut last = posts.clone();
+        assert_eq!(dedup(&mut last, Duplicates::KeepLast).unwrap(), ["/a"]);
+        assert_eq!(titles(&last), ["Second", "Other"]);
+        let mut error = posts;
+        assert!(dedup(&mut error, Duplicates::Error).is_err());
+    }
+
+    #[test]
+    fn test_facets() {
+        let meta = Meta::from([
+            ("category".to_string(), "rust".to_string()),
+            ("tags".to_string(), r#"["wasm","search"]"#.to_string()),
+            ("date".to_string(), "2020-01-01".to_string()),
+        ]);
+        let fields = ["category", "tags", "author"].map(String::from);
+        let facets = facets(&meta, &fields);
+        assert_eq!(facets.len(), 2);
+        assert_eq!(facets["category"], ["rust"]);
+        assert_eq!(facets["tags"], ["wasm", "search"]);
+    }
+
+    #[test]
+    fn test_parse_date() {
+        assert_eq!(parse_date("1970-01-02"), Some(86400));
+        assert_eq!(parse_date("1970-01-02T00:00:00Z"), Some(86400));
+        assert_eq!(parse_date("1970-01-02T01:00:00+01:00"), Some(86400));
+        assert_eq!(parse_date("1970-01-02 01:00:00 +0100"), Some(86400));
+        assert_eq!(parse_date("1970-01-02 00:00:00"), Some(86400));
+        assert_eq!(parse_date("yesterday"), None);
+    }
+
+    #[test]
+    fn test_resolve_aliases() {
+        let post = |url: &str, aliases: &str| Post {
+            title: url.to_string(),
+            url: url.to_string(),
+            meta: Meta::from([(ALIASES_KEY.to_string(), aliases.to_string())]),
+            body: None,
+        };
+        let mut posts = vec![
+            post("/blog/old", "[]"),
+            post("/blog/new", r#"["/blog/old", "/old"]"#),
+            post("/other", "/other-old"),
+            post("/mirror", "/old"),
+            post("/single", "/single"),
+        ];
+        resolve_aliases(&mut posts);
+        let canonical: Vec<Option<&str>> = posts
+            .iter()
+            .map(|post| post.meta.get(CANONICAL_KEY).map(String::as_str))
+            .collect();
+        assert_eq!(
+            canonical,
+            [
+                Some("/blog/new"),
+                Some("/blog/new"),
+                None,
+                Some("/blog/new"),
+                None
+            ]
+        );
+    }
+
+    #[test]
+    fn test_write_shards() {
+        let posts: Posts = (0..5)
+            .map(|i| Post {
+                title: format!("Post {}", i),
+                url: format!("/post-{}", i),
+                meta: Meta::new(),
+                body: Some("rust".to_string()),
+            })
+            .collect();
+        let dir = tempfile::tempdir().unwrap();
+        let options = IndexOptions {
+            shards: 2,
+            ..IndexOptions::default()
+        };
+        let paths = write_shards(posts, dir.path(), &options).unwrap();
+        assert_eq!(paths.len(), 2);
+        let sizes: Vec<usize> = paths
+            .iter()
+            .map(|path| read(path).unwrap().filters.len())
+            .collect();
+        assert_eq!(sizes, [3, 2]);
+        assert!(dir.path().join("storage.hash").exists());
+    }
+
+    #[test]
+    fn test_deterministic() {
+        let posts: Posts = ["charlie", "alpha", "bravo"]
+            .iter()
+            .map(|title| Post {
+                title: title.to_string(),
+                url: format!("/{}", title),
+                meta: Meta::new(),
+                body: Some(format!("{} rust wasm search", title)),
+            })
+            .collect();
+        let options = IndexOptions {
+            deterministic: true,
+            ..IndexOptions::default()
+        };
+        let bytes = build(posts.clone(), &options).unwrap();
+        assert_eq!(build(posts, &options).unwrap(), bytes);
+        let storage = Storage::from_bytes(&bytes).unwrap();
+        let urls: Vec<&str> = storage.documents().map(|post| post.1.as_str()).collect();
+        assert_eq!(urls, ["/alpha", "/bravo", "/charlie"]);
+    }
+
+    #[test]
+    fn test_target_size() {
+        let posts = || -> Posts {
+            ["alpha", "bravo", "charlie", "delta", "echo", "foxtrot"]
+                .iter()
+                .map(|title| Post {
+                    title: title.to_string(),
+                    url: format!("/{}", title),
+                    meta: Meta::new(),
+                    body: Some("rust".to_string()),
+                })
+                .collect()
+        };
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("storage");
+        write(posts(), &path, &IndexOptions::default()).unwrap();
+        let size = fs::metadata(&path).unwrap().len() as usize;
+
+        let options = IndexOptions {
+            target_size: Some(size - 1),
+            ..IndexOptions::default()
+        };
+        write(posts(), &path, &options).unwrap();
+        assert!((fs::metadata(&path).unwrap().len() as usize) < size);
+        let storage = read(&path).unwrap();
+        assert!(storage.search("rust".to_string(), 0).is_empty());
+        assert_eq!(storage.search("alpha".to_string(), 0).len(), 1);
+
+        let options = IndexOptions {
+            target_size: Some(1),
+            ..IndexOptions::default()
+        };
+        assert!(write(posts(), &path, &options).is_err());
+    }
+
+    #[test]
+    fn test_fit_by() {
+        let posts = || -> Posts {
+            ["alpha", "bravo", "charlie"]
+                .iter()
+                .map(|title| Post {
+                    title: title.to_string(),
+                    url: format!("/{}", title),
+                    meta: Meta::new(),
+                    body: Some(format!("rust wasm search {}typo", title)),
+                })
+                .collect()
+        };
+        let size = build(posts(), &IndexOptions::default()).unwrap().len();
+
+        let options = IndexOptions {
+            target_size: Some(size - 1),
+            fit_by: FitBy::RareWords,
+            ..IndexOptions::default()
+        };
+        let (bytes, cut) = build_words(posts(), &options).unwrap();
+        assert!(bytes.len() < size);
+        // Titles and typos are each found in one post, the body words in all of them
+        let Cut::Words(words) = cut else {
+            panic!("expected dropped words, got {:?}", cut)
+        };
+        assert!(!words.is_empty());
+        assert!(!words
+            .iter()
+            .any(|w| ["rust", "wasm", "search"].contains(&w.as_str())));
+
+        let options = IndexOptions {
+            target_size: Some(size - 1),
+            fit_by: FitBy::TruncateBodies,
+            ..IndexOptions::default()
+        };
+        let (bytes, cut) = build_words(posts(), &options).unwrap();
+        assert!(bytes.len() < size);
+        assert!(matches!(cut, Cut::Bodies { posts: 3, words } if words < 4));
+        let storage = Storage::from_bytes(&bytes).unwrap();
+        assert_eq!(storage.search("rust".to_string(), 0).len(), 3);
+        assert!(storage.search("alphatypo".to_string(), 0).is_empty());
     }
 }
@@ -0,0 +1,74 @@
+use anyhow::{Context, Error};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+/// Texts shown by the demo and the <tinysearch-modal> component, so they
+/// can be translated without editing the generated files
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Strings {
+    /// Placeholder of the search box
+    pub placeholder: String,
+    /// Shown when a query matches no posts
+    pub no_results: String,
+    /// Shown when a query matches a single post
+    pub one_result: String,
+    /// Shown when a query matches several posts, `{n}` is replaced by their number
+    pub results: String,
+}
+
+impl Default for Strings {
+    fn default() -> Self {
+        Strings {
+            placeholder: "Search".to_string(),
+            no_results: "No results".to_string(),
+            one_result: "1 result".to_string(),
+            results: "{n} results".to_string(),
+        }
+    }
+}
+
+impl Strings {
+    /// Read a JSON file with some or all of the strings, e.g.
+    /// `{ "placeholder": "Suchen", "no_results": "Keine Treffer" }`
+    pub fn read(path: &Path) -> Result<Self, Error> {
+        let raw = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read strings file {}", path.display()))?;
+        serde_json::from_str(&raw)
+            .with_context(|| format!("Failed to parse strings file {}", path.display()))
+    }
+
+    /// The strings as a JavaScript object literal, safe to embed in a `<script>` element
+    pub fn to_js(&self) -> Result<String, Error> {
+        Ok(serde_json::to_string(self)?.replace('<', "\\u003c"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_partial_strings() {
+        let strings: Strings =
+            serde_json::from_str(r#"{ "placeholder": "Suchen", "results": "{n} Treffer" }"#)
+                .unwrap();
+        assert_eq!(strings.placeholder, "Suchen");
+        assert_eq!(strings.results, "{n} Treffer");
+        assert_eq!(strings.no_results, Strings::default().no_results);
+        assert!(serde_json::from_str::<Strings>(r#"{ "placeholer": "Suchen" }"#).is_err());
+    }
+
+    #[test]
+    fn test_to_js() {
+        let strings = Strings {
+            placeholder: "</script>".to_string(),
+            ..Strings::default()
+        };
+        let js = strings.to_js().unwrap();
+        assert!(!js.contains("</script>"));
+        let parsed: Strings = serde_json::from_str(&js).unwrap();
+        assert_eq!(parsed, strings);
+    }
+}
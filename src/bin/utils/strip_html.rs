@@ -0,0 +1,29 @@
+use scraper::{ElementRef, Html, Node};
+
+/// Elements whose text never belongs into the index
+const SKIPPED_ELEMENTS: &[&str] = &["script", "style", "noscript", "nav", "header", "footer"];
+
+/// Remove all HTML markup from a string, keeping only the visible text.
+/// Tags, attributes, comments, and the contents of scripts, styles, and
+/// navigation elements are dropped; entities are decoded.
+pub fn strip_html(html: &str) -> String {
+    text(Html::parse_fragment(html).root_element())
+}
+
+/// The visible text of an element, see [`strip_html`]
+pub fn text(element: ElementRef) -> String {
+    let mut text = String::new();
+    for node in element.descendants() {
+        if let Node::Text(t) = node.value() {
+            let skipped = node
+                .ancestors()
+                .filter_map(|a| a.value().as_element())
+                .any(|e| SKIPPED_ELEMENTS.contains(&e.name()));
+            if !skipped {
+                text.push_str(t);
+                text.push(' ');
+            }
+        }
+    }
+    text
+}
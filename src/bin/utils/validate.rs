@@ -0,0 +1,211 @@
+use anyhow::{Context, Error};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// Fields of a post that tinysearch reads, see `index::Post`
+const FIELDS: [&str; 4] = ["title", "url", "body", "meta"];
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    /// The posts can't be indexed
+    Error,
+    /// The posts can be indexed, but probably not as intended
+    Warning,
+}
+
+/// A problem with a post of the input
+#[derive(Serialize, Debug, PartialEq, Eq)]
+pub struct Issue {
+    /// Position of the post in the input, starting at 0
+    pub post: usize,
+    pub url: Option<String>,
+    pub severity: Severity,
+    /// Stable name of the problem for scripts, e.g. `missing_url`
+    pub kind: &'static str,
+    pub message: String,
+}
+
+/// Issues found in the posts of an input file
+#[derive(Serialize, Debug)]
+pub struct Report {
+    pub posts: usize,
+    pub errors: usize,
+    pub warnings: usize,
+    pub issues: Vec<Issue>,
+}
+
+/// Check the posts of a JSON array or of JSON Lines against what tinysearch reads,
+/// without building an index. Unlike reading the posts, this reports every problem
+/// instead of stopping at the first one.
+pub fn validate(raw: &str) -> Result<Report, Error> {
+    let posts: Vec<Value> = match raw.trim_start().starts_with('[') {
+        true => serde_json::from_str(raw).context("The input isn't a JSON array")?,
+        false => serde_json::Deserializer::from_str(raw)
+            .into_iter()
+            .collect::<Result<_, _>>()
+            .context("The input isn't JSON Lines")?,
+    };
+    let mut issues = Vec::new();
+    // First post of each URL
+    let mut urls: HashMap<&str, usize> = HashMap::new();
+    for (i, post) in posts.iter().enumerate() {
+        let url = post.get("url").and_then(Value::as_str);
+        let mut issue = |severity, kind, message: String| {
+            issues.push(Issue {
+                post: i,
+                url: url.map(String::from),
+                severity,
+                kind,
+                message,
+            })
+        };
+        let Some(fields) = post.as_object() else {
+            issue(
+                Severity::Error,
+                "not_an_object",
+                "The post isn't an object".to_string(),
+            );
+            continue;
+        };
+        for field in ["title", "url"] {
+            match fields.get(field) {
+                None | Some(Value::Null) => issue(
+                    Severity::Error,
+                    if field == "url" {
+                        "missing_url"
+                    } else {
+                        "missing_title"
+                    },
+                    format!("The post has no {}", field),
+                ),
+                Some(Value::String(value)) if value.trim().is_empty() => issue(
+                    Severity::Warning,
+                    "empty_field",
+                    format!("The {} is empty", field),
+                ),
+                Some(Value::String(_)) => {}
+                Some(value) => issue(
+                    Severity::Error,
+                    "not_a_string",
+                    format!("The {} is {}, not a string", field, kind(value)),
+                ),
+            }
+        }
+        match fields.get("body") {
+            None | Some(Value::Null) => issue(
+                Severity::Warning,
+                "empty_field",
+                "The post has no body, only its title is searchable".to_string(),
+            ),
+            Some(Value::String(body)) if body.trim().is_empty() => issue(
+                Severity::Warning,
+                "empty_field",
+                "The body is empty, only the title is searchable".to_string(),
+            ),
+            Some(Value::String(_)) => {}
+            Some(value) => issue(
+                Severity::Error,
+                "not_a_string",
+                format!("The body is {}, not a string", kind(value)),
+            ),
+        }
+        match fields.get("meta") {
+            None | Some(Value::Null | Value::Object(_) | Value::String(_)) => {}
+            Some(value) => issue(
+                Severity::Error,
+                "invalid_meta",
+                format!("The meta field is {}, not an object", kind(value)),
+            ),
+        }
+        for field in fields
+            .keys()
+            .filter(|field| !FIELDS.contains(&field.as_str()))
+        {
+            issue(
+                Severity::Warning,
+                "unknown_field",
+                format!(
+                    "The field {} isn't read, put it into meta to keep it",
+                    field
+                ),
+            );
+        }
+        if let Some(url) = url {
+            match urls.get(url) {
+                Some(first) => issue(
+                    Severity::Error,
+                    "duplicate_url",
+                    format!("Post {} has the same URL", first),
+                ),
+                None => {
+                    urls.insert(url, i);
+                }
+            }
+        }
+    }
+    let errors = issues
+        .iter()
+        .filter(|issue| issue.severity == Severity::Error)
+        .count();
+    Ok(Report {
+        posts: posts.len(),
+        errors,
+        warnings: issues.len() - errors,
+        issues,
+    })
+}
+
+/// Name of the JSON type of a value, for messages
+fn kind(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "a boolean",
+        Value::Number(_) => "a number",
+        Value::String(_) => "a string",
+        Value::Array(_) => "an array",
+        Value::Object(_) => "an object",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate() {
+        let raw = r#"[
+            {"title": "Rust", "url": "/rust", "body": "Fearless"},
+            {"title": 1, "url": "/rust", "content": "Misnamed"},
+            "post",
+            {"title": "Go", "body": ["list"], "meta": 2}
+        ]"#;
+        let report = validate(raw).unwrap();
+        assert_eq!(report.posts, 4);
+        let kinds: Vec<(usize, &str)> = report
+            .issues
+            .iter()
+            .map(|issue| (issue.post, issue.kind))
+            .collect();
+        assert_eq!(
+            kinds,
+            [
+                (1, "not_a_string"),
+                (1, "empty_field"),
+                (1, "unknown_field"),
+                (1, "duplicate_url"),
+                (2, "not_an_object"),
+                (3, "missing_url"),
+                (3, "not_a_string"),
+                (3, "invalid_meta"),
+            ]
+        );
+        assert_eq!((report.errors, report.warnings), (6, 2));
+
+        let lines = "{\"title\": \"A\", \"url\": \"/a\", \"body\": \"a\"}\n\
+                     {\"title\": \"B\", \"url\": \"/b\", \"body\": \"b\"}\n";
+        let report = validate(lines).unwrap();
+        assert_eq!((report.posts, report.issues.len()), (2, 0));
+    }
+}
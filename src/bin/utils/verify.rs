@@ -0,0 +1,113 @@
+use anyhow::{anyhow, bail, Context, Error};
+use std::path::Path;
+use wasmtime::{Caller, Engine, Extern, Instance, Linker, Module, Store, Val};
+
+/// Number of results requested by the sample query
+const NUM_RESULTS: i32 = 5;
+
+/// Load a module generated by wasm-pack in wasmtime and run `query` through its
+/// `search` export, failing if the module traps.
+///
+/// There is no JavaScript host, so imports from the wasm-bindgen glue return default
+/// values. That's enough to run the search itself, but the results aren't checked.
+pub fn run(wasm_file: &Path, query: &str) -> Result<(), Error> {
+    let engine = Engine::default();
+    let module = Module::from_file(&engine, wasm_file)
+        .with_context(|| format!("Failed to load {}", wasm_file.display()))?;
+    let mut linker: Linker<()> = Linker::new(&engine);
+    for import in module.imports() {
+        // Errors raised by the module, e.g. panics, go through the throw import
+        if let (true, Some(ty)) = (import.name().contains("throw"), import.ty().func()) {
+            linker.func_new(import.module(), import.name(), ty.clone(), throw)?;
+        }
+    }
+    linker.define_unknown_imports_as_default_values(&module)?;
+
+    let mut store = Store::new(&engine, ());
+    let instance = linker.instantiate(&mut store, &module)?;
+    if let Some(start) = instance.get_func(&mut store, "__wbindgen_start") {
+        start.call(&mut store, &[], &mut [])?;
+    }
+    let (ptr, len) = pass_string(&instance, &mut store, query)?;
+    let search = instance
+        .get_func(&mut store, "search")
+        .context("The module doesn't export a search function")?;
+    let mut results = vec![Val::I32(0); search.ty(&store).results().len()];
+    search
+        .call(
+            &mut store,
+            &[Val::I32(ptr), Val::I32(len), Val::I32(NUM_RESULTS)],
+            &mut results,
+        )
+        .with_context(|| format!("Searching for \"{}\" trapped", query))?;
+    Ok(())
+}
+
+/// Copy a string into the module's memory, like the wasm-bindgen glue does
+fn pass_string(instance: &Instance, store: &mut Store<()>, s: &str) -> Result<(i32, i32), Error> {
+    let malloc = instance
+        .get_func(&mut *store, "__wbindgen_malloc")
+        .context("The module doesn't export __wbindgen_malloc")?;
+    let memory = instance
+        .get_memory(&mut *store, "memory")
+        .context("The module doesn't export its memory")?;
+    let len = s.len() as i32;
+    // Newer versions of wasm-bindgen also pass the alignment
+    let args = [Val::I32(len), Val::I32(1)];
+    let num_params = malloc.ty(&*store).params().len();
+    let mut ptr = [Val::I32(0)];
+    malloc.call(&mut *store, &args[..num_params], &mut ptr)?;
+    let ptr = ptr[0]
+        .i32()
+        .context("Unexpected return type of __wbindgen_malloc")?;
+    memory.write(&mut *store, ptr as usize, s.as_bytes())?;
+    Ok((ptr, len))
+}
+
+fn throw(mut caller: Caller<'_, ()>, args: &[Val], _results: &mut [Val]) -> Result<(), Error> {
+    let message = match (args.first(), args.get(1), caller.get_export("memory")) {
+        (Some(Val::I32(ptr)), Some(Val::I32(len)), Some(Extern::Memory(memory))) => {
+            let mut bytes = vec![0; *len as usize];
+            memory.read(&caller, *ptr as usize, &mut bytes)?;
+            String::from_utf8_lossy(&bytes).into_owned()
+        }
+        _ => bail!("The module threw an error"),
+    };
+    Err(anyhow!("The module threw an error: {}", message))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn module(search_body: &str) -> tempfile::TempPath {
+        let wat = format!(
+            r#"(module
+                (import "wbg" "__wbindgen_string_new" (func $string_new (param i32 i32) (result i32)))
+                (import "wbg" "__wbindgen_throw" (func $throw (param i32 i32)))
+                (memory (export "memory") 1)
+                (data (i32.const 0) "boom")
+                (func (export "__wbindgen_malloc") (param i32 i32) (result i32) i32.const 16)
+                (func (export "search") (param i32 i32 i32) (result i32) {})
+            )"#,
+            search_body
+        );
+        // wasmtime accepts the text format as well
+        let file = tempfile::NamedTempFile::new().unwrap();
+        std::fs::write(file.path(), wat).unwrap();
+        file.into_temp_path()
+    }
+
+    #[test]
+    fn test_verify() {
+        let ok = module("local.get 0 local.get 1 call $string_new");
+        assert!(run(&ok, "rust").is_ok());
+
+        let traps = module("unreachable");
+        assert!(run(&traps, "rust").is_err());
+
+        let throws = module("i32.const 0 i32.const 4 call $throw i32.const 0");
+        let error = format!("{:#}", run(&throws, "rust").unwrap_err());
+        assert!(error.contains("boom"), "{}", error);
+    }
+}
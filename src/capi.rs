@@ -0,0 +1,162 @@
+//! C API for building and searching indexes from other languages, e.g. from static
+//! site generators written in Python or JavaScript, without running the CLI.
+//!
+//! Build it as a shared library with
+//! `cargo rustc --release --lib --features capi --crate-type cdylib`. The functions
+//! are declared in `include/tinysearch.h`. Strings are NUL-terminated UTF-8, and the
+//! ones returned by the library must be released with [`tinysearch_free`]. On errors,
+//! functions return -1 or a null pointer, and [`tinysearch_last_error`] tells why.
+
+use std::cell::RefCell;
+use std::ffi::{c_char, c_int, CStr, CString};
+use std::fs;
+use std::ptr;
+
+use serde::Deserialize;
+
+use crate::{Meta, PostId, SearchOptions, Storage};
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// A post as accepted by [`tinysearch_build_index`]. Unlike the CLI, the meta values
+/// have to be strings.
+#[derive(Deserialize)]
+struct Post {
+    title: String,
+    url: String,
+    #[serde(default)]
+    meta: Meta,
+    body: Option<String>,
+}
+
+fn build_index(posts_json: &str) -> Result<Vec<u8>, String> {
+    let posts: Vec<Post> = serde_json::from_str(posts_json).map_err(|e| e.to_string())?;
+    let storage = Storage::from_texts(posts.into_iter().map(|post| {
+        let body = post.body.unwrap_or_default();
+        ((post.title, post.url, post.meta), body)
+    }));
+    storage.to_bytes().map_err(|e| e.to_string())
+}
+
+fn search(storage_path: &str, query: &str, num_results: usize) -> Result<String, String> {
+    let bytes = fs::read(storage_path).map_err(|e| format!("{}: {}", storage_path, e))?;
+    let storage = Storage::from_bytes(&bytes).map_err(|e| e.to_string())?;
+    let results: Vec<(&PostId, f32)> =
+        storage.search_with_options(query.to_string(), num_results, &SearchOptions::default());
+    serde_json::to_string(&results).map_err(|e| e.to_string())
+}
+
+fn set_last_error(message: String) {
+    let message = CString::new(message).unwrap_or_default();
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(message));
+}
+
+/// Read a string argument, which must not be null
+unsafe fn read_str<'a>(s: *const c_char, name: &str) -> Result<&'a str, String> {
+    if s.is_null() {
+        return Err(format!("{} is null", name));
+    }
+    CStr::from_ptr(s)
+        .to_str()
+        .map_err(|_| format!("{} isn't valid UTF-8", name))
+}
+
+/// Build an index from a JSON array of posts like
+/// `[{"title": "...", "url": "...", "body": "...", "meta": {"date": "..."}}]`
+/// and write it to `storage_path`. Returns 0 on success and -1 on errors.
+///
+/// # Safety
+///
+/// Both arguments must be valid NUL-terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn tinysearch_build_index(
+    posts_json: *const c_char,
+    storage_path: *const c_char,
+) -> c_int {
+    let result = read_str(posts_json, "posts_json").and_then(|posts_json| {
+        let path = read_str(storage_path, "storage_path")?;
+        let bytes = build_index(posts_json)?;
+        fs::write(path, bytes).map_err(|e| format!("{}: {}", path, e))
+    });
+    match result {
+        Ok(()) => 0,
+        Err(message) => {
+            set_last_error(message);
+            -1
+        }
+    }
+}
+
+/// Search the index at `storage_path`, returning at most `num_results` results (all
+/// of them for 0) as a JSON array of `[[title, url, meta], score]`. Returns null on
+/// errors. The result must be released with [`tinysearch_free`].
+///
+/// # Safety
+///
+/// `storage_path` and `query` must be valid NUL-terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn tinysearch_search(
+    storage_path: *const c_char,
+    query: *const c_char,
+    num_results: usize,
+) -> *mut c_char {
+    let result = read_str(storage_path, "storage_path").and_then(|storage_path| {
+        let query = read_str(query, "query")?;
+        search(storage_path, query, num_results)
+    });
+    match result.and_then(|json| CString::new(json).map_err(|e| e.to_string())) {
+        Ok(json) => json.into_raw(),
+        Err(message) => {
+            set_last_error(message);
+            ptr::null_mut()
+        }
+    }
+}
+
+/// Release a string returned by the library. Null is ignored.
+///
+/// # Safety
+///
+/// `s` must be null or a string returned by the library that wasn't released yet.
+#[no_mangle]
+pub unsafe extern "C" fn tinysearch_free(s: *mut c_char) {
+    if !s.is_null() {
+        drop(CString::from_raw(s));
+    }
+}
+
+/// The message of the last error on this thread, or null. The string belongs to the
+/// library and is valid until the next call that fails.
+#[no_mangle]
+pub extern "C" fn tinysearch_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| last.borrow().as_ref().map_or(ptr::null(), |e| e.as_ptr()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_and_search() {
+        let path = std::env::temp_dir().join(format!("tinysearch-capi-{}", std::process::id()));
+        let path = CString::new(path.to_str().unwrap()).unwrap();
+        let posts =
+            CString::new(r#"[{"title": "Rust", "url": "/rust", "body": "Fearless concurrency!"}]"#)
+                .unwrap();
+        unsafe {
+            assert_eq!(tinysearch_build_index(posts.as_ptr(), path.as_ptr()), 0);
+            let query = CString::new("concurrency").unwrap();
+            let results = tinysearch_search(path.as_ptr(), query.as_ptr(), 10);
+            let json = CStr::from_ptr(results).to_str().unwrap().to_string();
+            tinysearch_free(results);
+            assert!(json.starts_with(r#"[[["Rust","/rust",{}],"#));
+
+            let invalid = CString::new("{").unwrap();
+            assert_eq!(tinysearch_build_index(invalid.as_ptr(), path.as_ptr()), -1);
+            assert!(!tinysearch_last_error().is_null());
+        }
+        fs::remove_file(path.to_str().unwrap()).unwrap();
+    }
+}
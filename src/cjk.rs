@@ -0,0 +1,61 @@
+//! Segmentation for Chinese, Japanese, and Korean text, which doesn't separate
+//! words with whitespace. Runs of CJK characters are split into overlapping
+//! character bigrams, so that a query matches if its bigrams occur in a post.
+
+fn is_cjk(c: char) -> bool {
+    matches!(c,
+        '\u{3040}'..='\u{30FF}' // Hiragana and Katakana
+        | '\u{3400}'..='\u{4DBF}' // CJK Unified Ideographs Extension A
+        | '\u{4E00}'..='\u{9FFF}' // CJK Unified Ideographs
+        | '\u{AC00}'..='\u{D7AF}' // Hangul Syllables
+        | '\u{F900}'..='\u{FAFF}' // CJK Compatibility Ideographs
+        | '\u{20000}'..='\u{2FA1F}' // Supplementary ideographs
+    )
+}
+
+/// Split a whitespace-free token into words.
+/// Other scripts are left as they are, e.g. `rust言語` becomes `rust` and `言語`.
+pub fn segment(token: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut run: Vec<char> = Vec::new();
+    let mut other = String::new();
+    for c in token.chars() {
+        if is_cjk(c) {
+            if !other.is_empty() {
+                words.push(std::mem::take(&mut other));
+            }
+            run.push(c);
+        } else {
+            bigrams(&run, &mut words);
+            run.clear();
+            other.push(c);
+        }
+    }
+    bigrams(&run, &mut words);
+    if !other.is_empty() {
+        words.push(other);
+    }
+    words
+}
+
+fn bigrams(run: &[char], words: &mut Vec<String>) {
+    match run.len() {
+        0 => {}
+        1 => words.push(run[0].to_string()),
+        _ => words.extend(run.windows(2).map(|pair| pair.iter().collect::<String>())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_segment() {
+        assert_eq!(segment("東京大学"), ["東京", "京大", "大学"]);
+        assert_eq!(segment("rust言語"), ["rust", "言語"]);
+        assert_eq!(segment("猫"), ["猫"]);
+        assert_eq!(segment("wasm"), ["wasm"]);
+        assert_eq!(segment("검색엔진"), ["검색", "색엔", "엔진"]);
+    }
+}
@@ -0,0 +1,110 @@
+use std::borrow::Cow;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::StorageError;
+
+/// Compression of the serialized storage. It's recorded in the storage header,
+/// so reading a storage detects it automatically.
+///
+/// Compressing requires the feature of the same name, and so does decompressing,
+/// which adds the decoder to the WASM module. It pays off for large indexes.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Compression {
+    #[default]
+    None,
+    /// Requires the `gzip` feature
+    Gzip,
+    /// Requires the `brotli` feature. Compresses better than gzip but has a larger decoder.
+    Brotli,
+}
+
+impl Compression {
+    pub(crate) fn to_byte(self) -> u8 {
+        match self {
+            Compression::None => 0,
+            Compression::Gzip => 1,
+            Compression::Brotli => 2,
+        }
+    }
+
+    /// Read the compression byte of a storage header, e.g. to describe a storage file
+    pub fn from_byte(byte: u8) -> Result<Self, StorageError> {
+        match byte {
+            0 => Ok(Compression::None),
+            1 => Ok(Compression::Gzip),
+            2 => Ok(Compression::Brotli),
+            _ => Err(StorageError::UnknownCompression(byte)),
+        }
+    }
+
+    pub(crate) fn compress(self, bytes: Vec<u8>) -> Result<Vec<u8>, StorageError> {
+        match self {
+            Compression::None => Ok(bytes),
+            #[cfg(feature = "gzip")]
+            Compression::Gzip => {
+                use std::io::Write;
+                let mut encoder =
+                    flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::best());
+                encoder.write_all(&bytes)?;
+                Ok(encoder.finish()?)
+            }
+            #[cfg(feature = "brotli")]
+            Compression::Brotli => {
+                let mut compressed = Vec::new();
+                let params = brotli::enc::BrotliEncoderParams {
+                    quality: 11,
+                    ..Default::default()
+                };
+                brotli::BrotliCompress(&mut &bytes[..], &mut compressed, &params)?;
+                Ok(compressed)
+            }
+            #[allow(unreachable_patterns)]
+            unsupported => Err(StorageError::UnsupportedCompression(unsupported)),
+        }
+    }
+
+    pub(crate) fn decompress(self, bytes: &[u8]) -> Result<Cow<'_, [u8]>, StorageError> {
+        match self {
+            Compression::None => Ok(Cow::Borrowed(bytes)),
+            #[cfg(feature = "gzip")]
+            Compression::Gzip => {
+                use std::io::Read;
+                let mut decompressed = Vec::new();
+                flate2::read::GzDecoder::new(bytes).read_to_end(&mut decompressed)?;
+                Ok(Cow::Owned(decompressed))
+            }
+            #[cfg(feature = "brotli")]
+            Compression::Brotli => {
+                let mut decompressed = Vec::new();
+                brotli::BrotliDecompress(&mut &bytes[..], &mut decompressed)?;
+                Ok(Cow::Owned(decompressed))
+            }
+            #[allow(unreachable_patterns)]
+            unsupported => Err(StorageError::UnsupportedCompression(unsupported)),
+        }
+    }
+}
+
+impl fmt::Display for Compression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Compression::None => "none",
+            Compression::Gzip => "gzip",
+            Compression::Brotli => "brotli",
+        })
+    }
+}
+
+impl FromStr for Compression {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(Compression::None),
+            "gzip" => Ok(Compression::Gzip),
+            "brotli" => Ok(Compression::Brotli),
+            _ => Err(format!("unknown compression: {}", s)),
+        }
+    }
+}
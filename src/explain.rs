@@ -0,0 +1,229 @@
+//! Why a post matches a query or doesn't, see [`Storage::explain`]
+
+use serde::Serialize;
+use std::fmt;
+
+use crate::{
+    bigram_hashes, bigram_points, body_points, calibrated, facet_points, freshness, matches_filter,
+    post_section, query_words, title_points, Fields, Match, PostId, SearchOptions, Storage, Term,
+};
+
+/// How a query term matches a field of a post
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum FieldMatch {
+    None,
+    /// A word within the typo distance of the term, see [`SearchOptions::fuzzy`]
+    Fuzzy,
+    Exact,
+}
+
+impl From<Match> for FieldMatch {
+    fn from(m: Match) -> Self {
+        match m {
+            Match::None => FieldMatch::None,
+            Match::Fuzzy => FieldMatch::Fuzzy,
+            Match::Exact => FieldMatch::Exact,
+        }
+    }
+}
+
+impl fmt::Display for FieldMatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            FieldMatch::None => "none",
+            FieldMatch::Fuzzy => "fuzzy",
+            FieldMatch::Exact => "exact",
+        })
+    }
+}
+
+/// Where a query term was found in a post
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct TermExplanation {
+    pub term: String,
+    pub title: FieldMatch,
+    /// Whether the filter of the post contains the term. Filters have false
+    /// positives, so a term may match a post that doesn't contain it.
+    pub body: FieldMatch,
+    /// Whether the term is in one of the facet fields among [`SearchOptions::fields`]
+    pub facets: bool,
+}
+
+/// How a post scores for a query, see [`Storage::explain`]. Points are in the unit of
+/// scores.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct Explanation<'a> {
+    pub post_id: &'a PostId,
+    pub terms: Vec<TermExplanation>,
+    pub title_points: f32,
+    pub body_points: f32,
+    /// Points for pairs of adjacent query words, see [`Storage::bigram_boost`]
+    pub bigram_points: f32,
+    pub facet_points: f32,
+    /// Number of query terms found in any field
+    pub matched: usize,
+    /// Factor of the points for the share of the query terms the post matched
+    pub coverage: f32,
+    /// Factor of the points for the date of the post, see [`SearchOptions::recency`]
+    pub recency: f32,
+    /// Score of the post, whether or not it's among the results
+    pub score: f32,
+    /// Position of the post among the results, starting at 1, if it's among them
+    pub rank: Option<usize>,
+    /// Number of posts the query returns
+    pub results: usize,
+    /// Why the post isn't among the results, if it isn't
+    pub reason: Option<&'static str>,
+}
+
+impl Storage {
+    /// Explain how the post with the given URL scores for a query and where it ranks,
+    /// e.g. to debug an unexpected ranking. Returns `None` if no post has the URL.
+    pub fn explain(
+        &self,
+        query: &str,
+        url: &str,
+        options: &SearchOptions,
+    ) -> Option<Explanation<'_>> {
+        let i = self
+            .filters
+            .iter()
+            .position(|(post_id, _)| post_id.1 == url)?;
+        let (post_id, filter) = &self.filters[i];
+        let mut index = self.index();
+        index.boosts = options.boosts(index.boosts);
+        let (parsed, search_words) = query_words(&index, query, options);
+        let key = options.hash_key.as_str();
+        let prepare = |word: &str| Term::new(word, key).fuzzy(index.typos, options.fuzzy, key);
+        let terms: Vec<Term> = search_words.iter().map(|word| prepare(word)).collect();
+        let fields = Fields::new(&options.fields);
+        let typos = index.typos.map(|typos| &typos.filters[i]);
+        let title_terms = index.analyzer.tokenize(&post_id.0);
+
+        let explained = terms
+            .iter()
+            .map(|term| TermExplanation {
+                term: term.word.clone(),
+                title: match fields.title {
+                    true => term.in_title(&title_terms).into(),
+                    false => FieldMatch::None,
+                },
+                body: match fields.body {
+                    true => term.in_body(filter, typos).into(),
+                    false => FieldMatch::None,
+                },
+                facets: fields.in_facets(term, &index, i),
+            })
+            .collect();
+        let (body, bigrams) = match fields.body {
+            true => (
+                body_points(&terms, filter, typos, &index.boosts),
+                bigram_points(
+                    &bigram_hashes(&index, &search_words, key),
+                    filter,
+                    index.bigram_boost,
+                ),
+            ),
+            false => (0, 0),
+        };
+        let title = match fields.title {
+            true => title_points(&title_terms, &terms, &index.boosts),
+            false => 0,
+        };
+        let facets = facet_points(&terms, &fields, &index, i, typos);
+        let matched = terms
+            .iter()
+            .filter(|term| fields.contains(term, &index, i, &title_terms))
+            .count();
+        let squared_terms = (terms.len() * terms.len()).max(1) as f32;
+        let date = index.dates.get(i).copied().flatten();
+        let recency = 1.0 + options.recency * freshness(date, index.date_range);
+        let points = body + bigrams + title + facets;
+        let score = calibrated(points, matched) as f32 / 2.0 / squared_terms * recency;
+
+        let results = self.search_with_options(query.to_string(), 0, options);
+        let rank = results.iter().position(|(result, _)| result.1 == url);
+        let reason = match rank {
+            Some(_) => None,
+            None if options.exclude.contains(url) => Some("its URL is excluded"),
+            None if !options.filter.is_empty()
+                && !matches_filter(index.facets.get(i), &options.filter) =>
+            {
+                Some("its facets don't match the filter")
+            }
+            None if !options.sections.is_empty()
+                && !options.sections.contains(post_section(post_id)) =>
+            {
+                Some("it isn't in one of the sections searched")
+            }
+            None if points == 0 => Some("it contains none of the query terms"),
+            None if parsed.as_ref().is_some_and(|parsed| {
+                !parsed.matches(|word| fields.contains(&prepare(word), &index, i, &title_terms))
+            }) =>
+            {
+                Some("it doesn't satisfy the advanced query")
+            }
+            None => Some(
+                "a better match with the same canonical URL or more posts of its type \
+                 (see the type limits) rank higher",
+            ),
+        };
+        Some(Explanation {
+            post_id,
+            terms: explained,
+            title_points: title as f32 / 2.0,
+            body_points: body as f32 / 2.0,
+            bigram_points: bigrams as f32 / 2.0,
+            facet_points: facets as f32 / 2.0,
+            matched,
+            coverage: (matched * matched) as f32 / squared_terms,
+            recency,
+            score,
+            rank: rank.map(|rank| rank + 1),
+            results: results.len(),
+            reason,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Filter, Meta};
+
+    fn post(title: &str, body: &[&str]) -> (PostId, Filter) {
+        let terms: Vec<String> = body.iter().map(|term| term.to_string()).collect();
+        (
+            (title.to_string(), format!("/{}", title), Meta::new()),
+            Filter::from(&terms),
+        )
+    }
+
+    #[test]
+    fn test_explain() {
+        let storage = Storage::from(vec![post("rust", &["rust", "wasm"]), post("go", &["go"])]);
+        let options = SearchOptions::default();
+        let explanation = storage.explain("rust wasm", "/rust", &options).unwrap();
+        assert_eq!(explanation.terms[0].title, FieldMatch::Exact);
+        assert_eq!(explanation.terms[1].title, FieldMatch::None);
+        assert_eq!(explanation.terms[1].body, FieldMatch::Exact);
+        assert_eq!(explanation.title_points, 3.0);
+        assert_eq!(explanation.body_points, 2.0);
+        assert_eq!(explanation.coverage, 1.0);
+        assert_eq!(explanation.rank, Some(1));
+        let results = storage.search_with_scores("rust wasm".into(), 0);
+        assert_eq!(explanation.score, results[0].1);
+
+        let explanation = storage.explain("rust wasm", "/go", &options).unwrap();
+        assert_eq!(explanation.rank, None);
+        assert_eq!(
+            explanation.reason,
+            Some("it contains none of the query terms")
+        );
+        let options = options.with_exclude("/rust");
+        let explanation = storage.explain("rust", "/rust", &options).unwrap();
+        assert_eq!(explanation.reason, Some("its URL is excluded"));
+        assert!(storage.explain("rust", "/missing", &options).is_none());
+    }
+}
@@ -0,0 +1,174 @@
+use feed_rs::model::{Entry, Text};
+use scraper::{Html, Node};
+
+use crate::{Meta, PostId};
+
+/// Elements whose text doesn't belong into titles and bodies
+const SKIPPED_ELEMENTS: &[&str] = &["script", "style"];
+
+/// Errors parsing a feed with [`parse_posts_from_feed`]
+#[derive(Debug)]
+pub struct FeedError(feed_rs::parser::ParseFeedError);
+
+impl std::fmt::Display for FeedError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "failed to parse feed: {}", self.0)
+    }
+}
+
+impl std::error::Error for FeedError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.0)
+    }
+}
+
+/// Parse an RSS 2.0 or Atom feed into posts for [`Storage::from_texts`](crate::Storage::from_texts).
+///
+/// Titles and bodies are plain text, with the markup of HTML titles and content
+/// removed. The body is the full content of an entry, or its summary for feeds that
+/// only carry one. The date, the language of the feed as `locale`, and the tags (as
+/// a JSON array) end up in the meta of each post. Entries without a link can't be
+/// shown as search results and are skipped.
+pub fn parse_posts_from_feed(raw: &[u8]) -> Result<Vec<(PostId, String)>, FeedError> {
+    let feed = feed_rs::parser::parse(raw).map_err(FeedError)?;
+    let locale = feed.language;
+    Ok(feed
+        .entries
+        .into_iter()
+        .filter_map(|entry| post(entry, locale.as_deref()))
+        .collect())
+}
+
+fn post(entry: Entry, locale: Option<&str>) -> Option<(PostId, String)> {
+    let url = entry
+        .links
+        .iter()
+        .find(|link| link.rel.as_deref().unwrap_or("alternate") == "alternate")
+        .or_else(|| entry.links.first())
+        .map(|link| link.href.clone());
+    let url = match url {
+        Some(url) => url,
+        None => {
+            log::warn!("Skipping feed entry {} without a link", entry.id);
+            return None;
+        }
+    };
+
+    let mut meta = Meta::new();
+    if let Some(date) = entry.published.or(entry.updated) {
+        meta.insert("date".into(), date.to_rfc3339());
+    }
+    if let Some(locale) = locale {
+        meta.insert("locale".into(), locale.into());
+    }
+    let tags: Vec<String> = entry.categories.into_iter().map(|c| c.term).collect();
+    if !tags.is_empty() {
+        meta.insert(
+            "tags".into(),
+            serde_json::to_string(&tags).unwrap_or_default(),
+        );
+    }
+
+    // Prefer the full content; many feeds only carry a summary though
+    let body = match entry.content.and_then(|content| {
+        let html = is_html(content.content_type.subty().as_ref());
+        content.body.map(|body| (body, html))
+    }) {
+        Some((body, true)) => html_text(&body),
+        Some((body, false)) => body,
+        None => entry.summary.map(text).unwrap_or_default(),
+    };
+
+    let title = entry.title.map(text).unwrap_or_default();
+    let title = title.split_whitespace().collect::<Vec<_>>().join(" ");
+    Some(((title, url, meta), body))
+}
+
+fn is_html(subtype: &str) -> bool {
+    matches!(subtype, "html" | "xhtml")
+}
+
+/// The content of a text construct, without markup if it's HTML
+fn text(text: Text) -> String {
+    match is_html(text.content_type.subty().as_ref()) {
+        true => html_text(&text.content),
+        false => text.content,
+    }
+}
+
+/// The visible text of an HTML fragment, with entities decoded
+fn html_text(html: &str) -> String {
+    let fragment = Html::parse_fragment(html);
+    let mut text = String::new();
+    for node in fragment.root_element().descendants() {
+        if let Node::Text(t) = node.value() {
+            let skipped = node
+                .ancestors()
+                .filter_map(|a| a.value().as_element())
+                .any(|e| SKIPPED_ELEMENTS.contains(&e.name()));
+            if !skipped {
+                text.push_str(t);
+                text.push(' ');
+            }
+        }
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rss() {
+        let rss = br#"<?xml version="1.0"?>
+            <rss version="2.0"><channel><title>Blog</title><language>de</language>
+            <item><title>RSS &amp; more</title><link>https://example.com/rss</link>
+            <pubDate>Tue, 10 Oct 2023 12:00:00 GMT</pubDate>
+            <category>rust</category><category>wasm</category>
+            <description>&lt;p&gt;Hello &lt;b&gt;feed&lt;/b&gt;&lt;/p&gt;</description></item>
+            <item><title>No link</title><description>Lost</description></item>
+            </channel></rss>"#;
+        let posts = parse_posts_from_feed(rss).unwrap();
+        assert_eq!(posts.len(), 1);
+        let ((title, url, meta), body) = &posts[0];
+        assert_eq!(title, "RSS & more");
+        assert_eq!(url, "https://example.com/rss");
+        assert_eq!(meta["date"], "2023-10-10T12:00:00+00:00");
+        assert_eq!(meta["locale"], "de");
+        assert_eq!(meta["tags"], r#"["rust","wasm"]"#);
+        assert_eq!(
+            body.split_whitespace().collect::<Vec<_>>(),
+            ["Hello", "feed"]
+        );
+    }
+
+    #[test]
+    fn test_atom() {
+        let atom = br#"<?xml version="1.0" encoding="utf-8"?>
+            <feed xmlns="http://www.w3.org/2005/Atom"><title>Blog</title><id>blog</id>
+            <updated>2023-10-11T08:00:00Z</updated>
+            <entry><id>1</id><title type="html">Atom &lt;b&gt;post&lt;/b&gt;</title>
+            <link rel="edit" href="https://example.com/edit/1"/>
+            <link rel="alternate" href="https://example.com/atom"/>
+            <updated>2023-10-11T08:00:00Z</updated>
+            <content type="html">&lt;p&gt;Full&lt;/p&gt;&lt;script&gt;x()&lt;/script&gt;</content>
+            <summary>Short</summary></entry>
+            <entry><id>2</id><title>Edit only</title>
+            <link rel="edit" href="https://example.com/edit/2"/>
+            <updated>2023-10-11T08:00:00Z</updated></entry>
+            </feed>"#;
+        let posts = parse_posts_from_feed(atom).unwrap();
+        assert_eq!(posts.len(), 2);
+        let ((title, url, meta), body) = &posts[0];
+        assert_eq!(title, "Atom post");
+        assert_eq!(url, "https://example.com/atom");
+        assert_eq!(meta["date"], "2023-10-11T08:00:00+00:00");
+        assert!(!meta.contains_key("locale"));
+        assert!(!meta.contains_key("tags"));
+        assert_eq!(body.trim(), "Full");
+        // Without an alternate link, the first link is used
+        assert_eq!(posts[1].0 .1, "https://example.com/edit/2");
+        assert_eq!(posts[1].1, "");
+    }
+}
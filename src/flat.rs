@@ -0,0 +1,171 @@
+use serde::de::{self, SeqAccess, Visitor};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::borrow::Cow;
+use std::cell::Cell;
+use std::fmt;
+use xorf::Xor8;
+
+thread_local! {
+    /// Body of the storage decoded by [`borrowing`], which fingerprints are borrowed
+    /// from instead of copied. Empty otherwise.
+    static BODY: Cell<&'static [u8]> = const { Cell::new(&[]) };
+}
+
+/// Decode with `decode`, borrowing the fingerprints of all filters read from `body`
+/// instead of copying them
+pub(crate) fn borrowing<T>(body: &'static [u8], decode: impl FnOnce(&'static [u8]) -> T) -> T {
+    let previous = BODY.with(|cell| cell.replace(body));
+    let decoded = decode(body);
+    BODY.with(|cell| cell.set(previous));
+    decoded
+}
+
+/// An `Xor8` filter whose fingerprints are either owned or borrowed from the bytes of
+/// a storage, so that the filters of an index embedded in the WASM module are queried
+/// in place. It has the same fields as `Xor8`, so both are serialized the same way.
+///
+/// Borrowed fingerprints may come from a memory map that only lives as long as its
+/// `SearchIndex`, despite the `'static` lifetime. So the filter must never be cloned
+/// or moved out of its storage, and must not implement `Clone`, which `mmap.rs`
+/// asserts.
+#[derive(Serialize, Deserialize)]
+pub(crate) struct FlatXor8 {
+    seed: u64,
+    block_length: usize,
+    #[serde(
+        serialize_with = "serialize_fingerprints",
+        deserialize_with = "deserialize_fingerprints"
+    )]
+    fingerprints: Cow<'static, [u8]>,
+}
+
+impl FlatXor8 {
+    /// Same as `Xor8::contains`
+    pub(crate) fn contains(&self, key: u64) -> bool {
+        let hash = mix64(key.wrapping_add(self.seed));
+        let fingerprint = (hash ^ (hash >> 32)) as u8;
+        let index = |block: u32| {
+            let rotated = hash.rotate_left(block * 21) as u32;
+            ((rotated as u64 * self.block_length as u64) >> 32) as usize
+                + block as usize * self.block_length
+        };
+        fingerprint
+            == self.fingerprints[index(0)]
+                ^ self.fingerprints[index(1)]
+                ^ self.fingerprints[index(2)]
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.fingerprints.len()
+    }
+
+    /// Whether the fingerprints point into the bytes of a storage
+    #[cfg(test)]
+    pub(crate) fn is_borrowed(&self) -> bool {
+        matches!(self.fingerprints, Cow::Borrowed(_))
+    }
+}
+
+impl From<Xor8> for FlatXor8 {
+    fn from(filter: Xor8) -> Self {
+        FlatXor8 {
+            seed: filter.seed,
+            block_length: filter.block_length,
+            fingerprints: Cow::Owned(filter.fingerprints.into_vec()),
+        }
+    }
+}
+
+/// The finalizer of MurmurHash3, as used by `xorf`
+fn mix64(mut k: u64) -> u64 {
+    k ^= k >> 33;
+    k = k.wrapping_mul(0xff51_afd7_ed55_8ccd);
+    k ^= k >> 33;
+    k = k.wrapping_mul(0xc4ce_b9fe_1a85_ec53);
+    k ^= k >> 33;
+    k
+}
+
+// Bincode writes bytes like the sequence of a `Box<[u8]>`, and JSON as an array of
+// numbers, so storage written with `Xor8` reads the same
+fn serialize_fingerprints<S: Serializer>(
+    fingerprints: &[u8],
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.serialize_bytes(fingerprints)
+}
+
+fn deserialize_fingerprints<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Cow<'static, [u8]>, D::Error> {
+    deserializer.deserialize_bytes(FingerprintsVisitor)
+}
+
+struct FingerprintsVisitor;
+
+impl<'de> Visitor<'de> for FingerprintsVisitor {
+    type Value = Cow<'static, [u8]>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("filter fingerprints")
+    }
+
+    fn visit_borrowed_bytes<E: de::Error>(self, bytes: &'de [u8]) -> Result<Self::Value, E> {
+        // Find the bytes in the storage body by their address. The range only matches
+        // if they are part of it.
+        let body = BODY.with(Cell::get);
+        let start = (bytes.as_ptr() as usize).wrapping_sub(body.as_ptr() as usize);
+        match start
+            .checked_add(bytes.len())
+            .and_then(|end| body.get(start..end))
+        {
+            Some(borrowed) => Ok(Cow::Borrowed(borrowed)),
+            None => self.visit_bytes(bytes),
+        }
+    }
+
+    fn visit_bytes<E: de::Error>(self, bytes: &[u8]) -> Result<Self::Value, E> {
+        Ok(Cow::Owned(bytes.to_vec()))
+    }
+
+    fn visit_byte_buf<E: de::Error>(self, bytes: Vec<u8>) -> Result<Self::Value, E> {
+        Ok(Cow::Owned(bytes))
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let mut bytes = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+        while let Some(byte) = seq.next_element()? {
+            bytes.push(byte);
+        }
+        Ok(Cow::Owned(bytes))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use xorf::Filter as _;
+
+    #[test]
+    fn test_contains_like_xor8() {
+        let keys: Vec<u64> = (0..500).map(|i| i * 7919).collect();
+        let filter = Xor8::from(&keys);
+        let bytes = bincode::serialize(&filter).unwrap();
+        let flat: FlatXor8 = bincode::deserialize(&bytes).unwrap();
+        assert_eq!(bytes, bincode::serialize(&flat).unwrap());
+        for key in 0..10_000 {
+            assert_eq!(flat.contains(key), filter.contains(&key), "{}", key);
+        }
+    }
+
+    #[test]
+    fn test_borrowing() {
+        let filter = Xor8::from(&[1, 2, 3][..]);
+        let bytes: &'static [u8] = bincode::serialize(&filter).unwrap().leak();
+        let flat: FlatXor8 = bincode::deserialize(bytes).unwrap();
+        assert!(!flat.is_borrowed());
+        let flat: FlatXor8 = borrowing(bytes, |body| bincode::deserialize(body).unwrap());
+        assert!(flat.is_borrowed());
+        assert!(flat.contains(2));
+    }
+}
@@ -0,0 +1,98 @@
+use std::collections::HashSet;
+
+use crate::{Analyzer, PostId, Storage};
+
+type PostTokenized<'a> = Box<dyn FnMut(&PostId, &HashSet<String>) -> bool + 'a>;
+type IndexBuilt<'a> = Box<dyn FnMut(&Storage) + 'a>;
+
+/// Callbacks that [`Storage::from_texts_with_hooks`] runs while building a storage,
+/// e.g. to log posts, collect statistics, or leave posts out
+#[derive(Default)]
+pub struct IndexHooks<'a> {
+    post_tokenized: Option<PostTokenized<'a>>,
+    index_built: Option<IndexBuilt<'a>>,
+}
+
+impl<'a> IndexHooks<'a> {
+    /// Call `hook` with the words of each post, including those of its title, once the
+    /// post is tokenized. Posts it returns false for are left out of the index.
+    pub fn on_post_tokenized(
+        mut self,
+        hook: impl FnMut(&PostId, &HashSet<String>) -> bool + 'a,
+    ) -> Self {
+        self.post_tokenized = Some(Box::new(hook));
+        self
+    }
+
+    /// Call `hook` with the storage once all posts are indexed
+    pub fn on_index_built(mut self, hook: impl FnMut(&Storage) + 'a) -> Self {
+        self.index_built = Some(Box::new(hook));
+        self
+    }
+}
+
+impl Storage {
+    /// Like [`Storage::from_texts_with`], but runs `hooks` along the way
+    pub fn from_texts_with_hooks(
+        posts: impl IntoIterator<Item = (PostId, String)>,
+        analyzer: Analyzer,
+        mut hooks: IndexHooks,
+    ) -> Self {
+        let words =
+            |text: &str| -> HashSet<String> { analyzer.tokenize(text).into_iter().collect() };
+        let mut token_sets = Vec::new();
+        for (post_id, text) in posts {
+            let mut post_words = words(&post_id.0);
+            post_words.extend(words(&text));
+            let keep = match &mut hooks.post_tokenized {
+                Some(hook) => hook(&post_id, &post_words),
+                None => true,
+            };
+            if keep {
+                token_sets.push((post_id, post_words));
+            }
+        }
+        let storage = Storage {
+            analyzer,
+            ..Storage::from_token_sets(token_sets)
+        };
+        if let Some(hook) = &mut hooks.index_built {
+            hook(&storage);
+        }
+        storage
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Meta;
+
+    #[test]
+    fn test_hooks() {
+        let posts = vec![
+            (
+                ("Rust".into(), "/rust".into(), Meta::new()),
+                "and wasm".into(),
+            ),
+            (
+                ("Draft".into(), "/draft".into(), Meta::new()),
+                "rust".into(),
+            ),
+        ];
+        let mut tokenized = Vec::new();
+        let mut built = 0;
+        let hooks = IndexHooks::default()
+            .on_post_tokenized(|post_id, words| {
+                tokenized.push((post_id.1.clone(), words.len()));
+                post_id.0 != "Draft"
+            })
+            .on_index_built(|storage| built = storage.filters.len());
+        let storage = Storage::from_texts_with_hooks(posts, Analyzer::default(), hooks);
+
+        assert_eq!(tokenized, [("/rust".into(), 3), ("/draft".into(), 2)]);
+        assert_eq!(built, 1);
+        assert_eq!(storage.search("rust".into(), 0)[0].1, "/rust");
+        assert_eq!(storage.search("rust".into(), 0).len(), 1);
+    }
+}
@@ -0,0 +1,83 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+macro_rules! stopwords {
+    ($language:literal) => {
+        include_str!(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/assets/stopwords/",
+            $language
+        ))
+    };
+}
+
+/// Language of the posts, which selects the stopwords left out of the index and out
+/// of queries. The lists are compiled in, so an index only records the languages.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum Language {
+    #[default]
+    En,
+    De,
+    Fr,
+    Es,
+    Pt,
+    It,
+    Nl,
+    Ru,
+}
+
+impl Language {
+    /// The stopwords of the language, lowercase like the words of the index
+    pub fn stopwords(self) -> impl Iterator<Item = &'static str> {
+        let words = match self {
+            Language::En => stopwords!("en"),
+            Language::De => stopwords!("de"),
+            Language::Fr => stopwords!("fr"),
+            Language::Es => stopwords!("es"),
+            Language::Pt => stopwords!("pt"),
+            Language::It => stopwords!("it"),
+            Language::Nl => stopwords!("nl"),
+            Language::Ru => stopwords!("ru"),
+        };
+        words.split_whitespace()
+    }
+
+    pub fn is_stopword(self, word: &str) -> bool {
+        self.stopwords().any(|stopword| stopword == word)
+    }
+}
+
+impl fmt::Display for Language {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Language::En => "en",
+            Language::De => "de",
+            Language::Fr => "fr",
+            Language::Es => "es",
+            Language::Pt => "pt",
+            Language::It => "it",
+            Language::Nl => "nl",
+            Language::Ru => "ru",
+        })
+    }
+}
+
+impl FromStr for Language {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "en" => Ok(Language::En),
+            "de" => Ok(Language::De),
+            "fr" => Ok(Language::Fr),
+            "es" => Ok(Language::Es),
+            "pt" => Ok(Language::Pt),
+            "it" => Ok(Language::It),
+            "nl" => Ok(Language::Nl),
+            "ru" => Ok(Language::Ru),
+            _ => Err(format!("unknown language: {}", s)),
+        }
+    }
+}
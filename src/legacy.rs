@@ -0,0 +1,381 @@
+//! Storage layouts of earlier versions.
+//!
+//! Before version 3 the meta field of a post was a single string, which is kept
+//! as the `meta` entry of the map that replaced it. Posts of storage files before
+//! version 4 have no facets, posts before version 5 have no dates, storage files
+//! before version 6 have no bigrams, those before version 7 no corpus hash, those
+//! before version 9 no languages, the analyzer of those before version 10 has no
+//! settings for digits and joiners, and those before version 11 have no related terms.
+//!
+//! Storage files written before files started with a header have no version to go
+//! by, so their layouts are tried from the newest to the oldest.
+
+use bincode::Error as BincodeError;
+use serde::Deserialize;
+
+use crate::{
+    Analyzer, Boosts, Facets, Filter, Filters, Language, Meta, Storage, TypeLimits, Typos,
+};
+
+/// Post id with the meta field as a single string
+type StringMetaPostId = (String, String, Option<String>);
+type StringMetaFilters = Vec<(StringMetaPostId, Filter)>;
+
+/// Key of the migrated meta string in [`Meta`]
+const STRING_META_KEY: &str = "meta";
+
+fn migrate(filters: StringMetaFilters) -> Filters {
+    filters
+        .into_iter()
+        .map(|((title, url, meta), filter)| {
+            let meta: Meta = meta
+                .map(|meta| (STRING_META_KEY.to_string(), meta))
+                .into_iter()
+                .collect();
+            ((title, url, meta), filter)
+        })
+        .collect()
+}
+
+/// Layout of version 10
+#[derive(Deserialize)]
+struct StorageWithoutRelated {
+    filters: Filters,
+    boosts: Boosts,
+    typos: Option<Typos>,
+    type_limits: TypeLimits,
+    analyzer: Analyzer,
+    facets: Vec<Facets>,
+    dates: Vec<Option<i64>>,
+    bigram_boost: usize,
+    corpus_hash: String,
+    languages: Vec<Language>,
+    min_word_len: usize,
+}
+
+/// Decode the body of a version 10 storage file
+pub(crate) fn decode_without_related(bytes: &[u8]) -> Result<Storage, BincodeError> {
+    let old: StorageWithoutRelated = bincode::deserialize(bytes)?;
+    Ok(Storage {
+        boosts: old.boosts,
+        typos: old.typos,
+        type_limits: old.type_limits,
+        analyzer: old.analyzer,
+        facets: old.facets,
+        dates: old.dates,
+        bigram_boost: old.bigram_boost,
+        corpus_hash: old.corpus_hash,
+        languages: old.languages,
+        min_word_len: old.min_word_len,
+        ..Storage::from(old.filters)
+    })
+}
+
+/// Settings of the analyzer before version 10
+#[derive(Deserialize)]
+#[cfg_attr(test, derive(serde::Serialize, Default))]
+pub(crate) struct AnalyzerWithoutJoiners {
+    cjk: bool,
+    normalize_unicode: bool,
+}
+
+impl From<AnalyzerWithoutJoiners> for Analyzer {
+    fn from(old: AnalyzerWithoutJoiners) -> Self {
+        Analyzer {
+            cjk: old.cjk,
+            normalize_unicode: old.normalize_unicode,
+            ..Analyzer::default()
+        }
+    }
+}
+
+/// Layout of version 9
+#[derive(Deserialize)]
+struct StorageWithoutJoiners {
+    filters: Filters,
+    boosts: Boosts,
+    typos: Option<Typos>,
+    type_limits: TypeLimits,
+    analyzer: AnalyzerWithoutJoiners,
+    facets: Vec<Facets>,
+    dates: Vec<Option<i64>>,
+    bigram_boost: usize,
+    corpus_hash: String,
+    languages: Vec<Language>,
+    min_word_len: usize,
+}
+
+/// Decode the body of a version 9 storage file
+pub(crate) fn decode_without_joiners(bytes: &[u8]) -> Result<Storage, BincodeError> {
+    let old: StorageWithoutJoiners = bincode::deserialize(bytes)?;
+    Ok(Storage {
+        boosts: old.boosts,
+        typos: old.typos,
+        type_limits: old.type_limits,
+        analyzer: old.analyzer.into(),
+        facets: old.facets,
+        dates: old.dates,
+        bigram_boost: old.bigram_boost,
+        corpus_hash: old.corpus_hash,
+        languages: old.languages,
+        min_word_len: old.min_word_len,
+        ..Storage::from(old.filters)
+    })
+}
+
+/// Layout of versions 7 and 8
+#[derive(Deserialize)]
+struct StorageWithoutStopwords {
+    filters: Filters,
+    boosts: Boosts,
+    typos: Option<Typos>,
+    type_limits: TypeLimits,
+    analyzer: AnalyzerWithoutJoiners,
+    facets: Vec<Facets>,
+    dates: Vec<Option<i64>>,
+    bigram_boost: usize,
+    corpus_hash: String,
+}
+
+/// Decode the body of a version 7 or 8 storage file
+pub(crate) fn decode_without_stopwords(bytes: &[u8]) -> Result<Storage, BincodeError> {
+    let old: StorageWithoutStopwords = bincode::deserialize(bytes)?;
+    Ok(Storage {
+        boosts: old.boosts,
+        typos: old.typos,
+        type_limits: old.type_limits,
+        analyzer: old.analyzer.into(),
+        facets: old.facets,
+        dates: old.dates,
+        bigram_boost: old.bigram_boost,
+        corpus_hash: old.corpus_hash,
+        ..Storage::from(old.filters)
+    })
+}
+
+/// Layout of version 6
+#[derive(Deserialize)]
+struct StorageWithoutCorpusHash {
+    filters: Filters,
+    boosts: Boosts,
+    typos: Option<Typos>,
+    type_limits: TypeLimits,
+    analyzer: AnalyzerWithoutJoiners,
+    facets: Vec<Facets>,
+    dates: Vec<Option<i64>>,
+    bigram_boost: usize,
+}
+
+/// Decode the body of a version 6 storage file
+pub(crate) fn decode_without_corpus_hash(bytes: &[u8]) -> Result<Storage, BincodeError> {
+    let old: StorageWithoutCorpusHash = bincode::deserialize(bytes)?;
+    Ok(Storage {
+        boosts: old.boosts,
+        typos: old.typos,
+        type_limits: old.type_limits,
+        analyzer: old.analyzer.into(),
+        facets: old.facets,
+        dates: old.dates,
+        bigram_boost: old.bigram_boost,
+        ..Storage::from(old.filters)
+    })
+}
+
+/// Layout of version 5
+#[derive(Deserialize)]
+struct StorageWithoutBigrams {
+    filters: Filters,
+    boosts: Boosts,
+    typos: Option<Typos>,
+    type_limits: TypeLimits,
+    analyzer: AnalyzerWithoutJoiners,
+    facets: Vec<Facets>,
+    dates: Vec<Option<i64>>,
+}
+
+/// Decode the body of a version 5 storage file
+pub(crate) fn decode_without_bigrams(bytes: &[u8]) -> Result<Storage, BincodeError> {
+    let old: StorageWithoutBigrams = bincode::deserialize(bytes)?;
+    Ok(Storage {
+        boosts: old.boosts,
+        typos: old.typos,
+        type_limits: old.type_limits,
+        analyzer: old.analyzer.into(),
+        facets: old.facets,
+        dates: old.dates,
+        ..Storage::from(old.filters)
+    })
+}
+
+/// Layout of version 4
+#[derive(Deserialize)]
+struct StorageWithoutDates {
+    filters: Filters,
+    boosts: Boosts,
+    typos: Option<Typos>,
+    type_limits: TypeLimits,
+    analyzer: AnalyzerWithoutJoiners,
+    facets: Vec<Facets>,
+}
+
+/// Decode the body of a version 4 storage file
+pub(crate) fn decode_without_dates(bytes: &[u8]) -> Result<Storage, BincodeError> {
+    let old: StorageWithoutDates = bincode::deserialize(bytes)?;
+    Ok(Storage {
+        boosts: old.boosts,
+        typos: old.typos,
+        type_limits: old.type_limits,
+        analyzer: old.analyzer.into(),
+        facets: old.facets,
+        ..Storage::from(old.filters)
+    })
+}
+
+/// Layout of version 3
+#[derive(Deserialize)]
+struct StorageWithoutFacets {
+    filters: Filters,
+    boosts: Boosts,
+    typos: Option<Typos>,
+    type_limits: TypeLimits,
+    analyzer: AnalyzerWithoutJoiners,
+}
+
+/// Decode the body of a version 3 storage file
+pub(crate) fn decode_without_facets(bytes: &[u8]) -> Result<Storage, BincodeError> {
+    let old: StorageWithoutFacets = bincode::deserialize(bytes)?;
+    Ok(Storage {
+        boosts: old.boosts,
+        typos: old.typos,
+        type_limits: old.type_limits,
+        analyzer: old.analyzer.into(),
+        ..Storage::from(old.filters)
+    })
+}
+
+/// Layout of versions 1 and 2, which is the same as the one of the last files
+/// without a header
+#[derive(Deserialize)]
+struct StorageWithStringMeta {
+    filters: StringMetaFilters,
+    boosts: Boosts,
+    typos: Option<Typos>,
+    type_limits: TypeLimits,
+    analyzer: AnalyzerWithoutJoiners,
+}
+
+/// Layout before the analyzer settings were stored
+#[derive(Deserialize)]
+struct StorageWithoutAnalyzer {
+    filters: StringMetaFilters,
+    boosts: Boosts,
+    typos: Option<Typos>,
+    type_limits: TypeLimits,
+}
+
+/// Layout before result limits per post type were added
+#[derive(Deserialize)]
+struct StorageWithoutTypeLimits {
+    filters: StringMetaFilters,
+    boosts: Boosts,
+    typos: Option<Typos>,
+}
+
+/// Layout before typo tolerance was added
+#[derive(Deserialize)]
+struct StorageWithoutTypos {
+    filters: StringMetaFilters,
+    boosts: Boosts,
+}
+
+/// Decode the body of a version 1 or 2 storage file
+pub(crate) fn decode_string_meta(bytes: &[u8]) -> Result<Storage, BincodeError> {
+    let old: StorageWithStringMeta = bincode::deserialize(bytes)?;
+    Ok(Storage {
+        boosts: old.boosts,
+        typos: old.typos,
+        type_limits: old.type_limits,
+        analyzer: old.analyzer.into(),
+        ..Storage::from(migrate(old.filters))
+    })
+}
+
+/// Decode a storage file without a header
+pub(crate) fn decode(bytes: &[u8]) -> Result<Storage, BincodeError> {
+    let e = match decode_string_meta(bytes) {
+        Ok(storage) => return Ok(storage),
+        Err(e) => e,
+    };
+    if let Ok(old) = bincode::deserialize::<StorageWithoutAnalyzer>(bytes) {
+        return Ok(Storage {
+            boosts: old.boosts,
+            typos: old.typos,
+            type_limits: old.type_limits,
+            ..Storage::from(migrate(old.filters))
+        });
+    }
+    if let Ok(old) = bincode::deserialize::<StorageWithoutTypeLimits>(bytes) {
+        return Ok(Storage {
+            boosts: old.boosts,
+            typos: old.typos,
+            ..Storage::from(migrate(old.filters))
+        });
+    }
+    if let Ok(StorageWithoutTypos { filters, boosts }) = bincode::deserialize(bytes) {
+        return Ok(Storage {
+            boosts,
+            ..Storage::from(migrate(filters))
+        });
+    }
+    // Storage files written before boosts were configurable only contain the filters
+    match bincode::deserialize::<StringMetaFilters>(bytes) {
+        Ok(filters) => Ok(Storage::from(migrate(filters))),
+        Err(_) => Err(e),
+    }
+}
+
+/// Encode posts matching "rust" in the layout of versions 1 and 2
+#[cfg(test)]
+pub(crate) fn encode_string_meta(posts: &[(&str, Option<&str>)]) -> Vec<u8> {
+    #[derive(serde::Serialize)]
+    struct Old {
+        filters: StringMetaFilters,
+        boosts: Boosts,
+        typos: Option<Typos>,
+        type_limits: TypeLimits,
+        analyzer: AnalyzerWithoutJoiners,
+    }
+    let old = Old {
+        filters: posts
+            .iter()
+            .map(|(title, meta)| {
+                let post_id = (title.to_string(), "/".to_string(), meta.map(str::to_string));
+                (post_id, Filter::from(&vec!["rust".to_string()]))
+            })
+            .collect(),
+        boosts: Boosts::default(),
+        typos: None,
+        type_limits: TypeLimits::new(),
+        analyzer: AnalyzerWithoutJoiners::default(),
+    };
+    bincode::serialize(&old).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_migrate_string_meta() {
+        let bytes = encode_string_meta(&[("rust", Some(r#"{"date":"2020"}"#)), ("go", None)]);
+        let storage = decode(&bytes).unwrap();
+        let metas: Vec<&Meta> = storage
+            .filters
+            .iter()
+            .map(|(post_id, _)| &post_id.2)
+            .collect();
+        assert_eq!(metas[0]["meta"], r#"{"date":"2020"}"#);
+        assert!(metas[1].is_empty());
+        assert_eq!(storage.search("rust".to_string(), 0).len(), 2);
+    }
+}
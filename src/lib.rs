@@ -1,83 +1,2624 @@
 use bincode::Error as BincodeError;
 use serde::{Deserialize, Serialize};
-use std::cmp::Reverse;
+use std::cmp::{Ordering, Reverse};
 use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, BTreeSet, BinaryHeap, HashMap, HashSet};
 use std::convert::From;
-use xorf::{Filter as XorfFilter, HashProxy, Xor8};
+use std::hash::{Hash, Hasher};
+use xorf::{Filter as XorfFilter, Xor16, Xor8};
+
+mod analyzer;
+#[cfg(feature = "capi")]
+pub mod capi;
+#[cfg(feature = "cjk")]
+pub mod cjk;
+mod compression;
+mod explain;
+#[cfg(feature = "feed")]
+mod feed;
+mod flat;
+mod hooks;
+mod language;
+mod legacy;
+#[cfg(feature = "mmap")]
+mod mmap;
+pub mod prelude;
+#[cfg(feature = "python")]
+mod python;
+mod query;
+
+pub use analyzer::{Analyzer, Joiners};
+pub use compression::Compression;
+pub use explain::{Explanation, FieldMatch, TermExplanation};
+#[cfg(feature = "feed")]
+pub use feed::{parse_posts_from_feed, FeedError};
+pub use hooks::IndexHooks;
+pub use language::Language;
+#[cfg(feature = "mmap")]
+pub use mmap::SearchIndex;
 
 type Title = String;
 type Url = String;
-type Meta = Option<String>;
+/// Metadata of a post, e.g. its date or tags, which is passed through to the results
+pub type Meta = BTreeMap<String, String>;
 pub type PostId = (Title, Url, Meta);
-pub type PostFilter = (PostId, HashProxy<String, DefaultHasher, Xor8>);
+pub type PostFilter = (PostId, Filter);
 pub type Filters = Vec<PostFilter>;
 
+/// Key of the [`Meta`] entry with the canonical URL of a post that is available
+/// under several URLs. Only the best match of posts with the same canonical URL
+/// is returned by a search.
+pub const CANONICAL_KEY: &str = "canonical";
+
+/// Key of the [`Meta`] entry with the section of a post, see [`post_section`]
+pub const SECTION_KEY: &str = "section";
+
+/// Key of the [`Meta`] entry with the plain text excerpt of a post, see
+/// [`SearchResult::highlights`]
+pub const EXCERPT_KEY: &str = "excerpt";
+
 #[derive(Serialize, Deserialize)]
 pub struct Storage {
     pub filters: Filters,
+    pub boosts: Boosts,
+    /// Only present if the index was built with typo tolerance
+    pub typos: Option<Typos>,
+    /// Default maximum number of results per post type, see [`SearchOptions::type_limits`]
+    pub type_limits: TypeLimits,
+    /// How the posts were tokenized, which is applied to queries as well
+    pub analyzer: Analyzer,
+    /// Facet values of each post in the same order as the filters, see
+    /// [`SearchOptions::filter`]. Empty if the index has no facet fields.
+    pub facets: Vec<Facets>,
+    /// Date of each post as a Unix timestamp in the same order as the filters, see
+    /// [`SearchOptions::sort`] and [`SearchOptions::recency`]. Empty if the index
+    /// has no date field.
+    pub dates: Vec<Option<i64>>,
+    /// Points for each pair of adjacent query words that are next to each other in
+    /// a post as well, in the same order. The pairs of words of the posts (see
+    /// [`bigram`]) are only indexed if this is greater than 0.
+    pub bigram_boost: usize,
+    /// Fingerprint of the posts the index was built from, e.g. to tell whether a
+    /// deployed index is stale. Empty if unknown.
+    pub corpus_hash: String,
+    /// Languages of the posts, whose stopwords, e.g. "the", are left out of the index.
+    /// Query words that are stopwords in all of them are left out as well, as they
+    /// would still match titles and false positives of the filters. Empty keeps all words.
+    pub languages: Vec<Language>,
+    /// Query words with fewer characters are left out, see
+    /// [`SearchOptions::min_word_len`]. 0 keeps all words.
+    pub min_word_len: usize,
+    /// Words found in the most posts along with the words that occur in the same posts
+    /// the most, best first, see [`Storage::related_queries`]. They're stored in plain
+    /// text. Empty unless the index was built with related terms.
+    pub related: BTreeMap<String, Vec<String>>,
 }
 
 impl From<Filters> for Storage {
     fn from(filters: Filters) -> Self {
-        Storage { filters }
+        Storage {
+            filters,
+            boosts: Boosts::default(),
+            typos: None,
+            type_limits: TypeLimits::new(),
+            analyzer: Analyzer::default(),
+            facets: Vec::new(),
+            dates: Vec::new(),
+            bigram_boost: 0,
+            corpus_hash: String::new(),
+            languages: Vec::new(),
+            min_word_len: 0,
+            related: BTreeMap::new(),
+        }
+    }
+}
+
+/// Maximum number of queries returned by [`Storage::related_queries`]
+pub const MAX_RELATED_QUERIES: usize = 5;
+
+/// First bytes of every storage file
+pub const STORAGE_MAGIC: [u8; 4] = *b"TNYS";
+
+/// Version of the storage layout, which follows the magic number.
+/// It must be increased with every change to the layout.
+///
+/// Version 2 added a byte for the [`Compression`] of the bincode body after the version.
+/// Version 3 replaced the meta string of posts with a [`Meta`] map; the string of older
+/// storage files is kept as its `meta` entry.
+/// Version 4 added the [`Facets`] of posts.
+/// Version 5 added the dates of posts.
+/// Version 6 added the [`Storage::bigram_boost`].
+/// Version 7 added the [`Storage::corpus_hash`].
+/// Version 8 stores each [`Filter`] as a plain `Xor8` over `u64` term hashes instead of a
+/// `HashProxy` over strings. The bytes are the same, so version 7 is read as is.
+/// Version 9 added the [`Storage::languages`] and [`Storage::min_word_len`].
+/// Version 10 added the [`Analyzer::digits`] and [`Analyzer::joiners`].
+/// Version 11 added the [`Storage::related`] terms.
+pub const STORAGE_VERSION: u16 = 11;
+
+/// Errors reading a storage file
+#[derive(Debug)]
+pub enum StorageError {
+    /// The storage was written by a different version of tinysearch.
+    /// Rebuild the index with the current version.
+    VersionMismatch { found: u16, expected: u16 },
+    /// The file ends within the header
+    Truncated,
+    /// The storage could not be decoded
+    Decode(BincodeError),
+    /// The compression isn't supported because its feature isn't enabled
+    UnsupportedCompression(Compression),
+    /// The header names a compression this version doesn't know
+    UnknownCompression(u8),
+    /// Opening the storage file (see [`Storage::open_mmap`]), compressing, or
+    /// decompressing failed
+    Io(std::io::Error),
+    /// The index was analyzed with a setting whose feature isn't enabled, so queries
+    /// wouldn't be analyzed the same way as the posts, see [`Analyzer::missing_feature`]
+    UnsupportedAnalyzer(&'static str),
+}
+
+impl std::fmt::Display for StorageError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StorageError::VersionMismatch { found, expected } => write!(
+                f,
+                "storage version {} is not supported, expected version {}; please rebuild the index",
+                found, expected
+            ),
+            StorageError::Truncated => write!(f, "storage file is truncated"),
+            StorageError::Decode(e) => write!(f, "failed to decode storage: {}", e),
+            StorageError::UnsupportedCompression(compression) => write!(
+                f,
+                "{} compression is not supported, enable the {} feature of tinysearch",
+                compression, compression
+            ),
+            StorageError::UnknownCompression(byte) => {
+                write!(f, "unknown storage compression {}", byte)
+            }
+            StorageError::Io(e) => write!(f, "failed to read storage: {}", e),
+            StorageError::UnsupportedAnalyzer(feature) => write!(
+                f,
+                "the index was built with the {} analyzer, enable the {} feature of tinysearch",
+                feature, feature
+            ),
+        }
+    }
+}
+
+impl std::error::Error for StorageError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            StorageError::Decode(e) => Some(e),
+            StorageError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<BincodeError> for StorageError {
+    fn from(e: BincodeError) -> Self {
+        StorageError::Decode(e)
+    }
+}
+
+impl From<std::io::Error> for StorageError {
+    fn from(e: std::io::Error) -> Self {
+        StorageError::Io(e)
+    }
+}
+
+/// The words of each post along with their deletion variants, as used by SymSpell.
+///
+/// Two words are considered a typo of each other if deleting up to `max_distance`
+/// characters from each of them yields the same string. This covers all insertions,
+/// deletions, substitutions, and transpositions of a single character at a distance of 1.
+///
+/// Fuzzy matching checks many variants per query word, so these filters use 16 bit
+/// fingerprints to keep false positives rare.
+#[derive(Serialize, Deserialize)]
+pub struct Typos {
+    pub max_distance: usize,
+    /// One filter per post, in the same order as [`Storage::filters`]
+    pub filters: Vec<Xor16>,
+}
+
+impl Typos {
+    /// Build the filter of a post from its words
+    pub fn filter(words: &[String], max_distance: usize) -> Xor16 {
+        Self::filter_with_key(words, max_distance, "")
+    }
+
+    /// Like [`Typos::filter`], but hashes the words with a secret key, see
+    /// [`hash_term_with_key`]
+    pub fn filter_with_key(words: &[String], max_distance: usize, key: &str) -> Xor16 {
+        let mut hashes: Vec<u64> = words
+            .iter()
+            .flat_map(|word| deletions(word, max_distance))
+            .chain(words.iter().cloned())
+            .map(|word| hash_term_with_key(&word, key))
+            .collect();
+        hashes.sort_unstable();
+        hashes.dedup();
+        Xor16::from(hashes)
+    }
+}
+
+/// Words shorter than this are never matched fuzzily,
+/// as too many other words are only an edit or two away from them
+pub const MIN_FUZZY_WORD_LEN: usize = 4;
+
+/// All distinct strings that result from deleting 1 to `max_distance` characters from `word`.
+/// Words shorter than [`MIN_FUZZY_WORD_LEN`] have no variants.
+pub fn deletions(word: &str, max_distance: usize) -> Vec<String> {
+    let mut variants: Vec<String> = Vec::new();
+    if word.chars().count() < MIN_FUZZY_WORD_LEN {
+        return variants;
+    }
+    let mut seen: HashSet<String> = HashSet::new();
+    let mut current: Vec<String> = vec![word.to_string()];
+    for _ in 0..max_distance {
+        let mut next = Vec::new();
+        for word in &current {
+            let chars: Vec<char> = word.chars().collect();
+            for i in 0..chars.len() {
+                let variant: String = chars[..i].iter().chain(&chars[i + 1..]).collect();
+                if !variant.is_empty() && seen.insert(variant.clone()) {
+                    next.push(variant);
+                }
+            }
+        }
+        variants.extend(next.iter().cloned());
+        current = next;
+    }
+    variants
+}
+
+/// Weight of a query term match per field of a post
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Boosts {
+    pub title: usize,
+    pub body: usize,
+    /// Weight of query terms found only in facet fields of a post, like its tags
+    pub facets: usize,
+}
+
+impl Default for Boosts {
+    fn default() -> Self {
+        Boosts {
+            title: 3,
+            body: 1,
+            facets: 1,
+        }
+    }
+}
+
+/// How a query string is interpreted
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum QueryMode {
+    /// Every word of the query is optional; posts matching more words rank higher
+    #[default]
+    Simple,
+    /// Supports `"exact phrases"`, `term1 AND term2`, `term1 OR term2`, and `-excluded`.
+    ///
+    /// As the index doesn't store word positions, a phrase matches every post
+    /// that contains all of its words.
+    Advanced,
+}
+
+impl std::str::FromStr for QueryMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "simple" => Ok(QueryMode::Simple),
+            "advanced" => Ok(QueryMode::Advanced),
+            _ => Err(format!("Unknown query mode: {}", s)),
+        }
     }
 }
 
+/// Order of the results of a search
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Sort {
+    /// Best matches first
+    #[default]
+    Relevance,
+    /// Newest posts first, see [`Storage::dates`]. Posts with the same date are
+    /// ordered by relevance, and posts without a date come last.
+    Date,
+}
+
+impl std::str::FromStr for Sort {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "relevance" => Ok(Sort::Relevance),
+            "date" => Ok(Sort::Date),
+            _ => Err(format!("Unknown sort order: {}", s)),
+        }
+    }
+}
+
+/// Order of results with the same score, or the same date with [`Sort::Date`]
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TieBreak {
+    /// The order the posts were indexed in, which is by URL for indexes built with
+    /// `--deterministic`
+    #[default]
+    Index,
+    Title,
+    Url,
+    /// Newest posts first, posts without a date last
+    Date,
+}
+
+impl std::str::FromStr for TieBreak {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "index" => Ok(TieBreak::Index),
+            "title" => Ok(TieBreak::Title),
+            "url" => Ok(TieBreak::Url),
+            "date" => Ok(TieBreak::Date),
+            _ => Err(format!("Unknown tie-break: {}", s)),
+        }
+    }
+}
+
+/// A result of [`Storage::search_with_details`]
+#[derive(Serialize, Debug, PartialEq)]
+pub struct SearchResult<'a> {
+    pub post_id: &'a PostId,
+    pub score: f32,
+    /// The query terms found in the post, after analysis (e.g. lowercased).
+    /// Terms matched as a typo are included.
+    pub matched_terms: Vec<String>,
+    /// Where the matched terms are in the title (`title`) and in the excerpt
+    /// ([`EXCERPT_KEY`]) of the post, so that they can be highlighted without
+    /// rendering HTML. Fields without matches are left out.
+    pub highlights: Highlights,
+}
+
+/// Start and end byte offsets of the words of each field that match the query, see
+/// [`SearchResult::highlights`]
+pub type Highlights = BTreeMap<String, Vec<(usize, usize)>>;
+
+/// A result of [`search_indexes`]
+#[derive(Serialize, Debug, PartialEq)]
+pub struct LabeledResult<'a> {
+    /// Name of the index the post was found in
+    pub index: &'a str,
+    pub post_id: &'a PostId,
+    pub score: f32,
+}
+
+/// Results of [`Storage::search_with_deadline`]
+#[derive(Serialize, Debug)]
+pub struct SearchResults<'a> {
+    pub results: Vec<(&'a PostId, f32)>,
+    /// Whether the search ran out of time before all posts were scored
+    pub truncated: bool,
+}
+
+/// A page of results of [`Storage::search_paginated`]
+#[derive(Serialize, Debug, PartialEq)]
+pub struct Page<'a> {
+    /// Number of posts matching the query on all pages
+    pub total: usize,
+    pub results: Vec<(&'a PostId, f32)>,
+}
+
+impl<'a> Page<'a> {
+    /// The `page`-th page of `per_page` of all `results`, counting pages from 1. A
+    /// `per_page` of 0 puts all results on every page.
+    pub fn of(results: Vec<(&'a PostId, f32)>, page: usize, per_page: usize) -> Self {
+        let total = results.len();
+        let results = match per_page {
+            0 => results,
+            _ => results
+                .into_iter()
+                .skip(page.saturating_sub(1).saturating_mul(per_page))
+                .take(per_page)
+                .collect(),
+        };
+        Page { total, results }
+    }
+}
+
+/// Summary of a [`Storage`], e.g. for evaluating an index in a notebook.
+/// It serializes to a flat map, so bindings can hand it out as a dictionary.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct StorageStats {
+    pub posts: usize,
+    /// Number of posts with a meta field
+    pub posts_with_meta: usize,
+    /// Size of the post filters, which hold one byte per fingerprint
+    pub filter_bytes: usize,
+    /// Size of the typo filters, which hold two bytes per fingerprint
+    pub typo_filter_bytes: usize,
+    /// Maximum edit distance of typos the storage supports
+    pub max_typo_distance: usize,
+    /// Number of posts per post type, see [`post_type`]
+    pub posts_per_type: BTreeMap<String, usize>,
+}
+
+/// Maximum number of results per post type
+pub type TypeLimits = BTreeMap<String, usize>;
+
+/// Values of the facet fields of a post, e.g. its `category` or its `tags`
+pub type Facets = BTreeMap<String, Vec<String>>;
+
+/// The type of a post is the first segment of its URL path,
+/// e.g. `blog` for `https://example.com/blog/hello/`
+pub fn post_type(url: &str) -> &str {
+    let path = match url.find("://") {
+        Some(i) => url[i + 3..].find('/').map_or("", |j| &url[i + 3 + j..]),
+        None => url,
+    };
+    path.trim_start_matches('/').split('/').next().unwrap_or("")
+}
+
+/// The section of a post, e.g. `guides` or `api`, which searches can be limited to
+/// with [`SearchOptions::sections`]: its [`SECTION_KEY`] meta entry, or else its
+/// [`post_type`]
+pub fn post_section(post_id: &PostId) -> &str {
+    match post_id.2.get(SECTION_KEY) {
+        Some(section) => section,
+        None => post_type(&post_id.1),
+    }
+}
+
+/// Query-time options for [`Storage::search_with_options`]
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct SearchOptions {
+    pub query_mode: QueryMode,
+    /// Maximum edit distance of typos, 0 disables fuzzy matching
+    pub fuzzy: usize,
+    /// Maximum number of results per [`post_type`], so that a section with many
+    /// matching posts doesn't crowd out the others. Limits given here take precedence
+    /// over the ones stored in the index.
+    pub type_limits: TypeLimits,
+    /// Only return posts whose facets contain the given value of each field,
+    /// e.g. `{ "category": "rust" }`. Posts without the field never match.
+    pub filter: BTreeMap<String, String>,
+    pub sort: Sort,
+    /// Order of posts that [`SearchOptions::sort`] ranks the same
+    pub tie_break: TieBreak,
+    /// Boost of newer posts: the score of the newest post of the index is multiplied
+    /// by `1 + recency`, older posts get a boost proportional to their date between
+    /// the oldest and the newest post. Posts without a date aren't boosted.
+    pub recency: f32,
+    /// Only match query words in these fields: `title`, `body` (the whole text of a
+    /// post, including its title), or facet fields like `tags`. Empty matches the
+    /// title and the body.
+    pub fields: Vec<String>,
+    /// Never return posts with these URLs, e.g. the page a "related posts" widget
+    /// is shown on. URLs are compared as they were indexed.
+    pub exclude: BTreeSet<String>,
+    /// Only return posts of these sections, see [`post_section`], e.g. for the
+    /// "Guides" and "API" tabs of a docs site. Empty returns posts of all sections.
+    pub sections: BTreeSet<String>,
+    /// Secret key the index was built with, if any. Its words are hashed along with
+    /// the key, so that the index can't be probed for words without it.
+    pub hash_key: String,
+    /// Weight of query words found in the title instead of the one the index was
+    /// built with (see [`Storage::boosts`]), e.g. for a "prioritize titles" toggle
+    pub title_boost: Option<usize>,
+    /// Weight of query words found in the body instead of the one the index was
+    /// built with
+    pub body_boost: Option<usize>,
+    /// Weight of query words found only in facet fields instead of the one the index
+    /// was built with
+    pub facet_boost: Option<usize>,
+    /// Leave out query words with fewer characters instead of the minimum the index
+    /// was built with (see [`Storage::min_word_len`])
+    pub min_word_len: Option<usize>,
+}
+
+impl SearchOptions {
+    pub fn with_query_mode(mut self, query_mode: QueryMode) -> Self {
+        self.query_mode = query_mode;
+        self
+    }
+
+    /// Also match words up to `max_distance` edits away from a query word, which count
+    /// half as much as exact matches. This needs an index built with typo tolerance
+    /// (see [`Typos`]), whose distance also caps `max_distance`.
+    pub fn with_fuzzy(mut self, max_distance: usize) -> Self {
+        self.fuzzy = max_distance;
+        self
+    }
+
+    /// Return at most `limit` posts of the given [`post_type`]
+    pub fn with_type_limit(mut self, post_type: &str, limit: usize) -> Self {
+        self.type_limits.insert(post_type.to_string(), limit);
+        self
+    }
+
+    /// Only return posts whose facet `field` has the given `value`
+    pub fn with_filter(mut self, field: &str, value: &str) -> Self {
+        self.filter.insert(field.to_string(), value.to_string());
+        self
+    }
+
+    pub fn with_sort(mut self, sort: Sort) -> Self {
+        self.sort = sort;
+        self
+    }
+
+    pub fn with_tie_break(mut self, tie_break: TieBreak) -> Self {
+        self.tie_break = tie_break;
+        self
+    }
+
+    /// Rank newer posts above older ones with similar scores, see [`SearchOptions::recency`]
+    pub fn with_recency(mut self, recency: f32) -> Self {
+        self.recency = recency;
+        self
+    }
+
+    /// Also match query words in `field`, see [`SearchOptions::fields`]
+    pub fn with_field(mut self, field: &str) -> Self {
+        self.fields.push(field.to_string());
+        self
+    }
+
+    /// Never return the post with the given `url`
+    pub fn with_exclude(mut self, url: &str) -> Self {
+        self.exclude.insert(url.to_string());
+        self
+    }
+
+    /// Only return posts of the given section, see [`SearchOptions::sections`]
+    pub fn with_section(mut self, section: &str) -> Self {
+        self.sections.insert(section.to_string());
+        self
+    }
+
+    /// Search an index built with a secret key, see [`SearchOptions::hash_key`]
+    pub fn with_hash_key(mut self, key: &str) -> Self {
+        self.hash_key = key.to_string();
+        self
+    }
+
+    /// Weigh query words found in the title with `boost`, see [`SearchOptions::title_boost`]
+    pub fn with_title_boost(mut self, boost: usize) -> Self {
+        self.title_boost = Some(boost);
+        self
+    }
+
+    /// Weigh query words found in the body with `boost`, see [`SearchOptions::body_boost`]
+    pub fn with_body_boost(mut self, boost: usize) -> Self {
+        self.body_boost = Some(boost);
+        self
+    }
+
+    /// Weigh query words found only in facet fields with `boost`, see
+    /// [`SearchOptions::facet_boost`]
+    pub fn with_facet_boost(mut self, boost: usize) -> Self {
+        self.facet_boost = Some(boost);
+        self
+    }
+
+    /// Leave out query words shorter than `min_word_len` characters, see
+    /// [`SearchOptions::min_word_len`]
+    pub fn with_min_word_len(mut self, min_word_len: usize) -> Self {
+        self.min_word_len = Some(min_word_len);
+        self
+    }
+
+    /// The boosts of an index with the overrides of these options
+    fn boosts(&self, built: Boosts) -> Boosts {
+        Boosts {
+            title: self.title_boost.unwrap_or(built.title),
+            body: self.body_boost.unwrap_or(built.body),
+            facets: self.facet_boost.unwrap_or(built.facets),
+        }
+    }
+}
+
+/// The fields query words are matched against, see [`SearchOptions::fields`]
+#[derive(Clone)]
+struct Fields {
+    title: bool,
+    body: bool,
+    facets: Vec<String>,
+}
+
+impl Fields {
+    fn new(fields: &[String]) -> Self {
+        if fields.is_empty() {
+            return Fields {
+                title: true,
+                body: true,
+                facets: Vec::new(),
+            };
+        }
+        Fields {
+            title: fields.iter().any(|field| field == "title"),
+            body: fields.iter().any(|field| field == "body"),
+            facets: fields
+                .iter()
+                .filter(|field| !matches!(field.as_str(), "title" | "body"))
+                .cloned()
+                .collect(),
+        }
+    }
+
+    /// Whether a term is one of the words of the facet fields of the `i`th post
+    fn in_facets(&self, term: &Term, index: &Index, i: usize) -> bool {
+        let Some(facets) = index.facets.get(i) else {
+            return false;
+        };
+        self.facets
+            .iter()
+            .filter_map(|field| facets.get(field))
+            .flatten()
+            .any(|value| index.analyzer.tokenize(value).contains(&term.word))
+    }
+
+    /// Whether a term is found in any of the fields of the `i`th post
+    fn contains(&self, term: &Term, index: &Index, i: usize, title_terms: &[String]) -> bool {
+        let typos = index.typos.map(|typos| &typos.filters[i]);
+        (self.title && term.in_title(title_terms) != Match::None)
+            || (self.body && term.in_body(&index.filters[i].1, typos) != Match::None)
+            || self.in_facets(term, index, i)
+    }
+}
+
+/// Oldest and newest date of the posts, if any post has a date
+fn date_range(dates: &[Option<i64>]) -> Option<(i64, i64)> {
+    dates
+        .iter()
+        .flatten()
+        .fold(None, |range, &date| match range {
+            Some((oldest, newest)) => Some((date.min(oldest), date.max(newest))),
+            None => Some((date, date)),
+        })
+}
+
+/// Position of a date between the oldest (0) and the newest (1) date of the index
+fn freshness(date: Option<i64>, range: Option<(i64, i64)>) -> f32 {
+    match (date, range) {
+        (Some(date), Some((oldest, newest))) if newest > oldest => {
+            (date - oldest) as f32 / (newest - oldest) as f32
+        }
+        (Some(_), Some(_)) => 1.0,
+        _ => 0.0,
+    }
+}
+
+/// Whether the facets of a post satisfy all constraints of [`SearchOptions::filter`]
+fn matches_filter(facets: Option<&Facets>, filter: &BTreeMap<String, String>) -> bool {
+    filter.iter().all(|(field, value)| {
+        facets
+            .and_then(|facets| facets.get(field))
+            .is_some_and(|values| values.contains(value))
+    })
+}
+
 pub trait Score {
     fn score(&self, terms: &[String]) -> usize;
 }
 
 // the score denotes the number of terms from the query that are contained in the
 // current filter
-impl Score for HashProxy<String, DefaultHasher, Xor8> {
+impl Score for Filter {
     fn score(&self, terms: &[String]) -> usize {
-        terms.iter().filter(|term| self.contains(term)).count()
+        terms
+            .iter()
+            .filter(|term| self.contains_hash(hash_term(term)))
+            .count()
+    }
+}
+
+/// The term under which a pair of adjacent words is indexed. Words never contain
+/// whitespace, so it can't be mistaken for a single word.
+pub fn bigram(first: &str, second: &str) -> String {
+    format!("{} {}", first, second)
+}
+
+/// Hash a term the same way it was hashed when the filters were built.
+pub fn hash_term(term: &str) -> u64 {
+    hash_term_with_key(term, "")
+}
+
+/// Hash a term mixed with a secret key, so that the filters can't be probed for
+/// words without knowing the key, see [`SearchOptions::hash_key`]. An empty key
+/// hashes like [`hash_term`].
+pub fn hash_term_with_key(term: &str, key: &str) -> u64 {
+    let mut hasher = DefaultHasher::default();
+    if !key.is_empty() {
+        key.hash(&mut hasher);
+    }
+    term.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Xor filter over the `u64` hashes of the terms of a post.
+///
+/// Terms are hashed once with [`hash_term`] or [`hash_term_with_key`], when the index
+/// is built and once per query, and looked up by their hash in the filter of every
+/// post. The serialized layout is the same as that of the
+/// `xorf::HashProxy<String, DefaultHasher, Xor8>` filters of storage versions before 8.
+#[derive(Serialize, Deserialize)]
+pub struct Filter {
+    filter: flat::FlatXor8,
+}
+
+impl Filter {
+    /// Check a term hash produced by [`hash_term`] or [`hash_term_with_key`]
+    pub fn contains_hash(&self, hash: u64) -> bool {
+        self.filter.contains(hash)
+    }
+
+    /// Build a filter over term hashes
+    pub fn from_hashes(hashes: &[u64]) -> Self {
+        Filter {
+            filter: Xor8::from(hashes).into(),
+        }
+    }
+
+    /// Build a filter over terms hashed with a secret key, see [`hash_term_with_key`]
+    pub fn with_key(terms: &[String], key: &str) -> Self {
+        let hashes: Vec<u64> = terms
+            .iter()
+            .map(|term| hash_term_with_key(term, key))
+            .collect();
+        Self::from_hashes(&hashes)
+    }
+}
+
+impl XorfFilter<u64> for Filter {
+    fn contains(&self, hash: &u64) -> bool {
+        self.contains_hash(*hash)
+    }
+
+    fn len(&self) -> usize {
+        self.filter.len()
+    }
+}
+
+impl From<&[String]> for Filter {
+    fn from(terms: &[String]) -> Self {
+        Self::with_key(terms, "")
+    }
+}
+
+impl From<&Vec<String>> for Filter {
+    fn from(terms: &Vec<String>) -> Self {
+        Self::from(terms.as_slice())
     }
 }
 
 impl Storage {
+    /// Build a storage from the words of each post, bypassing the tokenization of the
+    /// indexer, e.g. for corpora analyzed or stemmed by other tools. Queries are still
+    /// split and lowercased by the [`Analyzer`], so the words have to be in that form
+    /// to be found.
+    pub fn from_token_sets(posts: impl IntoIterator<Item = (PostId, HashSet<String>)>) -> Self {
+        let filters: Filters = posts
+            .into_iter()
+            .map(|(post_id, words)| {
+                let words: Vec<String> = words.into_iter().collect();
+                (post_id, Filter::from(&words))
+            })
+            .collect();
+        Storage::from(filters)
+    }
+
+    /// Build a storage from the text of each post, e.g. its body, for programs that
+    /// index posts themselves instead of running the CLI. Posts are split into words
+    /// like the CLI does, along with their titles, but stopwords are kept and the text
+    /// has to be plain, without HTML or Markdown.
+    pub fn from_texts(posts: impl IntoIterator<Item = (PostId, String)>) -> Self {
+        Self::from_texts_with(posts, Analyzer::default())
+    }
+
+    /// Like [`Storage::from_texts`], but splits the posts with `analyzer`, e.g. to keep
+    /// digits and hyphenated words. It's stored, so that queries are split the same way.
+    pub fn from_texts_with(
+        posts: impl IntoIterator<Item = (PostId, String)>,
+        analyzer: Analyzer,
+    ) -> Self {
+        Self::from_texts_with_hooks(posts, analyzer, IndexHooks::default())
+    }
+
+    /// Count the posts and measure the filters of the storage
+    pub fn stats(&self) -> StorageStats {
+        let mut posts_per_type = BTreeMap::new();
+        for (post_id, _) in &self.filters {
+            *posts_per_type
+                .entry(post_type(&post_id.1).to_string())
+                .or_default() += 1;
+        }
+        StorageStats {
+            posts: self.filters.len(),
+            posts_with_meta: self
+                .filters
+                .iter()
+                .filter(|(post_id, _)| !post_id.2.is_empty())
+                .count(),
+            filter_bytes: self.filters.iter().map(|(_, filter)| filter.len()).sum(),
+            typo_filter_bytes: self.typos.as_ref().map_or(0, |typos| {
+                typos.filters.iter().map(|filter| filter.len() * 2).sum()
+            }),
+            max_typo_distance: self.typos.as_ref().map_or(0, |typos| typos.max_distance),
+            posts_per_type,
+        }
+    }
+
+    /// Encode with a header of [`STORAGE_MAGIC`], [`STORAGE_VERSION`] (little endian),
+    /// and the [`Compression`] of the body, which is [`Compression::None`]
     pub fn to_bytes(&self) -> Result<Vec<u8>, BincodeError> {
-        let encoded: Vec<u8> = bincode::serialize(&self)?;
+        let mut encoded = Self::header(Compression::None);
+        bincode::serialize_into(&mut encoded, &self)?;
+        Ok(encoded)
+    }
+
+    /// Like [`Storage::to_bytes`], but compresses the body
+    pub fn to_compressed_bytes(&self, compression: Compression) -> Result<Vec<u8>, StorageError> {
+        let mut encoded = Self::header(compression);
+        encoded.extend(compression.compress(bincode::serialize(&self)?)?);
         Ok(encoded)
     }
 
-    pub fn from_bytes(bytes: &[u8]) -> Result<Self, BincodeError> {
-        let decoded: Filters = bincode::deserialize(bytes)?;
-        Ok(Storage { filters: decoded })
+    fn header(compression: Compression) -> Vec<u8> {
+        let mut header = STORAGE_MAGIC.to_vec();
+        header.extend(STORAGE_VERSION.to_le_bytes());
+        header.push(compression.to_byte());
+        header
+    }
+
+    /// Decode a storage written by [`Storage::to_bytes`] or [`Storage::to_compressed_bytes`].
+    /// Storage files written by versions of tinysearch before the header was introduced
+    /// are read as well. Fails if the index needs an analyzer feature that isn't enabled.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, StorageError> {
+        let storage = Self::decode(bytes)?;
+        match storage.analyzer.missing_feature() {
+            Some(feature) => Err(StorageError::UnsupportedAnalyzer(feature)),
+            None => Ok(storage),
+        }
+    }
+
+    /// Like [`Storage::from_bytes`], but the filters of the posts aren't copied. They
+    /// are queried in place, e.g. for an index embedded with `include_bytes!`, so that
+    /// loading it only has to decode the posts. This only applies to uncompressed
+    /// storage; compressed storage is decompressed and copied as usual.
+    pub fn from_static_bytes(bytes: &'static [u8]) -> Result<Self, StorageError> {
+        flat::borrowing(bytes, Self::from_bytes)
+    }
+
+    fn decode(bytes: &[u8]) -> Result<Self, StorageError> {
+        let rest = match bytes.strip_prefix(&STORAGE_MAGIC) {
+            Some(rest) => rest,
+            None => return Ok(legacy::decode(bytes)?),
+        };
+        let (version, rest) = match rest {
+            [low, high, rest @ ..] => (u16::from_le_bytes([*low, *high]), rest),
+            _ => return Err(StorageError::Truncated),
+        };
+        match (version, rest) {
+            // Version 1 didn't support compression
+            (1, body) => Ok(legacy::decode_string_meta(body)?),
+            (2, [compression, body @ ..]) => {
+                let body = Compression::from_byte(*compression)?.decompress(body)?;
+                Ok(legacy::decode_string_meta(&body)?)
+            }
+            (STORAGE_VERSION, [compression, body @ ..]) => {
+                let body = Compression::from_byte(*compression)?.decompress(body)?;
+                Ok(bincode::deserialize(&body)?)
+            }
+            (3, [compression, body @ ..]) => {
+                let body = Compression::from_byte(*compression)?.decompress(body)?;
+                Ok(legacy::decode_without_facets(&body)?)
+            }
+            (4, [compression, body @ ..]) => {
+                let body = Compression::from_byte(*compression)?.decompress(body)?;
+                Ok(legacy::decode_without_dates(&body)?)
+            }
+            (5, [compression, body @ ..]) => {
+                let body = Compression::from_byte(*compression)?.decompress(body)?;
+                Ok(legacy::decode_without_bigrams(&body)?)
+            }
+            (6, [compression, body @ ..]) => {
+                let body = Compression::from_byte(*compression)?.decompress(body)?;
+                Ok(legacy::decode_without_corpus_hash(&body)?)
+            }
+            // Version 7 only differs in the type of the filters, not in their bytes
+            (7 | 8, [compression, body @ ..]) => {
+                let body = Compression::from_byte(*compression)?.decompress(body)?;
+                Ok(legacy::decode_without_stopwords(&body)?)
+            }
+            (9, [compression, body @ ..]) => {
+                let body = Compression::from_byte(*compression)?.decompress(body)?;
+                Ok(legacy::decode_without_joiners(&body)?)
+            }
+            (10, [compression, body @ ..]) => {
+                let body = Compression::from_byte(*compression)?.decompress(body)?;
+                Ok(legacy::decode_without_related(&body)?)
+            }
+            (2..=STORAGE_VERSION, []) => Err(StorageError::Truncated),
+            (found, _) => Err(StorageError::VersionMismatch {
+                found,
+                expected: STORAGE_VERSION,
+            }),
+        }
+    }
+
+    /// Serialize to JSON, e.g. for inspecting an index or loading it outside of Rust
+    #[cfg(feature = "json")]
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string_pretty(self)
+    }
+
+    #[cfg(feature = "json")]
+    pub fn from_json(json: &str) -> Result<Self, serde_json::Error> {
+        let storage: Self = serde_json::from_str(json)?;
+        match storage.analyzer.missing_feature() {
+            Some(feature) => Err(serde::de::Error::custom(StorageError::UnsupportedAnalyzer(
+                feature,
+            ))),
+            None => Ok(storage),
+        }
+    }
+
+    /// Like [`search`], but applies the field boosts the index was built with
+    pub fn search(&self, query: String, num_results: usize) -> Vec<&PostId> {
+        self.search_with_scores(query, num_results)
+            .into_iter()
+            .map(|p| p.0)
+            .collect()
+    }
+
+    /// Like [`search_with_scores`], but applies the field boosts the index was built with
+    pub fn search_with_scores(&self, query: String, num_results: usize) -> Vec<(&PostId, f32)> {
+        self.search_with_options(query, num_results, &SearchOptions::default())
+    }
+
+    /// Search with the given options, returning posts along with their scores
+    pub fn search_with_options(
+        &self,
+        query: String,
+        num_results: usize,
+        options: &SearchOptions,
+    ) -> Vec<(&PostId, f32)> {
+        ranked(&self.index(), &query, num_results, options, &|| false)
+            .results()
+            .results
+    }
+
+    /// Like [`Storage::search_with_options`], but returns the `page`-th page of
+    /// `per_page` results, counting from 1, along with the number of all matches, e.g.
+    /// for "42 results, page 2 of 5"
+    pub fn search_paginated(
+        &self,
+        query: String,
+        page: usize,
+        per_page: usize,
+        options: &SearchOptions,
+    ) -> Page<'_> {
+        Page::of(self.search_with_options(query, 0, options), page, per_page)
+    }
+
+    /// Like [`Storage::search_with_options`], but also returns the query terms
+    /// each post matched, e.g. for highlighting them
+    pub fn search_with_details(
+        &self,
+        query: String,
+        num_results: usize,
+        options: &SearchOptions,
+    ) -> Vec<SearchResult<'_>> {
+        let index = self.index();
+        ranked(&index, &query, num_results, options, &|| false)
+            .details(std::slice::from_ref(&index))
+    }
+
+    /// Like [`Storage::search_with_options`], but stops scoring posts once `expired`
+    /// returns true, which is checked every few posts. The best matches among the posts
+    /// scored so far are returned, and the results are marked as truncated.
+    ///
+    /// This keeps the latency of searching very large indexes bounded, e.g. with a
+    /// closure comparing the current time against a deadline.
+    pub fn search_with_deadline(
+        &self,
+        query: String,
+        num_results: usize,
+        options: &SearchOptions,
+        expired: impl Fn() -> bool,
+    ) -> SearchResults<'_> {
+        ranked(&self.index(), &query, num_results, options, &expired).results()
+    }
+
+    /// Up to [`MAX_RELATED_QUERIES`] queries related to `query`, e.g. for "people also
+    /// searched for" links below the results. Each is the query along with a word that
+    /// often occurs in the same posts as its words (see [`Storage::related`]), best
+    /// first. Empty if the index has no related terms for any of the words.
+    pub fn related_queries(&self, query: &str) -> Vec<String> {
+        let (_, words) = query_words(&self.index(), query, &SearchOptions::default());
+        // Terms get more points the higher they rank for more of the query words
+        let mut scores: Vec<(&str, usize)> = Vec::new();
+        for related in words.iter().filter_map(|word| self.related.get(word)) {
+            for (rank, term) in related.iter().enumerate() {
+                if words.contains(term) {
+                    continue;
+                }
+                let points = related.len() - rank;
+                match scores.iter_mut().find(|(other, _)| other == term) {
+                    Some((_, score)) => *score += points,
+                    None => scores.push((term, points)),
+                }
+            }
+        }
+        // The sort is stable, so ties keep the order of the query words
+        scores.sort_by_key(|(_, score)| Reverse(*score));
+        let query = query.split_whitespace().collect::<Vec<_>>().join(" ");
+        scores
+            .into_iter()
+            .take(MAX_RELATED_QUERIES)
+            .map(|(term, _)| format!("{} {}", query, term))
+            .collect()
+    }
+
+    /// All posts in the order they were indexed, e.g. to list posts while the search
+    /// box is still empty
+    pub fn documents(&self) -> impl Iterator<Item = &PostId> {
+        self.filters.iter().map(|(post_id, _)| post_id)
+    }
+
+    fn index(&self) -> Index<'_, '_> {
+        Index {
+            filters: &self.filters,
+            boosts: self.boosts,
+            typos: self.typos.as_ref(),
+            type_limits: &self.type_limits,
+            analyzer: self.analyzer,
+            facets: &self.facets,
+            dates: &self.dates,
+            date_range: date_range(&self.dates),
+            bigram_boost: self.bigram_boost,
+            languages: &self.languages,
+            min_word_len: self.min_word_len,
+        }
+    }
+}
+
+// Scores are kept in half points (see [`Match`]) until the posts are ranked.
+// Post title score usually has a higher weight than post body
+fn title_points(title_terms: &[String], terms: &[Term], boosts: &Boosts) -> usize {
+    let matches: usize = terms
+        .iter()
+        .map(|term| term.in_title(title_terms) as usize)
+        .sum();
+    boosts.title * matches
+}
+
+fn body_points(terms: &[Term], filter: &Filter, typos: Option<&Xor16>, boosts: &Boosts) -> usize {
+    let matches: usize = terms
+        .iter()
+        .map(|term| term.in_body(filter, typos) as usize)
+        .sum();
+    boosts.body * matches
+}
+
+/// Points for the query words found in the facet fields among [`SearchOptions::fields`].
+/// Words of the body count only once, even if they are a tag as well.
+fn facet_points(
+    terms: &[Term],
+    fields: &Fields,
+    index: &Index,
+    i: usize,
+    typos: Option<&Xor16>,
+) -> usize {
+    if fields.facets.is_empty() {
+        return 0;
     }
+    let filter = &index.filters[i].1;
+    let in_facets = terms
+        .iter()
+        .filter(|term| !fields.body || term.in_body(filter, typos) == Match::None)
+        .filter(|term| fields.in_facets(term, index, i))
+        .count();
+    index.boosts.facets * in_facets * Match::Exact as usize
 }
 
-pub type Filter = HashProxy<String, DefaultHasher, Xor8>;
+/// Scale the points of a post by the square of the number of query terms it matched
+/// in any field, so that matching more of the query outweighs matching fewer terms
+/// in a field with a higher boost: a post with all of three query words in its
+/// body ranks above one with only one of them in its title. The scores are divided
+/// by the square of the number of query terms when the posts are ranked, which keeps
+/// them comparable between queries and unchanged for single words.
+fn calibrated(points: usize, matched: usize) -> usize {
+    points * matched * matched
+}
 
-const TITLE_WEIGHT: usize = 3;
+/// Hashes of the pairs of adjacent query words, if the index has them
+fn bigram_hashes(index: &Index, words: &[String], key: &str) -> Vec<u64> {
+    match index.bigram_boost {
+        0 => Vec::new(),
+        _ => words
+            .windows(2)
+            .map(|pair| hash_term_with_key(&bigram(&pair[0], &pair[1]), key))
+            .collect(),
+    }
+}
 
-// Wrapper around filter score, that also scores the post title
-// Post title score has a higher weight than post body
-fn score(title: &str, search_terms: &[String], filter: &Filter) -> usize {
-    let title_terms: Vec<String> = tokenize(title);
-    let title_score: usize = search_terms
+/// Points for the `bigrams` (hashes of pairs of adjacent query words) found in a post
+fn bigram_points(bigrams: &[u64], filter: &Filter, bigram_boost: usize) -> usize {
+    let matches = bigrams
         .iter()
-        .filter(|term| title_terms.contains(term))
+        .filter(|hash| filter.contains_hash(**hash))
         .count();
-    TITLE_WEIGHT * title_score + filter.score(search_terms)
+    bigram_boost * matches * Match::Exact as usize
 }
 
-fn tokenize(s: &str) -> Vec<String> {
-    s.to_lowercase()
-        .split_whitespace()
-        .filter(|&t| !t.trim().is_empty())
-        .map(String::from)
-        .collect()
+/// How well a query word matches a field, in half points
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Match {
+    None = 0,
+    Fuzzy = 1,
+    Exact = 2,
 }
+
+/// A query word, prepared for matching against the filters
+struct Term {
+    word: String,
+    hash: u64,
+    /// Deletion variants of the word and their hashes, if it gets matched fuzzily
+    variants: Vec<(String, u64)>,
+    max_distance: usize,
+}
+
+impl Term {
+    fn new(word: &str, key: &str) -> Self {
+        Term {
+            word: word.to_string(),
+            hash: hash_term_with_key(word, key),
+            variants: Vec::new(),
+            max_distance: 0,
+        }
+    }
+
+    fn fuzzy(mut self, typos: Option<&Typos>, max_distance: usize, key: &str) -> Self {
+        self.max_distance = match typos {
+            Some(typos) => max_distance.min(typos.max_distance),
+            None => 0,
+        };
+        self.variants = deletions(&self.word, self.max_distance)
+            .into_iter()
+            .map(|variant| {
+                let hash = hash_term_with_key(&variant, key);
+                (variant, hash)
+            })
+            .collect();
+        self
+    }
+
+    fn is_fuzzy(&self) -> bool {
+        self.max_distance > 0 && self.word.chars().count() >= MIN_FUZZY_WORD_LEN
+    }
+
+    fn in_title(&self, title_terms: &[String]) -> Match {
+        if title_terms.contains(&self.word) {
+            Match::Exact
+        } else if self.is_fuzzy() && title_terms.iter().any(|term| self.is_typo_of(term)) {
+            Match::Fuzzy
+        } else {
+            Match::None
+        }
+    }
+
+    fn is_typo_of(&self, word: &str) -> bool {
+        let word_variants = deletions(word, self.max_distance);
+        word_variants.contains(&self.word)
+            || self
+                .variants
+                .iter()
+                .any(|(variant, _)| variant == word || word_variants.contains(variant))
+    }
+
+    fn in_body(&self, filter: &Filter, typos: Option<&Xor16>) -> Match {
+        if filter.contains_hash(self.hash) {
+            return Match::Exact;
+        }
+        let typos = match typos {
+            Some(typos) if self.is_fuzzy() => typos,
+            _ => return Match::None,
+        };
+        // The query word is an indexed word or one of its variants,
+        // or one of its own variants is
+        let fuzzy = typos.contains(&self.hash)
+            || self.variants.iter().any(|(_, hash)| typos.contains(hash));
+        if fuzzy {
+            Match::Fuzzy
+        } else {
+            Match::None
+        }
+    }
+}
+
+/// Return up to `num_results` posts matching `query`, best matches first.
+/// A `num_results` of 0 returns all matching posts.
 pub fn search(filters: &'_ Filters, query: String, num_results: usize) -> Vec<&'_ PostId> {
-    let search_terms: Vec<String> = tokenize(&query);
-    let mut matches: Vec<(&PostId, usize)> = filters
+    search_with_scores(filters, query, num_results)
+        .into_iter()
+        .map(|p| p.0)
+        .collect()
+}
+
+/// Like [`search`], but also returns the relevance score of each post.
+///
+/// Every query term contained in a post adds the [`Boosts`] of the fields
+/// it was found in to the score.
+pub fn search_with_scores(
+    filters: &'_ Filters,
+    query: String,
+    num_results: usize,
+) -> Vec<(&'_ PostId, f32)> {
+    let index = Index {
+        filters,
+        boosts: Boosts::default(),
+        typos: None,
+        type_limits: &TypeLimits::new(),
+        analyzer: Analyzer::default(),
+        facets: &[],
+        dates: &[],
+        date_range: None,
+        bigram_boost: 0,
+        languages: &[],
+        min_word_len: 0,
+    };
+    ranked(
+        &index,
+        &query,
+        num_results,
+        &SearchOptions::default(),
+        &|| false,
+    )
+    .results()
+    .results
+}
+
+/// Keep at most the limit of results of each post type, with the limits of the query
+/// taking precedence over the defaults of the index. `matches` must be sorted.
+fn limit_types<S>(matches: &mut Vec<(&PostId, S)>, limits: &TypeLimits, defaults: &TypeLimits) {
+    if limits.is_empty() && defaults.is_empty() {
+        return;
+    }
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    matches.retain(|(post_id, _)| {
+        let post_type = post_type(&post_id.1);
+        let limit = limits.get(post_type).or_else(|| defaults.get(post_type));
+        let count = counts.entry(post_type).or_default();
+        *count += 1;
+        limit.is_none_or(|limit| *count <= *limit)
+    });
+}
+
+/// Keep only the first of the matches with the same [`CANONICAL_KEY`]
+fn dedup_canonical<S>(matches: &mut Vec<(&PostId, S)>) {
+    let mut seen: HashSet<&str> = HashSet::new();
+    matches.retain(|(post_id, _)| match post_id.2.get(CANONICAL_KEY) {
+        Some(canonical) => seen.insert(canonical),
+        None => true,
+    });
+}
+
+/// Search the shards of an index as if they were one storage. The shards must be built
+/// with the same settings from consecutive batches of posts and be given in order.
+///
+/// Shards are searched one after the other until `expired` returns true, after which
+/// the remaining shards are skipped and the results are marked as truncated.
+pub fn search_shards<'a>(
+    shards: &[&'a Storage],
+    query: String,
+    num_results: usize,
+    options: &SearchOptions,
+    expired: impl Fn() -> bool,
+) -> SearchResults<'a> {
+    merge_shards(shards, &query, num_results, options, &expired).results()
+}
+
+/// Like [`search_shards`], but also returns the query terms each post matched
+pub fn search_shards_with_details<'a>(
+    shards: &[&'a Storage],
+    query: String,
+    num_results: usize,
+    options: &SearchOptions,
+    expired: impl Fn() -> bool,
+) -> Vec<SearchResult<'a>> {
+    let ranking = merge_shards(shards, &query, num_results, options, &expired);
+    let indexes: Vec<Index> = shards.iter().map(|shard| shard.index()).collect();
+    ranking.details(&indexes)
+}
+
+/// Rank the posts of each shard until `expired` returns true, and merge them into
+/// the best `num_results`
+fn merge_shards<'a>(
+    shards: &[&'a Storage],
+    query: &str,
+    num_results: usize,
+    options: &SearchOptions,
+    expired: &dyn Fn() -> bool,
+) -> Ranking<'a> {
+    // The recency boost is relative to the dates of all shards, like in a single storage
+    let all_dates = shards
         .iter()
-        .map(|(post_id, filter)| (post_id, score(&post_id.0, &search_terms, filter)))
-        .filter(|(_post_id, score)| *score > 0)
+        .filter_map(|shard| date_range(&shard.dates))
+        .reduce(|(a, b), (c, d)| (a.min(c), b.max(d)));
+    let mut merged = Ranking {
+        hits: Vec::new(),
+        fields: Fields::new(&options.fields),
+        terms: Vec::new(),
+        truncated: false,
+    };
+    for (i, shard) in shards.iter().enumerate() {
+        if merged.truncated {
+            break;
+        }
+        let index = Index {
+            date_range: all_dates,
+            ..shard.index()
+        };
+        let ranking = ranked(&index, query, num_results, options, expired);
+        merged.hits.extend(
+            ranking
+                .hits
+                .into_iter()
+                .map(|(post_id, hit)| (post_id, Hit { shard: i, ..hit })),
+        );
+        merged.terms = ranking.terms;
+        merged.truncated = ranking.truncated || expired();
+    }
+    sort_hits(&mut merged.hits, options, |post_id| post_id);
+    dedup_canonical(&mut merged.hits);
+    if let Some(first) = shards.first() {
+        limit_types(&mut merged.hits, &options.type_limits, &first.type_limits);
+    }
+    if num_results > 0 {
+        merged.hits.truncate(num_results);
+    }
+    merged
+}
+
+/// Search several independent indexes, e.g. of the docs and the blog of a site, each
+/// given by its name and its shards. Their results are merged into the best
+/// `num_results` and labeled with the name of their index.
+pub fn search_indexes<'a>(
+    indexes: &[(&'a str, &[&'a Storage])],
+    query: String,
+    num_results: usize,
+    options: &SearchOptions,
+) -> Vec<LabeledResult<'a>> {
+    let mut hits = Vec::new();
+    for (name, shards) in indexes {
+        let ranking = merge_shards(shards, &query, num_results, options, &|| false);
+        hits.extend(
+            ranking
+                .hits
+                .into_iter()
+                .map(|(post_id, hit)| ((*name, post_id), hit)),
+        );
+    }
+    sort_hits(&mut hits, options, |(_, post_id)| post_id);
+    if num_results > 0 {
+        hits.truncate(num_results);
+    }
+    hits.into_iter()
+        .map(|((index, post_id), hit)| LabeledResult {
+            index,
+            post_id,
+            score: hit.score,
+        })
+        .collect()
+}
+
+/// Sort hits in the order of the options. The sort is stable, so earlier posts win
+/// the ties left by [`SearchOptions::tie_break`].
+fn sort_hits<T>(hits: &mut [(T, Hit)], options: &SearchOptions, post_id: impl Fn(&T) -> &PostId) {
+    hits.sort_by(|(a_post, a), (b_post, b)| {
+        let order = match options.sort {
+            Sort::Relevance => b.score.total_cmp(&a.score),
+            Sort::Date => b.date.cmp(&a.date).then(b.score.total_cmp(&a.score)),
+        };
+        order.then_with(|| match options.tie_break {
+            TieBreak::Index => Ordering::Equal,
+            TieBreak::Title => post_id(a_post).0.cmp(&post_id(b_post).0),
+            TieBreak::Url => post_id(a_post).1.cmp(&post_id(b_post).1),
+            TieBreak::Date => b.date.cmp(&a.date),
+        })
+    });
+}
+
+/// Everything of a [`Storage`] needed for ranking
+struct Index<'a, 'b> {
+    filters: &'a Filters,
+    boosts: Boosts,
+    typos: Option<&'b Typos>,
+    type_limits: &'b TypeLimits,
+    analyzer: Analyzer,
+    facets: &'b [Facets],
+    dates: &'b [Option<i64>],
+    /// Oldest and newest date, which may span more than `dates` when searching shards
+    date_range: Option<(i64, i64)>,
+    bigram_boost: usize,
+    languages: &'b [Language],
+    min_word_len: usize,
+}
+
+/// How many posts are scored between two checks whether the time budget is used up
+const BUDGET_CHECK_INTERVAL: usize = 64;
+
+/// A post found by [`ranked`]
+#[derive(Clone, Copy)]
+struct Hit {
+    /// Position of the post in the index
+    index: usize,
+    /// Position of the index among the shards searched by [`merge_shards`]
+    shard: usize,
+    /// Score including the recency boost
+    score: f32,
+    date: Option<i64>,
+}
+
+/// Posts found by [`ranked`], best matches first
+struct Ranking<'a> {
+    hits: Vec<(&'a PostId, Hit)>,
+    fields: Fields,
+    /// The query terms posts were matched against
+    terms: Vec<Term>,
+    truncated: bool,
+}
+
+impl<'a> Ranking<'a> {
+    fn results(self) -> SearchResults<'a> {
+        SearchResults {
+            results: self
+                .hits
+                .into_iter()
+                .map(|(post_id, hit)| (post_id, hit.score))
+                .collect(),
+            truncated: self.truncated,
+        }
+    }
+
+    /// The results along with their matched terms. `indexes` holds the index of each
+    /// shard the hits were found in, see [`Hit::shard`].
+    fn details(self, indexes: &[Index<'a, '_>]) -> Vec<SearchResult<'a>> {
+        let terms = self.terms;
+        self.hits
+            .into_iter()
+            .map(|(post_id, hit)| {
+                let index = &indexes[hit.shard];
+                let title_terms = index.analyzer.tokenize(&post_id.0);
+                let mut matched_terms: Vec<String> = Vec::new();
+                let mut matched: Vec<&Term> = Vec::new();
+                for term in &terms {
+                    let found = self.fields.contains(term, index, hit.index, &title_terms);
+                    if found && !matched_terms.contains(&term.word) {
+                        matched_terms.push(term.word.clone());
+                        matched.push(term);
+                    }
+                }
+                let mut highlights = Highlights::new();
+                let fields = [
+                    ("title", Some(&post_id.0)),
+                    (EXCERPT_KEY, post_id.2.get(EXCERPT_KEY)),
+                ];
+                for (field, text) in fields {
+                    let ranges = text
+                        .map_or_else(Vec::new, |text| highlight(text, &matched, index.analyzer));
+                    if !ranges.is_empty() {
+                        highlights.insert(field.to_string(), ranges);
+                    }
+                }
+                SearchResult {
+                    post_id,
+                    score: hit.score,
+                    matched_terms,
+                    highlights,
+                }
+            })
+            .collect()
+    }
+}
+
+/// Byte ranges of the words of `text` that are one of `terms`, or a typo of it.
+/// Words are split like [`Analyzer::split`] does, but keep their digits.
+fn highlight(text: &str, terms: &[&Term], analyzer: Analyzer) -> Vec<(usize, usize)> {
+    let mut ranges = Vec::new();
+    let mut start = None;
+    for (i, c) in text.char_indices().chain([(text.len(), ' ')]) {
+        match (
+            start,
+            c.is_alphanumeric() || c == '\'' || analyzer.joiners.contains(c),
+        ) {
+            (None, true) => start = Some(i),
+            (Some(first), false) => {
+                let words = analyzer.tokenize(&text[first..i]);
+                let matches = words.iter().any(|word| {
+                    terms
+                        .iter()
+                        .any(|term| term.in_title(std::slice::from_ref(word)) != Match::None)
+                });
+                if matches {
+                    ranges.push((first, i));
+                }
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    ranges
+}
+
+/// The parsed query in advanced mode, and the words to search for. Stopwords and
+/// words shorter than the minimum length are left out, like when building the index.
+fn query_words(
+    index: &Index,
+    query: &str,
+    options: &SearchOptions,
+) -> (Option<query::Query>, Vec<String>) {
+    let min_word_len = options.min_word_len.unwrap_or(index.min_word_len);
+    let stopword = |word: &str| {
+        !index.languages.is_empty() && index.languages.iter().all(|l| l.is_stopword(word))
+    };
+    let keep = |word: &String| word.chars().count() >= min_word_len && !stopword(word);
+    let parsed = match options.query_mode {
+        QueryMode::Simple => None,
+        QueryMode::Advanced => Some(query::parse(query, |token| {
+            let mut words = index.analyzer.words(token);
+            words.retain(keep);
+            words
+        })),
+    };
+    let search_words: Vec<String> = match &parsed {
+        Some(parsed) => parsed.positive_words(),
+        None => {
+            let mut words = index.analyzer.tokenize(query);
+            words.retain(keep);
+            words
+        }
+    };
+    (parsed, search_words)
+}
+
+fn ranked<'a>(
+    index: &Index<'a, '_>,
+    query: &str,
+    num_results: usize,
+    options: &SearchOptions,
+    expired: &dyn Fn() -> bool,
+) -> Ranking<'a> {
+    let index = &Index {
+        boosts: options.boosts(index.boosts),
+        ..*index
+    };
+    let (parsed, search_words) = query_words(index, query, options);
+    let key = options.hash_key.as_str();
+    let prepare = |word: &str| Term::new(word, key).fuzzy(index.typos, options.fuzzy, key);
+    let terms: Vec<Term> = search_words.iter().map(|word| prepare(word)).collect();
+    let fields = Fields::new(&options.fields);
+    // Queries of only stopwords and short words can't match any post
+    if terms.is_empty() {
+        return Ranking {
+            hits: Vec::new(),
+            fields,
+            terms,
+            truncated: false,
+        };
+    }
+    let bigrams = bigram_hashes(index, &search_words, key);
+    let word_terms: HashMap<&str, Term> = parsed
+        .iter()
+        .flat_map(|parsed| parsed.words())
+        .map(|word| (word.as_str(), prepare(word)))
         .collect();
 
-    matches.sort_by_key(|k| Reverse(k.1));
+    // Once `num_results` posts were found, posts that can't beat the worst of them
+    // are skipped without scoring their title. The type limits and duplicate URLs
+    // may remove any of the results though, advanced queries need the title terms
+    // anyway, and dates and facet fields can rank posts with lower scores first.
+    let early_exit = num_results > 0
+        && fields.facets.is_empty()
+        && parsed.is_none()
+        && options.sort == Sort::Relevance
+        && options.recency == 0.0
+        && options.type_limits.is_empty()
+        && index.type_limits.is_empty()
+        && !index
+            .filters
+            .iter()
+            .any(|(post_id, _)| post_id.2.contains_key(CANONICAL_KEY));
+    let mut top: BinaryHeap<Reverse<usize>> = BinaryHeap::new();
+    // Upper bound of the title points of any post, as if all terms were in the title
+    let max_title_points = match fields.title {
+        true => index.boosts.title * terms.len() * Match::Exact as usize,
+        false => 0,
+    };
+
+    let mut truncated = false;
+    let mut matches: Vec<(&PostId, Hit)> = index
+        .filters
+        .iter()
+        .enumerate()
+        .take_while(|(i, _)| {
+            truncated = i % BUDGET_CHECK_INTERVAL == BUDGET_CHECK_INTERVAL - 1 && expired();
+            !truncated
+        })
+        .filter_map(|(i, (post_id, filter))| {
+            if !options.filter.is_empty() && !matches_filter(index.facets.get(i), &options.filter) {
+                return None;
+            }
+            if options.exclude.contains(&post_id.1) {
+                return None;
+            }
+            if !options.sections.is_empty() && !options.sections.contains(post_section(post_id)) {
+                return None;
+            }
+            let typos = index.typos.map(|typos| &typos.filters[i]);
+            let body = match fields.body {
+                true => {
+                    body_points(&terms, filter, typos, &index.boosts)
+                        + bigram_points(&bigrams, filter, index.bigram_boost)
+                }
+                false => 0,
+            };
+            if let (true, Some(Reverse(threshold))) = (top.len() == num_results, top.peek()) {
+                // Earlier posts win ties, so matching the threshold isn't enough unless
+                // ties are broken otherwise
+                let bound = calibrated(body + max_title_points, terms.len());
+                if bound < *threshold
+                    || (bound == *threshold && options.tie_break == TieBreak::Index)
+                {
+                    return None;
+                }
+            }
+            let title_terms = index.analyzer.tokenize(&post_id.0);
+            if let Some(parsed) = &parsed {
+                let matches = parsed
+                    .matches(|word| fields.contains(&word_terms[word], index, i, &title_terms));
+                if !matches {
+                    return None;
+                }
+            }
+            let mut score = body;
+            if fields.title {
+                score += title_points(&title_terms, &terms, &index.boosts);
+            }
+            score += facet_points(&terms, &fields, index, i, typos);
+            if score == 0 {
+                return None;
+            }
+            let matched = terms
+                .iter()
+                .filter(|term| fields.contains(term, index, i, &title_terms))
+                .count();
+            let score = calibrated(score, matched);
+            if early_exit {
+                top.push(Reverse(score));
+                if top.len() > num_results {
+                    top.pop();
+                }
+            }
+            let date = index.dates.get(i).copied().flatten();
+            let boost = 1.0 + options.recency * freshness(date, index.date_range);
+            let hit = Hit {
+                index: i,
+                shard: 0,
+                score: score as f32 / 2.0 / (terms.len() * terms.len()) as f32 * boost,
+                date,
+            };
+            Some((post_id, hit))
+        })
+        .collect();
 
-    matches.into_iter().take(num_results).map(|p| p.0).collect()
+    sort_hits(&mut matches, options, |post_id| post_id);
+    dedup_canonical(&mut matches);
+    limit_types(&mut matches, &options.type_limits, index.type_limits);
+    if num_results > 0 {
+        matches.truncate(num_results);
+    }
+    Ranking {
+        hits: matches,
+        fields,
+        terms,
+        truncated,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use xorf::HashProxy;
+
+    #[test]
+    fn test_filter_is_compatible_with_hash_proxy() {
+        let terms: Vec<String> = vec!["rust".into(), "wasm".into(), "search".into()];
+        let proxy: HashProxy<String, DefaultHasher, Xor8> = HashProxy::from(&terms);
+        let bytes = bincode::serialize(&proxy).unwrap();
+        assert_eq!(bytes, bincode::serialize(&Filter::from(&terms)).unwrap());
+
+        let filter: Filter = bincode::deserialize(&bytes).unwrap();
+        for term in &terms {
+            assert!(filter.contains(&hash_term(term)));
+        }
+        assert!(!filter.contains_hash(hash_term("kubernetes")));
+    }
+
+    #[test]
+    fn test_read_storage_version_7() {
+        // Bincode writes structs like tuples of their fields
+        let filters = vec![post("rust", &["rust"])];
+        let old = (
+            filters,
+            Boosts::default(),
+            None::<Typos>,
+            TypeLimits::new(),
+            legacy::AnalyzerWithoutJoiners::default(),
+            Vec::<Facets>::new(),
+            Vec::<Option<i64>>::new(),
+            0usize,
+            "posts",
+        );
+        let body = bincode::serialize(&old).unwrap();
+        // Version 9 added the languages and the minimum word length
+        let with_languages = [
+            body.clone(),
+            bincode::serialize(&(vec![Language::En], 3usize)).unwrap(),
+        ]
+        .concat();
+        for (version, body) in [(7u16, &body), (8, &body), (9, &with_languages)] {
+            let mut bytes = STORAGE_MAGIC.to_vec();
+            bytes.extend(version.to_le_bytes());
+            bytes.push(Compression::None.to_byte());
+            bytes.extend(body);
+            let storage = Storage::from_bytes(&bytes).unwrap();
+            assert_eq!(storage.search("rust".into(), 0)[0].0, "rust");
+            assert_eq!(storage.corpus_hash, "posts");
+            assert_eq!(storage.analyzer, Analyzer::default());
+            assert_eq!(storage.min_word_len, if version == 9 { 3 } else { 0 });
+        }
+    }
+
+    #[test]
+    fn test_related_queries() {
+        let mut storage = Storage::from(vec![post("rust", &["rust"])]);
+        assert!(storage.related_queries("rust").is_empty());
+        let related = |words: &[&str]| words.iter().map(|word| word.to_string()).collect();
+        storage.related = [
+            ("rust".to_string(), related(&["wasm", "async"])),
+            ("async".to_string(), related(&["await", "rust", "wasm"])),
+        ]
+        .into();
+        assert_eq!(
+            storage.related_queries(" Rust "),
+            ["Rust wasm", "Rust async"]
+        );
+        // Words of the query aren't suggested again
+        assert_eq!(
+            storage.related_queries("rust  async"),
+            ["rust async wasm", "rust async await"]
+        );
+    }
+
+    #[test]
+    fn test_query_stopwords() {
+        let mut storage = Storage::from(vec![
+            post("The Rust Book", &["rust", "book"]),
+            post("Go", &["go"]),
+        ]);
+        storage.languages = vec![Language::En];
+        storage.min_word_len = 3;
+        assert!(storage.search("the".into(), 0).is_empty());
+        assert_eq!(storage.search("the rust".into(), 0)[0].0, "The Rust Book");
+        assert!(storage.search("go".into(), 0).is_empty());
+        let options = SearchOptions::default()
+            .with_query_mode(QueryMode::Advanced)
+            .with_min_word_len(2);
+        let results = storage.search_with_options("\"the go\"".into(), 0, &options);
+        assert_eq!(results[0].0 .0, "Go");
+    }
+
+    #[test]
+    fn test_from_static_bytes() {
+        let storage = Storage::from(vec![post("rust", &["wasm"]), post("go", &["gopher"])]);
+        let bytes: &'static [u8] = storage.to_bytes().unwrap().leak();
+        let storage = Storage::from_static_bytes(bytes).unwrap();
+        assert!(storage.filters.iter().all(|(_, f)| f.filter.is_borrowed()));
+        assert_eq!(storage.search("wasm".into(), 0)[0].0, "rust");
+        assert!(storage.search("kubernetes".into(), 0).is_empty());
+
+        // Filters decoded afterwards are owned again
+        let storage = Storage::from_bytes(bytes).unwrap();
+        assert!(!storage.filters[0].1.filter.is_borrowed());
+    }
+
+    fn post(title: &str, body: &[&str]) -> PostFilter {
+        let terms: Vec<String> = body.iter().map(|term| term.to_string()).collect();
+        (
+            (title.to_string(), format!("/{}", title), Meta::new()),
+            Filter::from(&terms),
+        )
+    }
+
+    #[test]
+    fn test_zero_results_means_all() {
+        let filters: Filters = (0..10)
+            .map(|i| post(&format!("post{}", i), &["rust"]))
+            .collect();
+        assert_eq!(search(&filters, "rust".into(), 3).len(), 3);
+        assert_eq!(search(&filters, "rust".into(), 0).len(), 10);
+        assert!(search(&filters, "wasm".into(), 0).is_empty());
+    }
+
+    #[test]
+    fn test_search_paginated() {
+        let storage = Storage::from(
+            (0..10)
+                .map(|i| post(&format!("post{}", i), &["rust"]))
+                .collect::<Filters>(),
+        );
+        let options = SearchOptions::default();
+        let titles = |page: Page| -> Vec<String> {
+            assert_eq!(page.total, 10);
+            page.results
+                .iter()
+                .map(|(post_id, _)| post_id.0.clone())
+                .collect()
+        };
+        let page = storage.search_paginated("rust".into(), 2, 4, &options);
+        assert_eq!(titles(page), ["post4", "post5", "post6", "post7"]);
+        let page = storage.search_paginated("rust".into(), 3, 4, &options);
+        assert_eq!(titles(page), ["post8", "post9"]);
+        assert!(titles(storage.search_paginated("rust".into(), 4, 4, &options)).is_empty());
+        assert_eq!(
+            titles(storage.search_paginated("rust".into(), 1, 0, &options)).len(),
+            10
+        );
+    }
+
+    #[test]
+    fn test_search_with_scores() {
+        let filters: Filters = vec![
+            post("rust", &["rust", "wasm"]),
+            post("other", &["rust", "wasm"]),
+            post("unrelated", &["go"]),
+        ];
+        let results = search_with_scores(&filters, "Rust WASM".into(), 0);
+        let scores: Vec<(&str, f32)> = results
+            .iter()
+            .map(|(post_id, score)| (post_id.0.as_str(), *score))
+            .collect();
+        assert_eq!(scores, [("rust", 5.0), ("other", 2.0)]);
+    }
+
+    #[test]
+    fn test_search_with_details() {
+        let storage = Storage::from(vec![
+            post("rust", &["wasm", "search"]),
+            post("other", &["search"]),
+        ]);
+        let results = storage.search_with_details(
+            "Rust WASM search rust go".into(),
+            0,
+            &SearchOptions::default(),
+        );
+        let details: Vec<(&str, Vec<&str>)> = results
+            .iter()
+            .map(|result| {
+                let terms = result.matched_terms.iter().map(String::as_str).collect();
+                (result.post_id.0.as_str(), terms)
+            })
+            .collect();
+        assert_eq!(
+            details,
+            [
+                ("rust", vec!["rust", "wasm", "search"]),
+                ("other", vec!["search"])
+            ]
+        );
+        let scores = storage.search_with_options(
+            "Rust WASM search rust go".into(),
+            0,
+            &SearchOptions::default(),
+        );
+        assert_eq!(results[0].score, scores[0].1);
+    }
+
+    #[test]
+    fn test_highlights() {
+        let (mut post_id, filter) = post("Rust and WebAssembly", &["rust", "wasm", "café"]);
+        post_id.0 = "Rust and WebAssembly".into();
+        post_id
+            .2
+            .insert(EXCERPT_KEY.into(), "Use café Rust's WASM, rusty".into());
+        let storage = Storage::from(vec![(post_id, filter)]);
+        let results =
+            storage.search_with_details("rust wasm café".into(), 0, &SearchOptions::default());
+        let highlights = &results[0].highlights;
+        assert_eq!(highlights["title"], [(0, 4)]);
+        let excerpt = &results[0].post_id.2[EXCERPT_KEY];
+        let words: Vec<&str> = highlights[EXCERPT_KEY]
+            .iter()
+            .map(|&(start, end)| &excerpt[start..end])
+            .collect();
+        assert_eq!(words, ["café", "WASM"]);
+    }
+
+    #[test]
+    fn test_storage_boosts() {
+        let mut storage = Storage::from(vec![
+            post("rust", &["rust"]),
+            post("other", &["rust", "wasm"]),
+        ]);
+        let titles = |storage: &Storage| -> Vec<String> {
+            storage
+                .search("rust wasm".into(), 0)
+                .iter()
+                .map(|post_id| post_id.0.clone())
+                .collect()
+        };
+        assert_eq!(titles(&storage), ["other", "rust"]);
+
+        storage.boosts = Boosts {
+            title: 10,
+            ..Boosts::default()
+        };
+        let storage = Storage::from_bytes(&storage.to_bytes().unwrap()).unwrap();
+        assert_eq!(storage.boosts.title, 10);
+        assert_eq!(titles(&storage), ["rust", "other"]);
+    }
+
+    #[test]
+    fn test_query_boosts() {
+        let storage = Storage::from(vec![
+            post("rust", &["rust"]),
+            post("other", &["rust", "wasm"]),
+        ]);
+        let titles = |options: SearchOptions| -> Vec<String> {
+            storage
+                .search_with_options("rust wasm".into(), 0, &options)
+                .iter()
+                .map(|(post_id, _)| post_id.0.clone())
+                .collect()
+        };
+        assert_eq!(titles(SearchOptions::default()), ["other", "rust"]);
+        assert_eq!(
+            titles(SearchOptions::default().with_title_boost(10)),
+            ["rust", "other"]
+        );
+    }
+
+    #[test]
+    fn test_score_calibration() {
+        let storage = Storage::from(vec![
+            post("rust", &["rust"]),
+            post("wasm", &["wasm", "search"]),
+            post("body", &["rust", "wasm", "search"]),
+        ]);
+        let results = storage.search_with_scores("rust wasm search".into(), 0);
+        let titles: Vec<&str> = results.iter().map(|(post_id, _)| &*post_id.0).collect();
+        // All words in the body beat two words, one of them in the title, which beat
+        // a single word in the title
+        assert_eq!(titles, ["body", "wasm", "rust"]);
+        assert_eq!(results[0].1, 3.0);
+
+        // Scores of single words don't depend on the coverage
+        let results = storage.search_with_scores("rust".into(), 0);
+        assert_eq!(results[0].1, 4.0);
+        assert_eq!(results[1].1, 1.0);
+    }
+
+    #[test]
+    fn test_advanced_query_mode() {
+        let storage = Storage::from(vec![
+            post("rust", &["rust", "wasm"]),
+            post("go", &["go", "wasm"]),
+            post("both", &["rust", "go"]),
+        ]);
+        let titles = |query: &str, mode: QueryMode| -> Vec<String> {
+            let options = SearchOptions::default().with_query_mode(mode);
+            storage
+                .search_with_options(query.into(), 0, &options)
+                .iter()
+                .map(|(post_id, _)| post_id.0.clone())
+                .collect()
+        };
+        assert_eq!(titles("rust wasm", QueryMode::Simple).len(), 3);
+        assert_eq!(titles("rust AND wasm", QueryMode::Advanced), ["rust"]);
+        assert_eq!(titles("\"go wasm\"", QueryMode::Advanced), ["go"]);
+        assert_eq!(titles("wasm -go", QueryMode::Advanced), ["rust"]);
+        assert_eq!(
+            titles("rust AND wasm OR go AND wasm", QueryMode::Advanced),
+            ["rust", "go"]
+        );
+    }
+
+    #[test]
+    fn test_deletions() {
+        assert_eq!(deletions("rust", 1), ["ust", "rst", "rut", "rus"]);
+        assert_eq!(deletions("rust", 2).len(), 4 + 6);
+        assert!(deletions("go", 1).is_empty());
+    }
+
+    #[test]
+    fn test_fuzzy() {
+        let posts = [
+            ("containers", vec!["kubernetes", "docker"]),
+            ("languages", vec!["rust", "python"]),
+        ];
+        let mut storage = Storage::from(
+            posts
+                .iter()
+                .map(|(title, words)| post(title, words))
+                .collect::<Filters>(),
+        );
+        let titles = |storage: &Storage, query: &str, fuzzy: usize| -> Vec<String> {
+            let options = SearchOptions::default().with_fuzzy(fuzzy);
+            storage
+                .search_with_options(query.into(), 0, &options)
+                .iter()
+                .map(|(post_id, _)| post_id.0.clone())
+                .collect()
+        };
+        // Without typo filters in the storage, fuzzy search has no effect
+        assert!(titles(&storage, "kubernets", 1).is_empty());
+
+        storage.typos = Some(Typos {
+            max_distance: 1,
+            filters: posts
+                .iter()
+                .map(|(_, words)| {
+                    let words: Vec<String> = words.iter().map(|word| word.to_string()).collect();
+                    Typos::filter(&words, 1)
+                })
+                .collect(),
+        });
+        let storage = Storage::from_bytes(&storage.to_bytes().unwrap()).unwrap();
+        assert!(titles(&storage, "kubernets", 0).is_empty());
+        // Deletion, insertion, substitution, and transposition
+        for typo in ["kubernets", "kubernetess", "kubernites", "kubrenetes"] {
+            assert_eq!(titles(&storage, typo, 1), ["containers"]);
+        }
+        assert_eq!(titles(&storage, "langauges", 1), ["languages"]);
+        assert!(titles(&storage, "kubrnetis", 1).is_empty());
+
+        // Typos count half as much as exact matches
+        let scores: Vec<f32> = storage
+            .search_with_options(
+                "rust rusty".into(),
+                0,
+                &SearchOptions::default().with_fuzzy(1),
+            )
+            .iter()
+            .map(|(_, score)| *score)
+            .collect();
+        assert_eq!(scores, [1.5]);
+    }
+
+    #[test]
+    fn test_early_exit_keeps_results() {
+        let words = ["rust", "wasm", "search", "static", "site"];
+        let filters: Filters = (0..100)
+            .map(|i| {
+                let title = format!("{} {}", words[i % 5], words[i * 7 % 5]);
+                let body: Vec<&str> = words.iter().copied().skip(i % 3).take(i % 6).collect();
+                post(&title, &body)
+            })
+            .collect();
+        for query in ["rust", "wasm site", "search static rust"] {
+            for n in [1, 3, 10] {
+                let all = search_with_scores(&filters, query.into(), 0);
+                let top = search_with_scores(&filters, query.into(), n);
+                assert_eq!(top, all[..n], "{} {}", query, n);
+            }
+        }
+    }
+
+    #[test]
+    fn test_search_with_deadline() {
+        let storage = Storage::from(
+            (0..200)
+                .map(|i| post(&format!("post{}", i), &["rust"]))
+                .collect::<Filters>(),
+        );
+        let options = SearchOptions::default();
+        let all = storage.search_with_deadline("rust".into(), 0, &options, || false);
+        assert_eq!(all.results.len(), 200);
+        assert!(!all.truncated);
+
+        // Expire on the second check, after two intervals of posts
+        let checks = std::cell::Cell::new(0);
+        let partial = storage.search_with_deadline("rust".into(), 0, &options, || {
+            checks.set(checks.get() + 1);
+            checks.get() > 1
+        });
+        assert!(partial.truncated);
+        assert_eq!(partial.results.len(), 2 * BUDGET_CHECK_INTERVAL - 1);
+        assert_eq!(partial.results[0].0 .0, "post0");
+    }
+
+    #[test]
+    fn test_post_type() {
+        assert_eq!(post_type("https://example.com/blog/hello/"), "blog");
+        assert_eq!(post_type("/docs/install.html"), "docs");
+        assert_eq!(post_type("https://example.com"), "");
+    }
+
+    #[test]
+    fn test_type_limits() {
+        let mut storage = Storage::from(
+            ["blog/a", "blog/b", "blog/c", "docs/a", "docs/b"]
+                .iter()
+                .map(|title| post(title, &["rust"]))
+                .collect::<Filters>(),
+        );
+        let urls = |storage: &Storage, options: &SearchOptions| -> Vec<String> {
+            storage
+                .search_with_options("rust".into(), 4, options)
+                .iter()
+                .map(|(post_id, _)| post_id.1.clone())
+                .collect()
+        };
+        let options = SearchOptions::default().with_type_limit("blog", 1);
+        assert_eq!(urls(&storage, &options), ["/blog/a", "/docs/a", "/docs/b"]);
+
+        storage.type_limits.insert("docs".into(), 1);
+        let storage = Storage::from_bytes(&storage.to_bytes().unwrap()).unwrap();
+        assert_eq!(
+            urls(&storage, &SearchOptions::default()),
+            ["/blog/a", "/blog/b", "/blog/c", "/docs/a"]
+        );
+        let options = options.with_type_limit("docs", 2);
+        assert_eq!(urls(&storage, &options), ["/blog/a", "/docs/a", "/docs/b"]);
+    }
+
+    #[test]
+    fn test_sections() {
+        let mut guide = post("guides/install", &["rust"]);
+        guide.0 .2.insert(SECTION_KEY.into(), "api".into());
+        let storage = Storage::from(vec![
+            guide,
+            post("guides/start", &["rust"]),
+            post("api/search", &["rust"]),
+        ]);
+        let urls = |options: &SearchOptions| -> Vec<String> {
+            storage
+                .search_with_options("rust".into(), 0, options)
+                .iter()
+                .map(|(post_id, _)| post_id.1.clone())
+                .collect()
+        };
+        assert_eq!(urls(&SearchOptions::default()).len(), 3);
+        let options = SearchOptions::default().with_section("api");
+        assert_eq!(urls(&options), ["/guides/install", "/api/search"]);
+        let options = options.with_section("guides");
+        assert_eq!(urls(&options).len(), 3);
+    }
+
+    #[test]
+    fn test_facets() {
+        let mut storage = Storage::from(vec![
+            post("rust", &["search"]),
+            post("go", &["search"]),
+            post("untagged", &["search"]),
+        ]);
+        let facets = |category: &str, tags: &[&str]| -> Facets {
+            Facets::from([
+                ("category".to_string(), vec![category.to_string()]),
+                (
+                    "tags".to_string(),
+                    tags.iter().map(|t| t.to_string()).collect(),
+                ),
+            ])
+        };
+        storage.facets = vec![
+            facets("lang", &["rust", "wasm"]),
+            facets("lang", &["go"]),
+            Facets::new(),
+        ];
+        let storage = Storage::from_bytes(&storage.to_bytes().unwrap()).unwrap();
+        let titles = |options: SearchOptions| -> Vec<String> {
+            storage
+                .search_with_options("search".into(), 0, &options)
+                .iter()
+                .map(|(post_id, _)| post_id.0.clone())
+                .collect()
+        };
+        assert_eq!(titles(SearchOptions::default()).len(), 3);
+        let lang = SearchOptions::default().with_filter("category", "lang");
+        assert_eq!(titles(lang.clone()), ["rust", "go"]);
+        assert_eq!(titles(lang.with_filter("tags", "wasm")), ["rust"]);
+        assert!(titles(SearchOptions::default().with_filter("author", "me")).is_empty());
+    }
+
+    #[test]
+    fn test_canonical_dedup() {
+        let mut filters: Filters = vec![
+            post("new", &["rust"]),
+            post("old", &["rust", "wasm"]),
+            post("other", &["rust"]),
+        ];
+        for (post_id, _) in &mut filters[..2] {
+            post_id
+                .2
+                .insert(CANONICAL_KEY.to_string(), "/new".to_string());
+        }
+        let storage = Storage::from(filters);
+        let titles = |query: &str, num_results| -> Vec<String> {
+            storage
+                .search(query.into(), num_results)
+                .iter()
+                .map(|post_id| post_id.0.clone())
+                .collect()
+        };
+        assert_eq!(titles("rust", 0), ["new", "other"]);
+        assert_eq!(titles("rust", 2), ["new", "other"]);
+        // The best match of the duplicates is kept
+        assert_eq!(titles("rust wasm", 0), ["old", "other"]);
+    }
+
+    #[test]
+    fn test_sort_by_date() {
+        let mut storage = Storage::from(vec![
+            post("old", &["rust", "wasm"]),
+            post("new", &["rust"]),
+            post("undated", &["rust"]),
+        ]);
+        storage.dates = vec![Some(100), Some(200), None];
+        let storage = Storage::from_bytes(&storage.to_bytes().unwrap()).unwrap();
+        let titles = |query: &str, options: SearchOptions| -> Vec<String> {
+            storage
+                .search_with_options(query.into(), 0, &options)
+                .iter()
+                .map(|(post_id, _)| post_id.0.clone())
+                .collect()
+        };
+        let options = SearchOptions::default();
+        assert_eq!(titles("rust", options.clone()), ["old", "new", "undated"]);
+        let by_date = options.clone().with_sort(Sort::Date);
+        assert_eq!(titles("rust wasm", by_date), ["new", "old", "undated"]);
+        // The newest post gets eight times the score, which ties with the better match
+        let recency = options.clone().with_recency(7.0);
+        assert_eq!(titles("rust wasm", recency), ["old", "new", "undated"]);
+        let recency = options.with_recency(8.0);
+        assert_eq!(titles("rust wasm", recency), ["new", "old", "undated"]);
+    }
+
+    #[test]
+    fn test_tie_break() {
+        let mut storage = Storage::from(vec![
+            post("c", &["rust"]),
+            post("a", &["rust"]),
+            post("b", &["rust"]),
+        ]);
+        storage.dates = vec![Some(1), None, Some(3)];
+        let titles = |num_results: usize, tie_break: TieBreak| -> Vec<String> {
+            let options = SearchOptions::default().with_tie_break(tie_break);
+            storage
+                .search_with_options("rust".into(), num_results, &options)
+                .iter()
+                .map(|(post_id, _)| post_id.0.clone())
+                .collect()
+        };
+        assert_eq!(titles(0, TieBreak::Index), ["c", "a", "b"]);
+        assert_eq!(titles(0, TieBreak::Title), ["a", "b", "c"]);
+        assert_eq!(titles(0, TieBreak::Date), ["b", "c", "a"]);
+        // Posts that tie with the results found so far aren't skipped
+        assert_eq!(titles(1, TieBreak::Url), ["a"]);
+    }
+
+    #[test]
+    fn test_bigram_boost() {
+        let async_rust = bigram("async", "rust");
+        let rust_async = bigram("rust", "async");
+        let mut storage = Storage::from(vec![
+            post("ordered", &["async", "rust", &async_rust]),
+            post("reversed", &["rust", "async", &rust_async]),
+        ]);
+        let scores = |storage: &Storage| -> Vec<(String, f32)> {
+            storage
+                .search_with_scores("Rust async".into(), 0)
+                .iter()
+                .map(|(post_id, score)| (post_id.0.clone(), *score))
+                .collect()
+        };
+        assert_eq!(scores(&storage)[0], ("ordered".to_string(), 2.0));
+        storage.bigram_boost = 1;
+        assert_eq!(scores(&storage)[0], ("reversed".to_string(), 3.0));
+        assert_eq!(scores(&storage)[1], ("ordered".to_string(), 2.0));
+    }
+
+    #[test]
+    fn test_search_indexes() {
+        let docs = Storage::from(vec![post("install", &["rust"]), post("api", &["go"])]);
+        let blog = Storage::from(vec![post("release", &["rust", "release"])]);
+        let labels = |query: &str, num_results| -> Vec<(&str, String)> {
+            search_indexes(
+                &[("docs", &[&docs]), ("blog", &[&blog])],
+                query.into(),
+                num_results,
+                &SearchOptions::default(),
+            )
+            .iter()
+            .map(|result| (result.index, result.post_id.0.clone()))
+            .collect()
+        };
+        assert_eq!(
+            labels("rust", 0),
+            [
+                ("docs", "install".to_string()),
+                ("blog", "release".to_string())
+            ]
+        );
+        assert_eq!(labels("rust release", 1), [("blog", "release".to_string())]);
+    }
+
+    #[test]
+    fn test_hash_key() {
+        let words = vec!["secret".to_string()];
+        let post_id = ("a".to_string(), "/a".to_string(), Meta::new());
+        let storage = Storage::from(vec![(post_id, Filter::with_key(&words, "key"))]);
+        let search = |key: &str| {
+            let options = SearchOptions::default().with_hash_key(key);
+            storage
+                .search_with_options("secret".into(), 0, &options)
+                .len()
+        };
+        assert_eq!(search("key"), 1);
+        assert_eq!(search(""), 0);
+        assert_eq!(search("other"), 0);
+        assert_eq!(hash_term_with_key("rust", ""), hash_term("rust"));
+    }
+
+    #[test]
+    fn test_from_token_sets() {
+        let words = |words: &[&str]| words.iter().map(|word| word.to_string()).collect();
+        let storage = Storage::from_token_sets([
+            (
+                ("Running".to_string(), "/running".to_string(), Meta::new()),
+                words(&["run", "fast"]),
+            ),
+            (
+                ("Walking".to_string(), "/walking".to_string(), Meta::new()),
+                words(&["walk"]),
+            ),
+        ]);
+        let results = storage.search("RUN".into(), 0);
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].1, "/running");
+        // Titles are matched as they are
+        assert_eq!(storage.search("walking".into(), 0)[0].1, "/walking");
+    }
+
+    #[test]
+    fn test_exclude() {
+        let storage = Storage::from(vec![
+            post("a", &["rust"]),
+            post("b", &["rust"]),
+            post("c", &["rust"]),
+        ]);
+        let options = SearchOptions::default().with_exclude("/b");
+        let titles: Vec<String> = storage
+            .search_with_options("rust".into(), 2, &options)
+            .into_iter()
+            .map(|(post_id, _)| post_id.0.clone())
+            .collect();
+        // Excluded posts don't take up any of the results
+        assert_eq!(titles, ["a", "c"]);
+    }
+
+    #[test]
+    fn test_fields() {
+        let mut storage = Storage::from(vec![post("rust", &["wasm"]), post("wasm", &["rust"])]);
+        storage.facets = vec![
+            Facets::from([("tags".to_string(), vec!["search".to_string()])]),
+            Facets::from([("tags".to_string(), vec!["Rust".to_string()])]),
+        ];
+        let results = |fields: &[&str]| -> Vec<(String, f32)> {
+            let options = fields
+                .iter()
+                .fold(SearchOptions::default(), |options, field| {
+                    options.with_field(field)
+                });
+            storage
+                .search_with_options("rust".into(), 0, &options)
+                .iter()
+                .map(|(post_id, score)| (post_id.0.clone(), *score))
+                .collect()
+        };
+        let rust = || ("rust".to_string(), 3.0);
+        let wasm = || ("wasm".to_string(), 1.0);
+        assert_eq!(results(&[]), [rust(), wasm()]);
+        assert_eq!(results(&["title"]), [rust()]);
+        assert_eq!(results(&["body"]), [wasm()]);
+        assert_eq!(results(&["tags"]), [wasm()]);
+        // A word in the body and the tags counts once
+        assert_eq!(results(&["body", "tags"]), [wasm()]);
+
+        storage.boosts.facets = 4;
+        let options = SearchOptions::default().with_field("tags");
+        let results = storage.search_with_options("rust".into(), 0, &options);
+        assert_eq!(results[0].1, 4.0);
+        let results = storage.search_with_options("rust".into(), 0, &options.with_facet_boost(2));
+        assert_eq!(results[0].1, 2.0);
+    }
+
+    #[test]
+    fn test_read_storage_version_3() {
+        #[derive(Serialize)]
+        struct StorageWithoutFacets {
+            filters: Filters,
+            boosts: Boosts,
+            typos: Option<Typos>,
+            type_limits: TypeLimits,
+            analyzer: legacy::AnalyzerWithoutJoiners,
+        }
+        let old = StorageWithoutFacets {
+            filters: vec![post("rust", &["rust"])],
+            boosts: Boosts::default(),
+            typos: None,
+            type_limits: TypeLimits::new(),
+            analyzer: legacy::AnalyzerWithoutJoiners::default(),
+        };
+        let mut bytes = STORAGE_MAGIC.to_vec();
+        bytes.extend(3u16.to_le_bytes());
+        bytes.push(Compression::None.to_byte());
+        bytes.extend(bincode::serialize(&old).unwrap());
+        let storage = Storage::from_bytes(&bytes).unwrap();
+        assert_eq!(storage.filters.len(), 1);
+        assert!(storage.facets.is_empty());
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn test_json() {
+        let mut storage = Storage::from(vec![post("rust", &["rust", "wasm"])]);
+        storage.type_limits.insert("blog".into(), 2);
+        let json = storage.to_json().unwrap();
+        let storage = Storage::from_json(&json).unwrap();
+        assert_eq!(storage.type_limits["blog"], 2);
+        assert_eq!(storage.search("wasm".into(), 0)[0].0, "rust");
+    }
+
+    #[test]
+    fn test_documents() {
+        let storage = Storage::from(vec![post("rust", &["rust"]), post("go", &["go"])]);
+        let titles: Vec<&str> = storage
+            .documents()
+            .map(|post_id| post_id.0.as_str())
+            .collect();
+        assert_eq!(titles, ["rust", "go"]);
+    }
+
+    #[test]
+    fn test_storage_header() {
+        let storage = Storage::from(vec![post("rust", &["rust"])]);
+        let mut bytes = storage.to_bytes().unwrap();
+        assert_eq!(bytes[..4], STORAGE_MAGIC);
+        assert_eq!(Storage::from_bytes(&bytes).unwrap().filters.len(), 1);
+
+        bytes[4..6].copy_from_slice(&(STORAGE_VERSION + 1).to_le_bytes());
+        match Storage::from_bytes(&bytes) {
+            Err(StorageError::VersionMismatch { found, expected }) => {
+                assert_eq!((found, expected), (STORAGE_VERSION + 1, STORAGE_VERSION));
+            }
+            _ => panic!("expected a version mismatch"),
+        }
+        assert!(matches!(
+            Storage::from_bytes(&STORAGE_MAGIC[..]),
+            Err(StorageError::Truncated)
+        ));
+    }
+
+    #[test]
+    fn test_read_storage_version_1() {
+        let body = legacy::encode_string_meta(&[("rust", Some("2020"))]);
+        let mut bytes = STORAGE_MAGIC.to_vec();
+        bytes.extend(1u16.to_le_bytes());
+        bytes.extend(&body);
+        let storage = Storage::from_bytes(&bytes).unwrap();
+        assert_eq!(storage.filters.len(), 1);
+        assert_eq!(storage.filters[0].0 .2["meta"], "2020");
+
+        // Version 2 added the compression
+        let mut bytes = STORAGE_MAGIC.to_vec();
+        bytes.extend(2u16.to_le_bytes());
+        bytes.push(Compression::None.to_byte());
+        bytes.extend(&body);
+        assert_eq!(Storage::from_bytes(&bytes).unwrap().filters.len(), 1);
+    }
+
+    #[cfg(all(feature = "gzip", feature = "brotli"))]
+    #[test]
+    fn test_compression() {
+        let posts: Vec<_> = (0..20)
+            .map(|i| post(&format!("post {}", i), &["rust", "wasm", "search"]))
+            .collect();
+        let storage = Storage::from(posts);
+        let uncompressed = storage.to_bytes().unwrap().len();
+        for compression in [Compression::Gzip, Compression::Brotli] {
+            let bytes = storage.to_compressed_bytes(compression).unwrap();
+            assert!(bytes.len() < uncompressed, "{}", compression);
+            let read = Storage::from_bytes(&bytes).unwrap();
+            assert_eq!(read.search("rust".to_string(), 0).len(), 20);
+        }
+    }
+
+    #[test]
+    fn test_read_storage_without_header() {
+        let bytes = legacy::encode_string_meta(&[("rust", None)]);
+        assert_eq!(Storage::from_bytes(&bytes).unwrap().filters.len(), 1);
+    }
+
+    #[test]
+    fn test_search_shards() {
+        let posts = |range: std::ops::Range<usize>| -> Filters {
+            range
+                .map(|i| {
+                    let title = format!("post {}", i);
+                    match i % 3 {
+                        0 => post(&title, &["rust", "wasm"]),
+                        _ => post(&title, &["rust"]),
+                    }
+                })
+                .collect()
+        };
+        let whole = Storage::from(posts(0..10));
+        let shards: Vec<Storage> = [0..4, 4..8, 8..10]
+            .into_iter()
+            .map(|range| Storage::from(posts(range)))
+            .collect();
+        let shards: Vec<&Storage> = shards.iter().collect();
+        let options = SearchOptions::default();
+        for n in [0, 3, 5] {
+            let expected = whole.search_with_options("rust wasm".to_string(), n, &options);
+            let sharded = search_shards(&shards, "rust wasm".to_string(), n, &options, || false);
+            assert_eq!(sharded.results, expected);
+            assert!(!sharded.truncated);
+
+            let details = whole.search_with_details("rust wasm".to_string(), n, &options);
+            let sharded =
+                search_shards_with_details(&shards, "rust wasm".to_string(), n, &options, || false);
+            assert_eq!(sharded, details);
+        }
+
+        let sharded = search_shards(&shards, "rust".to_string(), 0, &options, || true);
+        assert!(sharded.truncated);
+        assert!(sharded.results.len() < 10);
+    }
+
+    #[test]
+    fn test_stats() {
+        let storage = Storage::from(vec![
+            post("blog/a", &["rust", "wasm"]),
+            post("blog/b", &["rust"]),
+            post("docs", &["search"]),
+        ]);
+        let stats = storage.stats();
+        assert_eq!(stats.posts, 3);
+        assert_eq!(stats.posts_with_meta, 0);
+        assert!(stats.filter_bytes > 0);
+        assert_eq!(stats.typo_filter_bytes, 0);
+        assert_eq!(stats.posts_per_type["blog"], 2);
+        assert_eq!(stats.posts_per_type["docs"], 1);
+    }
+
+    #[test]
+    fn test_read_storage_without_boosts() {
+        let ((title, url, _), filter) = post("rust", &["rust"]);
+        let meta: Option<String> = None;
+        let bytes = bincode::serialize(&vec![((title, url, meta), filter)]).unwrap();
+        let storage = Storage::from_bytes(&bytes).unwrap();
+        assert_eq!(storage.filters.len(), 1);
+        assert_eq!(storage.boosts, Boosts::default());
+    }
 }
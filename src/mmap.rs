@@ -0,0 +1,96 @@
+use memmap2::Mmap;
+use std::fs::File;
+use std::ops::Deref;
+use std::path::Path;
+
+use crate::flat::FlatXor8;
+use crate::{Filter, Storage, StorageError};
+
+/// A storage file opened with [`Storage::open_mmap`]. It derefs to a read-only
+/// [`Storage`] whose filters are queried from the mapped file.
+pub struct SearchIndex {
+    // Declared before the map, so that it's dropped before the bytes it borrows
+    storage: Storage,
+    map: Mmap,
+}
+
+/// Fails to compile if `T` implements `Clone`. The storage of a [`SearchIndex`]
+/// borrows the map with a fake `'static` lifetime, so a clone of it, or of any of its
+/// filters, could outlive the map.
+trait NotClone<A> {
+    fn check() {}
+}
+
+impl<T> NotClone<()> for T {}
+
+struct Cloneable;
+
+// With a second impl for `Clone` types, `<T as NotClone<_>>` is ambiguous for them
+impl<T: Clone> NotClone<Cloneable> for T {}
+
+const _: fn() = || {
+    let _ = <Storage as NotClone<_>>::check;
+    let _ = <Filter as NotClone<_>>::check;
+    let _ = <FlatXor8 as NotClone<_>>::check;
+};
+
+impl SearchIndex {
+    /// Size of the mapped storage file in bytes
+    pub fn file_size(&self) -> usize {
+        self.map.len()
+    }
+}
+
+impl Deref for SearchIndex {
+    type Target = Storage;
+
+    fn deref(&self) -> &Storage {
+        &self.storage
+    }
+}
+
+impl Storage {
+    /// Open a storage file written by [`Storage::to_bytes`] as a memory map, e.g. for
+    /// large indexes on a server. Like [`Storage::from_static_bytes`], the filters of
+    /// the posts are queried from the file instead of being copied into memory, which
+    /// leaves it to the page cache to keep them around. Compressed storage is decoded
+    /// like with [`Storage::from_bytes`].
+    ///
+    /// The file must not be changed while the index is open, which would change the
+    /// filters underneath it. Write new indexes to a new file and rename it instead.
+    pub fn open_mmap(path: impl AsRef<Path>) -> Result<SearchIndex, StorageError> {
+        let file = File::open(path)?;
+        // SAFETY: The file isn't changed while mapped, as documented above
+        let map = unsafe { Mmap::map(&file)? };
+        // SAFETY: The bytes of a memory map don't move along with the map. The storage
+        // borrowing them is only handed out by reference, and dropped before the map.
+        let bytes: &'static [u8] = unsafe { std::slice::from_raw_parts(map.as_ptr(), map.len()) };
+        let storage = Storage::from_static_bytes(bytes)?;
+        Ok(SearchIndex { storage, map })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Filter, PostId};
+
+    #[test]
+    fn test_open_mmap() {
+        let words: Vec<String> = vec!["mmap".into()];
+        let post_id: PostId = ("Mapped".into(), "/mapped".into(), Default::default());
+        let storage = Storage::from(vec![(post_id, Filter::from(&words))]);
+        let path = std::env::temp_dir().join(format!("tinysearch-mmap-{}", std::process::id()));
+        std::fs::write(&path, storage.to_bytes().unwrap()).unwrap();
+
+        let index = Storage::open_mmap(&path).unwrap();
+        assert!(index.filters[0].1.filter.is_borrowed());
+        assert_eq!(index.search("mmap".into(), 0)[0].0, "Mapped");
+        drop(index);
+        std::fs::remove_file(&path).unwrap();
+        assert!(matches!(
+            Storage::open_mmap(&path),
+            Err(StorageError::Io(_))
+        ));
+    }
+}
@@ -0,0 +1,17 @@
+//! The stable part of the API: `use tinysearch::prelude::*;`
+//!
+//! These items follow semantic versioning, so they only change in breaking ways
+//! with a new major version. The rest of the public API, like [`Filter`](crate::Filter),
+//! [`Typos`](crate::Typos), the fields of [`Storage`], and the errors wrapped by
+//! [`StorageError`], exposes the xorf and bincode types the storage is built from.
+//! It's there for the CLI and the generated engine, and may change with any new
+//! storage version.
+
+#[cfg(feature = "mmap")]
+pub use crate::SearchIndex;
+pub use crate::{
+    post_section, search_indexes, Analyzer, Compression, Highlights, IndexHooks, Joiners,
+    LabeledResult, Meta, Page, PostId, QueryMode, SearchOptions, SearchResult, SearchResults, Sort,
+    Storage, StorageError, StorageStats, TieBreak, CANONICAL_KEY, EXCERPT_KEY, MAX_RELATED_QUERIES,
+    SECTION_KEY,
+};
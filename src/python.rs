@@ -0,0 +1,147 @@
+//! Python module for building and searching indexes in-process, e.g. from the
+//! build scripts of Pelican or MkDocs sites, instead of running the CLI.
+//!
+//! Build it as a wheel with
+//! `maturin build --release --features python,pyo3/extension-module`, and install
+//! the wheel with pip. The module is named `tinysearch`.
+
+// Flags the error conversions the pymethods macro generates
+#![allow(clippy::useless_conversion)]
+
+use pyo3::exceptions::{PyKeyError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::{PyBytes, PyDict};
+use std::cell::RefCell;
+
+use crate::prelude::{Analyzer, IndexHooks, Meta, PostId, Storage, StorageStats};
+
+/// A post given as a dict like `{"title": ..., "url": ..., "body": ..., "meta": {...}}`,
+/// where `body` and `meta` are optional
+fn post(dict: &Bound<'_, PyDict>) -> PyResult<(PostId, String)> {
+    let required = |key: &str| -> PyResult<String> {
+        dict.get_item(key)?
+            .ok_or_else(|| PyKeyError::new_err(format!("post without {}", key)))?
+            .extract()
+    };
+    let body: Option<String> = match dict.get_item("body")? {
+        Some(body) => body.extract()?,
+        None => None,
+    };
+    let meta: Option<Meta> = match dict.get_item("meta")? {
+        Some(meta) => meta.extract()?,
+        None => None,
+    };
+    let post_id = (
+        required("title")?,
+        required("url")?,
+        meta.unwrap_or_default(),
+    );
+    Ok((post_id, body.unwrap_or_default()))
+}
+
+/// The stats of a storage as a dict, e.g. `{"posts": 42, ...}`
+fn stats<'py>(py: Python<'py>, stats: &StorageStats) -> PyResult<Bound<'py, PyDict>> {
+    let dict = PyDict::new_bound(py);
+    dict.set_item("posts", stats.posts)?;
+    dict.set_item("posts_with_meta", stats.posts_with_meta)?;
+    dict.set_item("filter_bytes", stats.filter_bytes)?;
+    dict.set_item("typo_filter_bytes", stats.typo_filter_bytes)?;
+    dict.set_item("max_typo_distance", stats.max_typo_distance)?;
+    dict.set_item("posts_per_type", stats.posts_per_type.clone())?;
+    Ok(dict)
+}
+
+#[pyclass]
+struct TinySearch;
+
+#[pymethods]
+impl TinySearch {
+    /// Build an index from a list of posts, returning the storage file, which the
+    /// WASM engine or `search` can load. `digits` keeps digits in words, and `joiners`
+    /// lists the punctuation that joins words, like `--digits` and `--joiners` of the CLI.
+    ///
+    /// `on_post_tokenized` is called with `(title, url, meta)` and the set of words of
+    /// each post; posts it returns `False` for are left out. `on_index_built` is called
+    /// with the stats of the index, e.g. `{"posts": 42, ...}`. Exceptions they raise
+    /// stop the build.
+    #[staticmethod]
+    #[pyo3(signature = (
+        posts,
+        digits = false,
+        joiners = "",
+        on_post_tokenized = None,
+        on_index_built = None,
+    ))]
+    fn build_index<'py>(
+        py: Python<'py>,
+        posts: Vec<Bound<'py, PyDict>>,
+        digits: bool,
+        joiners: &str,
+        on_post_tokenized: Option<PyObject>,
+        on_index_built: Option<PyObject>,
+    ) -> PyResult<Bound<'py, PyBytes>> {
+        let posts = posts.iter().map(post).collect::<PyResult<Vec<_>>>()?;
+        let analyzer = Analyzer {
+            digits,
+            joiners: joiners.parse().map_err(PyValueError::new_err)?,
+            ..Analyzer::default()
+        };
+        // The hooks can't fail the build themselves, so the first exception is kept
+        // and raised once the storage is built
+        let error: RefCell<Option<PyErr>> = RefCell::new(None);
+        let failed = &error;
+        let mut hooks = IndexHooks::default();
+        if let Some(hook) = on_post_tokenized {
+            hooks = hooks.on_post_tokenized(move |post_id: &PostId, words| {
+                if failed.borrow().is_some() {
+                    return false;
+                }
+                match hook.call1(py, (post_id.clone(), words.clone())) {
+                    Ok(keep) => !matches!(keep.extract::<Option<bool>>(py), Ok(Some(false))),
+                    Err(e) => {
+                        failed.replace(Some(e));
+                        false
+                    }
+                }
+            });
+        }
+        if let Some(hook) = on_index_built {
+            hooks = hooks.on_index_built(move |storage| {
+                if failed.borrow().is_some() {
+                    return;
+                }
+                let result = stats(py, &storage.stats()).and_then(|stats| hook.call1(py, (stats,)));
+                if let Err(e) = result {
+                    failed.replace(Some(e));
+                }
+            });
+        }
+        let storage = Storage::from_texts_with_hooks(posts, analyzer, hooks);
+        if let Some(e) = error.into_inner() {
+            return Err(e);
+        }
+        let bytes = storage
+            .to_bytes()
+            .map_err(|e| PyValueError::new_err(e.to_string()))?;
+        Ok(PyBytes::new_bound(py, &bytes))
+    }
+
+    /// Search a storage file, returning at most `n` results (all of them for 0) as
+    /// `(title, url, meta, score)` tuples
+    #[staticmethod]
+    #[pyo3(signature = (index, query, n = 10))]
+    fn search(index: &[u8], query: String, n: usize) -> PyResult<Vec<(String, String, Meta, f32)>> {
+        let storage =
+            Storage::from_bytes(index).map_err(|e| PyValueError::new_err(e.to_string()))?;
+        Ok(storage
+            .search_with_scores(query, n)
+            .into_iter()
+            .map(|((title, url, meta), score)| (title.clone(), url.clone(), meta.clone(), score))
+            .collect())
+    }
+}
+
+#[pymodule]
+fn tinysearch(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<TinySearch>()
+}
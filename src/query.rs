@@ -0,0 +1,184 @@
+//! Parser for the advanced query syntax, see [`QueryMode::Advanced`](crate::QueryMode).
+//!
+//! Supported are bare terms, `"exact phrases"`, `-excluded` terms or phrases, and the
+//! `AND` and `OR` operators. `AND` binds stronger than `OR`; terms without an operator
+//! between them are combined with `OR`, just like in the simple query mode.
+//! Excluded terms apply to the whole query, no matter where they appear.
+
+/// A term or a phrase
+type Unit = Vec<String>;
+
+/// A query in disjunctive normal form: it matches if any of its groups matches
+/// and none of the excluded units does. A group matches if all of its units match.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct Query {
+    pub groups: Vec<Vec<Unit>>,
+    pub excluded: Vec<Unit>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Token {
+    Unit(Unit),
+    Excluded(Unit),
+    And,
+    Or,
+}
+
+impl Query {
+    /// All words that contribute to the score, i.e. all words which aren't excluded
+    pub fn positive_words(&self) -> Vec<String> {
+        let mut words: Vec<String> = Vec::new();
+        for word in self.groups.iter().flatten().flatten() {
+            if !words.contains(word) {
+                words.push(word.clone());
+            }
+        }
+        words
+    }
+
+    /// All words of the query, including excluded ones
+    pub fn words(&self) -> impl Iterator<Item = &String> {
+        self.groups
+            .iter()
+            .flatten()
+            .chain(self.excluded.iter())
+            .flatten()
+    }
+
+    /// Evaluate the query against a post, given a membership test for its words
+    pub fn matches(&self, contains: impl Fn(&str) -> bool) -> bool {
+        // Phrases can only be approximated: the filters don't store word positions
+        let unit_matches = |unit: &Unit| unit.iter().all(|word| contains(word));
+        self.groups
+            .iter()
+            .any(|group| group.iter().all(unit_matches))
+            && !self.excluded.iter().any(unit_matches)
+    }
+}
+
+/// Parse a query, splitting each term or phrase into words with `analyze`
+pub(crate) fn parse(query: &str, analyze: impl Fn(&str) -> Vec<String>) -> Query {
+    let mut groups: Vec<Vec<Unit>> = vec![Vec::new()];
+    let mut excluded = Vec::new();
+    let mut pending_and = false;
+    for token in lex(query, analyze) {
+        match token {
+            Token::And => pending_and = true,
+            Token::Or => pending_and = false,
+            Token::Excluded(unit) => {
+                excluded.push(unit);
+                pending_and = false;
+            }
+            Token::Unit(unit) => {
+                let current = groups.last_mut().expect("at least one group");
+                if !pending_and && !current.is_empty() {
+                    groups.push(Vec::new());
+                }
+                groups.last_mut().expect("at least one group").push(unit);
+                pending_and = false;
+            }
+        }
+    }
+    groups.retain(|group| !group.is_empty());
+    Query { groups, excluded }
+}
+
+fn lex(query: &str, analyze: impl Fn(&str) -> Vec<String>) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut chars = query.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+            continue;
+        }
+        let excluded = c == '-';
+        if excluded {
+            chars.next();
+        }
+        let raw: String = if chars.peek() == Some(&'"') {
+            chars.next();
+            chars.by_ref().take_while(|&c| c != '"').collect()
+        } else {
+            let mut word = String::new();
+            while let Some(&c) = chars.peek() {
+                if c.is_whitespace() {
+                    break;
+                }
+                word.push(c);
+                chars.next();
+            }
+            if !excluded && word == "AND" {
+                tokens.push(Token::And);
+                continue;
+            }
+            if !excluded && word == "OR" {
+                tokens.push(Token::Or);
+                continue;
+            }
+            word
+        };
+        let words: Vec<String> = raw
+            .to_lowercase()
+            .split_whitespace()
+            .flat_map(&analyze)
+            .collect();
+        if !words.is_empty() {
+            tokens.push(if excluded {
+                Token::Excluded(words)
+            } else {
+                Token::Unit(words)
+            });
+        }
+    }
+    tokens
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(query: &str) -> Query {
+        super::parse(query, |token| vec![token.to_string()])
+    }
+
+    fn unit(words: &[&str]) -> Unit {
+        words.iter().map(|w| w.to_string()).collect()
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            parse("rust AND wasm OR \"Static Site\" -jekyll"),
+            Query {
+                groups: vec![
+                    vec![unit(&["rust"]), unit(&["wasm"])],
+                    vec![unit(&["static", "site"])],
+                ],
+                excluded: vec![unit(&["jekyll"])],
+            }
+        );
+        assert_eq!(
+            parse("rust AND -\"go lang\" AND wasm"),
+            Query {
+                groups: vec![vec![unit(&["rust"]), unit(&["wasm"])]],
+                excluded: vec![unit(&["go", "lang"])],
+            }
+        );
+        assert_eq!(parse("  AND \"\" OR ").groups.len(), 0);
+    }
+
+    #[test]
+    fn test_matches() {
+        let post = ["rust", "static", "site"];
+        let contains = |word: &str| post.contains(&word);
+        assert!(parse("rust AND site").matches(contains));
+        assert!(!parse("rust AND wasm").matches(contains));
+        assert!(parse("rust AND wasm OR static").matches(contains));
+        assert!(parse("\"static site\"").matches(contains));
+        assert!(!parse("\"static wasm\"").matches(contains));
+        assert!(!parse("rust AND -site").matches(contains));
+        assert!(parse("rust AND -wasm").matches(contains));
+        assert!(!parse("rust OR wasm -site").matches(contains));
+        assert!(!parse("-wasm").matches(contains));
+    }
+}